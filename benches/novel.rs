@@ -0,0 +1,92 @@
+// Copyright (C) 2015 Élisabeth HENRY.
+//
+// This file is part of Caribon.
+//
+// Caribon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Caribon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Measures repetition detection on a novel-sized text, with and without fuzzy string
+//! matching.
+//!
+//! Unlike the benchmarks of `bench.rs`, this one works with a stable compiler: run it with
+//! `cargo bench --bench novel`.
+
+extern crate caribon;
+
+use caribon::{Ast, Parser};
+
+use std::time::{Duration, Instant};
+
+/// Number of runs of each measure; the fastest one is kept
+const RUNS: u32 = 10;
+
+/// Generates a novel-sized text (about 100,000 words) with a large vocabulary
+fn get_novel() -> String {
+    const SYLLABLES: &[&str] = &["ca", "ri", "bon", "ta", "me", "lu", "po", "ver", "sin", "ga",
+                                 "dor", "e", "an", "tre", "mo", "li", "su", "pha", "rel", "o"];
+    let mut seed: u32 = 42;
+    let mut random = move |n: u32| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (seed >> 16) % n
+    };
+    // About 10,000 different words
+    let vocabulary: Vec<String> = (0..10000)
+        .map(|_| (0..2 + random(3)).map(|_| SYLLABLES[random(20) as usize]).collect())
+        .collect();
+    let mut s = String::new();
+    for i in 0..100000 {
+        // Common words are more frequent
+        let n = random(10000) * random(10000) / 10000;
+        s.push_str(&vocabulary[n as usize]);
+        s.push_str(if i % 15 == 14 { ". " } else { " " });
+    }
+    s
+}
+
+/// Returns the shortest time taken by `f` on a copy of `ast`
+fn measure<F: Fn(&mut Ast)>(ast: &Ast, f: F) -> Duration {
+    (0..RUNS).map(|_| {
+                 let mut ast = ast.clone();
+                 let start = Instant::now();
+                 f(&mut ast);
+                 start.elapsed()
+             })
+             .min()
+             .unwrap()
+}
+
+fn main() {
+    let text = get_novel();
+    let fuzzy = |value| Parser::new("english").unwrap().with_fuzzy(value);
+    let (exact, approximate) = (fuzzy(None), fuzzy(Some(0.2)));
+    let ast = exact.tokenize(&text).unwrap();
+    let ratio = |a: Duration, b: Duration| a.as_secs_f64() / b.as_secs_f64();
+    let print = |name: &str, exact: Duration, approximate: Duration| {
+        println!("{}: {:?} without fuzzy matching, {:?} with --fuzzy=0.2 ({:.2} times slower)",
+                 name, exact, approximate, ratio(approximate, exact));
+    };
+
+    // What the program does by default: tokenize, detect local repetitions and print HTML
+    let run = |parser: &Parser| measure(&ast, |_| {
+        let mut ast = parser.tokenize(&text).unwrap();
+        parser.detect_local(&mut ast, 1.9);
+        parser.ast_to_html(&ast, true);
+    });
+    print("whole run", run(&exact), run(&approximate));
+    print("local detection",
+          measure(&ast, |ast| exact.detect_local(ast, 1.9)),
+          measure(&ast, |ast| approximate.detect_local(ast, 1.9)));
+    print("global detection",
+          measure(&ast, |ast| exact.detect_global(ast, 0.01)),
+          measure(&ast, |ast| approximate.detect_global(ast, 0.01)));
+}
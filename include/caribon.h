@@ -0,0 +1,111 @@
+/* Copyright (C) 2015, 2016 Élisabeth HENRY.
+ *
+ * This file is part of Caribon.
+ *
+ * Caribon is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Lesser General Public License as published
+ * by the Free Software Foundation, either version 2.1 of the License, or
+ * (at your option) any later version.
+ *
+ * Caribon is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Lesser General Public License for more details.
+ *
+ * You should have received a copy of the GNU Lesser General Public License
+ * along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/* C interface to Caribon, implemented in src/lib/capi.rs. The tests of that module
+ * check that this file matches it. */
+
+#ifndef CARIBON_H
+#define CARIBON_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Input formats, for caribon_parser_set_input_format */
+#define CARIBON_INPUT_TEXT 0
+#define CARIBON_INPUT_HTML 1
+#define CARIBON_INPUT_MARKDOWN 2
+#define CARIBON_INPUT_LATEX 3
+
+/* Distance units, for caribon_parser_set_distance_unit */
+#define CARIBON_DISTANCE_WORDS 0
+#define CARIBON_DISTANCE_CHARACTERS 1
+#define CARIBON_DISTANCE_SENTENCES 2
+#define CARIBON_DISTANCE_PARAGRAPHS 3
+#define CARIBON_DISTANCE_SECTIONS 4
+
+/* Metrics, for caribon_parser_set_metric */
+#define CARIBON_METRIC_LEVENSHTEIN 0
+#define CARIBON_METRIC_DAMERAU_LEVENSHTEIN 1
+#define CARIBON_METRIC_JARO_WINKLER 2
+
+/* Opaque parser handle */
+typedef struct CaribonParser caribon_parser;
+
+/* A repetition */
+typedef struct {
+    size_t offset;      /* beginning, in characters */
+    size_t length;      /* length, in characters */
+    size_t byte_offset; /* beginning, in bytes */
+    size_t byte_length; /* length, in bytes */
+    float value;        /* "severity" of the repetition */
+    char *colour;       /* how the repetition should be displayed */
+} caribon_repetition;
+
+/* An array of repetitions */
+typedef struct {
+    caribon_repetition *repetitions;
+    size_t len;
+} caribon_result;
+
+/* Message of the last error in this thread, or NULL. Owned by the library. */
+const char *caribon_last_error(void);
+
+/* Creates a parser for the given language; returns NULL on error. */
+caribon_parser *caribon_parser_new(const char *lang);
+void caribon_parser_free(caribon_parser *parser);
+
+/* Setters; they return 0 on success and -1 on error. */
+int caribon_parser_set_html(caribon_parser *parser, int html);
+int caribon_parser_set_input_format(caribon_parser *parser, int format);
+int caribon_parser_set_fuzzy(caribon_parser *parser, float fuzzy); /* < 0 to deactivate */
+int caribon_parser_set_max_distance(caribon_parser *parser, unsigned int max_distance);
+int caribon_parser_set_distance_unit(caribon_parser *parser, int unit);
+int caribon_parser_set_metric(caribon_parser *parser, int metric, int ignore_accents);
+int caribon_parser_set_theme(caribon_parser *parser, const char *theme); /* name or file */
+int caribon_parser_set_phrase_length(caribon_parser *parser, unsigned int min, unsigned int max);
+int caribon_parser_set_phrase_skip_ignored(caribon_parser *parser, int skip);
+int caribon_parser_set_html_attributes(caribon_parser *parser, const char *list);
+int caribon_parser_set_ignore_proper(caribon_parser *parser, int proper);
+int caribon_parser_set_ignored(caribon_parser *parser, const char *list);
+int caribon_parser_add_ignored(caribon_parser *parser, const char *list);
+int caribon_parser_add_ignored_pattern(caribon_parser *parser, const char *pattern);
+int caribon_parser_add_ignored_file(caribon_parser *parser, const char *path);
+
+/* Detects repetitions in text (UTF-8). Global repetitions are only detected if
+ * global_threshold > 0. Returns NULL on error; the result must be freed with
+ * caribon_result_free. */
+caribon_result *caribon_detect(const caribon_parser *parser,
+                               const char *text,
+                               float threshold,
+                               float global_threshold);
+/* Same, but also detects repeated expressions if phrase_threshold > 0. */
+caribon_result *caribon_detect_with_phrases(const caribon_parser *parser,
+                                            const char *text,
+                                            float threshold,
+                                            float global_threshold,
+                                            float phrase_threshold);
+void caribon_result_free(caribon_result *result);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CARIBON_H */
@@ -18,34 +18,50 @@
 use std::env;
 use std::process::exit;
 use std::fs::File;
-use std::error::Error;
 use std::io;
 use std::io::Read;
 use std::io::Write;
-use caribon::Parser;
+use caribon::{Parser, Theme};
 
-const ARG_LANG:&'static str = "--language=";
-const ARG_LANG_SHORT:&'static str = "-d";
-const ARG_THRESHOLD:&'static str = "--threshold=";
-const ARG_MAX_DISTANCE:&'static str = "--max-distance=";
-const ARG_GLOBAL_THRESHOLD:&'static str = "--global-threshold=";
-const ARG_INPUT_FORMAT:&'static str = "--input-format=";
-const ARG_OUTPUT_FORMAT:&'static str = "--output-format=";
-const ARG_IGNORE_PROPER:&'static str = "--ignore-proper=";
-const ARG_USAGE:&'static str = "--help";
-const ARG_INPUT:&'static str = "--input=";
-const ARG_OUTPUT:&'static str = "--output=";
-const ARG_VERSION:&'static str = "--version";
-const ARG_LIST_LANGUAGES:&'static str = "--list-languages";
-const ARG_IGNORE:&'static str = "--ignore=";
-const ARG_ADD_IGNORED:&'static str = "--add-ignored=";
-const ARG_FUZZY:&'static str = "--fuzzy=";
-const ARG_STATS:&'static str = "--print-stats";
-const ARG_ISPELL:&'static str = "-a";
-const ARG_ISPELL_VERSION:&'static str = "-v";
-const ARG_ISPELL_VERSION_VARIANT:&'static str = "-vv";
-const ARG_LIST:&'static str = "-l";
-const ARG_ISPELL_IGNORE:&'static [&'static str] = &["-m", "-B"];
+const ARG_LANG: &str = "--language=";
+const ARG_LANG_SHORT: &str = "-d";
+const ARG_THRESHOLD: &str = "--threshold=";
+const ARG_MAX_DISTANCE: &str = "--max-distance=";
+const ARG_DISTANCE_UNIT: &str = "--distance-unit=";
+const ARG_GLOBAL_THRESHOLD: &str = "--global-threshold=";
+const ARG_PHRASE_THRESHOLD: &str = "--phrase-threshold=";
+const ARG_PHRASE_LENGTH: &str = "--phrase-length=";
+const ARG_PHRASE_SKIP_IGNORED: &str = "--phrase-skip-ignored=";
+const ARG_INPUT_FORMAT: &str = "--input-format=";
+const ARG_OUTPUT_FORMAT: &str = "--output-format=";
+const ARG_LATEX_COMMAND: &str = "--latex-command=";
+const ARG_HTML_ATTRIBUTES: &str = "--html-attributes=";
+const ARG_THEME: &str = "--theme=";
+const ARG_COLOUR: &str = "--colour=";
+const ARG_LEGEND: &str = "--legend";
+const ARG_GUTTER: &str = "--gutter";
+const ARG_IGNORE_PROPER: &str = "--ignore-proper=";
+const ARG_USAGE: &str = "--help";
+const ARG_INPUT: &str = "--input=";
+const ARG_OUTPUT: &str = "--output=";
+const ARG_VERSION: &str = "--version";
+const ARG_LIST_LANGUAGES: &str = "--list-languages";
+const ARG_PRINT_IGNORED: &str = "--print-ignored";
+const ARG_IGNORE: &str = "--ignore=";
+const ARG_ADD_IGNORED: &str = "--add-ignored=";
+const ARG_IGNORE_FILE: &str = "--ignore-file=";
+const ARG_ADD_IGNORED_FILE: &str = "--add-ignored-file=";
+const ARG_PROJECT_IGNORED: &str = "--project-ignored=";
+const ARG_IGNORE_PATTERN: &str = "--ignore-pattern=";
+const ARG_FUZZY: &str = "--fuzzy=";
+const ARG_FUZZY_METRIC: &str = "--fuzzy-metric=";
+const ARG_IGNORE_ACCENTS: &str = "--ignore-accents=";
+const ARG_STATS: &str = "--print-stats";
+const ARG_ISPELL: &str = "-a";
+const ARG_ISPELL_VERSION: &str = "-v";
+const ARG_ISPELL_VERSION_VARIANT: &str = "-vv";
+const ARG_LIST: &str = "-l";
+const ARG_ISPELL_IGNORE: &[&str] = &["-m", "-B"];
 
 pub fn list_languages() {
     println!("Supported languages:");
@@ -72,6 +88,8 @@ Options:
   {}: displays this message
   {}: displays program version
   {}: lists the implemented languages
+  {}: prints the list of ignored words (with the language and
+      the options changing it) and exits
   {}: in addition to detecting repetition, displays some
       statistics the input text
   {}[language] (or {} language): sets the language of the text (default: french)
@@ -81,9 +99,23 @@ Options:
       separated by spaces or comma (default: the builtin list 
       that depends on the language)
   {}[string]: adds words contained in the string to the list of 
-      ignored words (default: none)
+      ignored words; a word preceded by '~' (e.g. ~play) also ignores
+      its other forms (e.g. plays, played) (default: none)
+  {}[filename]: like {}, but reads the words in a file, one
+      per line, where '#' starts a comment (can be given several times)
+  {}[filename]: like {}, but reads the words in a file
+      (can be given several times)
+  {}[regex]: ignores words matching a regular expression, e.g.
+      [0-9]+(st|nd|rd|th) (can be given several times)
+  {}[true|false]: if true, adds the words of the first '.caribon-ignore'
+      file found in the directory of the input file (or the current
+      directory for stdin) or in its parents (default: true if an
+      input file is given, false for stdin)
   {}[value]: sets max distance to be considered a repetition 
-      (in words) (default: 50)
+      (default: 50 words, 300 characters, or 1 sentence, paragraph
+      or section, i.e. the same one)
+  {}[words|characters|sentences|paragraphs|sections]: sets the unit
+      of the max distance; sections begin at each heading (default: words)
   {}[value]: sets threshold value for underlining local repetitions
       (default: 1.9)
   {}[value|none]: activate global repetition detector and sets 
@@ -92,22 +124,46 @@ Options:
       in the text, e.g. a threshold of 0.01 means that a word must
       represent at least 1% of the total words in the text to be 
       underlined) (default: not activated)
-  {}[text|html]: sets input format 
+  {}[value|none]: activate repeated expressions detector and sets
+      threshold value for underlining them (default: not activated)
+  {}[min-max]: sets the minimal and maximal number of words of
+      an expression (default: 2-4)
+  {}[true|false]: if true, ignored words are left out of expressions,
+      so 'end of the day' and 'end of a day' are the same (default: false)
+  {}[text|html|markdown|latex]: sets input format 
       (default: text, depends on file extension)
-  {}[terminal|html]|markdown]: sets output format 
-      (default: terminal, depends on file extension)
+  {}[list]: with HTML input, comma-separated list of attributes
+      whose value is checked, e.g. alt,title (default: none)
+  {}[terminal|html|report|markdown|latex|json]: sets output format 
+      (default: terminal, depends on file extension; report is an
+      interactive HTML page listing repetitions and statistics)
+  {}[name]: sets the LaTeX command wrapping repetitions, taking
+      the colour and the word as arguments (default: caribon)
+  {}[{}|filename]: sets the colours and styles
+      of repetitions, either a built-in theme or a theme file (default: default)
+  {}[auto|never|16|256|truecolour]: sets the colours used by terminal output;
+      'auto' only uses them if the output is a terminal, guessing how many
+      colours it supports, unless NO_COLOR is set (default: auto)
+  {}: with terminal output, prints a legend of the colours after the text
+  {}: with terminal output, prints the number of each line
   {}[true|false]: if true, try to detect proper nouns and don't 
       count them (default: false)
-  {}[value|none]: activate fuzzy string matching; value must be between 
+  {}[value|none]: sets fuzzy string matching; value must be between 
       0.0 and 1.0 and corresponds to the maximal 'difference' between 
       two words until they are no more considered identical (e.g. 0.25
-      means that two words must have no more than 25% of difference) 
-      (default: not activated)
+      means that two words must have no more than 25% of difference),
+      'none' deactivates it (default: 0.2)
+  {}[levenshtein|damerau-levenshtein|jaro-winkler]: sets the way
+      the 'difference' between two words is computed for fuzzy string
+      matching (default: levenshtein)
+  {}[true|false]: if true, accents are not taken into account by
+      fuzzy string matching (default: false)
   {}: try to run Caribon with ispell compatibility mode",
              env!("CARGO_PKG_VERSION"),
              ARG_USAGE,
              ARG_VERSION,
              ARG_LIST_LANGUAGES,
+             ARG_PRINT_IGNORED,
              ARG_STATS,
              ARG_LANG,
              ARG_LANG_SHORT,
@@ -115,13 +171,32 @@ Options:
              ARG_OUTPUT,
              ARG_IGNORE,
              ARG_ADD_IGNORED,
+             ARG_IGNORE_FILE,
+             ARG_IGNORE,
+             ARG_ADD_IGNORED_FILE,
+             ARG_ADD_IGNORED,
+             ARG_IGNORE_PATTERN,
+             ARG_PROJECT_IGNORED,
              ARG_MAX_DISTANCE,
+             ARG_DISTANCE_UNIT,
              ARG_THRESHOLD,
              ARG_GLOBAL_THRESHOLD,
+             ARG_PHRASE_THRESHOLD,
+             ARG_PHRASE_LENGTH,
+             ARG_PHRASE_SKIP_IGNORED,
              ARG_INPUT_FORMAT,
+             ARG_HTML_ATTRIBUTES,
              ARG_OUTPUT_FORMAT,
+             ARG_LATEX_COMMAND,
+             ARG_THEME,
+             Theme::list_names().join("|"),
+             ARG_COLOUR,
+             ARG_LEGEND,
+             ARG_GUTTER,
              ARG_IGNORE_PROPER,
              ARG_FUZZY,
+             ARG_FUZZY_METRIC,
+             ARG_IGNORE_ACCENTS,
              ARG_ISPELL
     );
 }
@@ -130,18 +205,35 @@ pub struct Config {
     pub lang: String,
     pub threshold: f32,
     pub global_threshold: Option<f32>,
-    pub max_distance: u32,
+    pub phrase_threshold: Option<f32>,
+    pub phrase_length: (usize, usize),
+    pub phrase_skip_ignored: bool,
+    pub max_distance: Option<u32>,
+    pub distance_unit: String,
     pub input_format: String,
     pub output_format: String,
+    pub latex_command: String,
+    pub html_attributes: String,
+    pub theme: Theme,
+    pub colour: String,
+    pub legend: bool,
+    pub gutter: bool,
     pub ignore_proper: bool,
-    pub input: Box<Read>,
+    pub input: Box<dyn Read>,
     pub input_filename: String,
-    pub output: Box<Write>,
+    pub output: Box<dyn Write>,
     pub output_filename: String,
     pub ignored: String,
     pub add_ignored: String,
+    pub ignored_files: Vec<String>,
+    pub add_ignored_files: Vec<String>,
+    pub ignored_patterns: Vec<String>,
+    pub project_ignored: Option<bool>,
     pub fuzzy: Option<f32>,
+    pub fuzzy_metric: String,
+    pub ignore_accents: bool,
     pub print_stats: bool,
+    pub print_ignored: bool,
     pub ispell: bool,
     pub ispell_list: bool,
 }
@@ -153,9 +245,19 @@ impl Config {
             lang: "french".to_owned(),
             threshold:1.9,
             global_threshold: None,
-            max_distance:50,
+            phrase_threshold: None,
+            phrase_length: (2, 4),
+            phrase_skip_ignored: false,
+            max_distance: None,
+            distance_unit: "words".to_owned(),
             input_format: String::new(),
             output_format: String::new(),
+            latex_command: "caribon".to_owned(),
+            html_attributes: String::new(),
+            theme: Theme::default(),
+            colour: "auto".to_owned(),
+            legend: false,
+            gutter: false,
             ignore_proper:false,
             input: Box::new(io::stdin()),
             input_filename: String::new(),
@@ -163,8 +265,15 @@ impl Config {
             output_filename: String::new(),
             ignored: String::new(),
             add_ignored: String::new(),
-            fuzzy: None,
+            ignored_files: vec![],
+            add_ignored_files: vec![],
+            ignored_patterns: vec![],
+            project_ignored: None,
+            fuzzy: Some(0.2),
+            fuzzy_metric: "levenshtein".to_owned(),
+            ignore_accents: false,
             print_stats: false,
+            print_ignored: false,
             ispell: false,
             ispell_list: false
         }
@@ -189,6 +298,11 @@ impl Config {
         if config.input_format.is_empty() {
             if config.input_filename.ends_with(".html") {
                 config.input_format = "html".to_owned();
+            } else if config.input_filename.ends_with(".md") ||
+                      config.input_filename.ends_with(".markdown") {
+                config.input_format = "markdown".to_owned();
+            } else if config.input_filename.ends_with(".tex") {
+                config.input_format = "latex".to_owned();
             } else {
                 config.input_format = "text".to_owned();
             }
@@ -198,6 +312,10 @@ impl Config {
                 config.output_format = "html".to_owned();
             } else if config.output_filename.ends_with(".md") {
                 config.output_format = "markdown".to_owned();
+            } else if config.output_filename.ends_with(".tex") {
+                config.output_format = "latex".to_owned();
+            } else if config.output_filename.ends_with(".json") {
+                config.output_format = "json".to_owned();
             } else {
                 config.output_format = "terminal".to_owned();
             }
@@ -212,8 +330,7 @@ impl Config {
     pub fn parse_arg(&mut self, arg:&str) -> bool {
         if arg == ARG_LANG_SHORT {
             return true;
-        } else if arg.starts_with(ARG_OUTPUT) {
-            let option = &arg[ARG_OUTPUT.len()..];
+        } else if let Some(option) = arg.strip_prefix(ARG_OUTPUT) {
             let result = File::create(option);
             match result {
                 Ok(f) => {
@@ -221,12 +338,11 @@ impl Config {
                     self.output_filename = option.to_owned();
                 },
                 Err(e) => {
-                    println!("Error opening file {}: {}", option, e.description());
+                    println!("Error opening file {}: {}", option, e);
                     exit(0);
                 }
             }
-        } else if arg.starts_with(ARG_INPUT) {
-            let option = &arg[ARG_INPUT.len()..];
+        } else if let Some(option) = arg.strip_prefix(ARG_INPUT) {
             let result = File::open(option);
             match result {
                 Ok(f) => {
@@ -234,15 +350,13 @@ impl Config {
                     self.input_filename = option.to_owned();
                 },
                 Err(e) => {
-                    println!("Error opening file {}: {}", option, e.description());
+                    println!("Error opening file {}: {}", option, e);
                     exit(0);
                 }
             }
-        } else if arg.starts_with(ARG_LANG) {
-            let option = &arg[ARG_LANG.len()..];
+        } else if let Some(option) = arg.strip_prefix(ARG_LANG) {
             self.lang = option.to_owned();
-        } else if arg.starts_with(ARG_THRESHOLD) {
-            let option = &arg[ARG_THRESHOLD.len()..];
+        } else if let Some(option) = arg.strip_prefix(ARG_THRESHOLD) {
             self.threshold = match option.parse() {
                 Ok(x) => x,
                 Err(_) => {
@@ -250,8 +364,7 @@ impl Config {
                     exit(0);
                 }
             }
-        } else if arg.starts_with(ARG_FUZZY) {
-            let option = &arg[ARG_FUZZY.len()..];
+        } else if let Some(option) = arg.strip_prefix(ARG_FUZZY) {
             self.fuzzy = if option == "none" {
                 None
             } else {
@@ -263,8 +376,7 @@ impl Config {
                     }
                 }
             }
-        } else if arg.starts_with(ARG_GLOBAL_THRESHOLD) {
-            let option = &arg[ARG_GLOBAL_THRESHOLD.len()..];
+        } else if let Some(option) = arg.strip_prefix(ARG_GLOBAL_THRESHOLD) {
             self.global_threshold = if option == "none" {
                 None
             } else {
@@ -276,38 +388,127 @@ impl Config {
                     }
                 }
             }
-        } else if arg.starts_with(ARG_MAX_DISTANCE) {
-            let option = &arg[ARG_MAX_DISTANCE.len()..];
+        } else if let Some(option) = arg.strip_prefix(ARG_PHRASE_THRESHOLD) {
+            self.phrase_threshold = if option == "none" {
+                None
+            } else {
+                match option.parse() {
+                    Ok(x) => Some(x),
+                    Err(_) => {
+                        println!("Error passing argument to phrase threshold: {}", option);
+                        exit(0);
+                    }
+                }
+            }
+        } else if let Some(option) = arg.strip_prefix(ARG_PHRASE_LENGTH) {
+            let bounds: Vec<_> = option.split('-').map(|s| s.parse::<usize>()).collect();
+            self.phrase_length = match bounds.as_slice() {
+                [Ok(min), Ok(max)] if *min >= 2 && min <= max => (*min, *max),
+                [Ok(n)] if *n >= 2 => (*n, *n),
+                _ => {
+                    println!("Error passing argument to phrase length: {}", option);
+                    exit(0);
+                }
+            }
+        } else if let Some(option) = arg.strip_prefix(ARG_PHRASE_SKIP_IGNORED) {
+            match option {
+                "true" => self.phrase_skip_ignored = true,
+                "false" => self.phrase_skip_ignored = false,
+                _ => {
+                    println!("Wrong argument to phrase_skip_ignored: expected 'true' or 'false', received: {}", option);
+                    exit(0);
+                }
+            }
+        } else if let Some(option) = arg.strip_prefix(ARG_MAX_DISTANCE) {
             self.max_distance = match option.parse() {
-                Ok(x) => x,
+                Ok(x) => Some(x),
                 Err(_) => {
                     println!("Error passing argument to max_distance: {}", option);
                     exit(0);
                 }
             }
-        } else if arg.starts_with(ARG_INPUT_FORMAT) {
-            let option = &arg[ARG_INPUT_FORMAT.len()..];
+        } else if let Some(option) = arg.strip_prefix(ARG_DISTANCE_UNIT) {
+            match option {
+                "words" | "characters" | "sentences" | "paragraphs" | "sections" => self.distance_unit = option.to_owned(),
+                _ => {
+                    println!("Wrong argument to {}: expected 'words', 'characters', 'sentences', 'paragraphs' or 'sections', received: {}", ARG_DISTANCE_UNIT, option);
+                    exit(0);
+                }
+            }
+        } else if let Some(option) = arg.strip_prefix(ARG_INPUT_FORMAT) {
             match option {
                 "html" => self.input_format = option.to_owned(),
                 "text" => self.input_format = option.to_owned(),
+                "markdown" => self.input_format = option.to_owned(),
+                "latex" => self.input_format = option.to_owned(),
                 _ => {
-                    println!("Wrong argument to {}: expected 'html' or 'text', received: {}", ARG_INPUT_FORMAT, option);
+                    println!("Wrong argument to {}: expected 'html', 'text', 'markdown' or 'latex', received: {}", ARG_INPUT_FORMAT, option);
                     exit(0);
                 }
             }
-        } else if arg.starts_with(ARG_OUTPUT_FORMAT) {
-            let option = &arg[ARG_OUTPUT_FORMAT.len()..];
+        } else if let Some(option) = arg.strip_prefix(ARG_OUTPUT_FORMAT) {
             match option {
                 "html" => self.output_format = option.to_owned(),
+                "report" => self.output_format = option.to_owned(),
                 "terminal" => self.output_format = option.to_owned(),
                 "markdown" => self.output_format = option.to_owned(),
+                "latex" => self.output_format = option.to_owned(),
+                "json" => self.output_format = option.to_owned(),
+                _ => {
+                    println!("Wrong argument to {}: expected 'html', 'report', 'terminal', 'markdown', 'latex' or 'json', received: {}", ARG_OUTPUT_FORMAT, option);
+                    exit(0);
+                }
+            }
+        } else if let Some(option) = arg.strip_prefix(ARG_HTML_ATTRIBUTES) {
+            self.html_attributes = option.to_owned();
+        } else if let Some(option) = arg.strip_prefix(ARG_LATEX_COMMAND) {
+            let option = option.trim_start_matches('\\');
+            if option.is_empty() || !option.chars().all(|c| c.is_ascii_alphabetic()) {
+                println!("Wrong argument to {}: expected a command name made of letters, received: {}", ARG_LATEX_COMMAND, option);
+                exit(0);
+            }
+            self.latex_command = option.to_owned();
+        } else if let Some(option) = arg.strip_prefix(ARG_FUZZY_METRIC) {
+            match option {
+                "levenshtein" | "damerau-levenshtein" | "jaro-winkler" => self.fuzzy_metric = option.to_owned(),
                 _ => {
-                    println!("Wrong argument to {}: expected 'html', 'terminal' or 'markdown', received: {}", ARG_OUTPUT_FORMAT, option);
+                    println!("Wrong argument to {}: expected 'levenshtein', 'damerau-levenshtein' or 'jaro-winkler', received: {}", ARG_FUZZY_METRIC, option);
                     exit(0);
                 }
             }
-        } else if arg.starts_with(ARG_IGNORE_PROPER) {
-            let option = &arg[ARG_IGNORE_PROPER.len()..];
+        } else if let Some(option) = arg.strip_prefix(ARG_IGNORE_ACCENTS) {
+            match option {
+                "true" => self.ignore_accents = true,
+                "false" => self.ignore_accents = false,
+                _ => {
+                    println!("Wrong argument to ignore_accents: expected 'true' or 'false', received: {}", option);
+                    exit(0);
+                }
+            }
+        } else if let Some(option) = arg.strip_prefix(ARG_THEME) {
+            self.theme = match Theme::named(option) {
+                Some(theme) => theme,
+                None => match Theme::from_file(option) {
+                    Ok(theme) => theme,
+                    Err(e) => {
+                        println!("Wrong argument to {}: {}", ARG_THEME, e);
+                        exit(0);
+                    }
+                },
+            };
+        } else if let Some(option) = arg.strip_prefix(ARG_COLOUR) {
+            match option {
+                "auto" | "never" | "16" | "256" | "truecolour" => self.colour = option.to_owned(),
+                _ => {
+                    println!("Wrong argument to {}: expected 'auto', 'never', '16', '256' or 'truecolour', received: {}", ARG_COLOUR, option);
+                    exit(0);
+                }
+            }
+        } else if arg == ARG_LEGEND {
+            self.legend = true;
+        } else if arg == ARG_GUTTER {
+            self.gutter = true;
+        } else if let Some(option) = arg.strip_prefix(ARG_IGNORE_PROPER) {
             match option {
                 "true" => self.ignore_proper = true,
                 "false" => self.ignore_proper = false,
@@ -316,12 +517,25 @@ impl Config {
                     exit(0);
                 }
             }
-        } else if arg.starts_with(ARG_IGNORE) {
-            let option = &arg[ARG_IGNORE.len()..];
+        } else if let Some(option) = arg.strip_prefix(ARG_IGNORE) {
             self.ignored = option.to_owned();
-        } else if arg.starts_with(ARG_ADD_IGNORED) {
-            let option = &arg[ARG_ADD_IGNORED.len()..];
+        } else if let Some(option) = arg.strip_prefix(ARG_ADD_IGNORED) {
             self.add_ignored = option.to_owned();
+        } else if let Some(option) = arg.strip_prefix(ARG_IGNORE_FILE) {
+            self.ignored_files.push(option.to_owned());
+        } else if let Some(option) = arg.strip_prefix(ARG_ADD_IGNORED_FILE) {
+            self.add_ignored_files.push(option.to_owned());
+        } else if let Some(option) = arg.strip_prefix(ARG_IGNORE_PATTERN) {
+            self.ignored_patterns.push(option.to_owned());
+        } else if let Some(option) = arg.strip_prefix(ARG_PROJECT_IGNORED) {
+            match option {
+                "true" => self.project_ignored = Some(true),
+                "false" => self.project_ignored = Some(false),
+                _ => {
+                    println!("Wrong argument to {}: expected 'true' or 'false', received: {}", ARG_PROJECT_IGNORED, option);
+                    exit(0);
+                }
+            }
         } else if arg == ARG_USAGE {
             usage();
             exit(0);
@@ -331,6 +545,8 @@ impl Config {
         } else if arg == ARG_LIST_LANGUAGES {
             list_languages();
             exit(0);
+        } else if arg == ARG_PRINT_IGNORED {
+            self.print_ignored = true;
         } else if arg == ARG_STATS {
             self.print_stats = true;
         } else if arg == ARG_ISPELL {
@@ -346,6 +562,6 @@ impl Config {
             println!("Unrecognized argument: {}. See {} for help", arg, ARG_USAGE);
             exit(0);
         }
-        return false;
+        false
     }
 }
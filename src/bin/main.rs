@@ -18,7 +18,9 @@
 extern crate caribon;
 mod config;
 use config::Config;
-use caribon::Parser;
+use caribon::{Parser, InputFormat, DistanceUnit, Pipeline, LocalDetector, GlobalDetector, PhraseDetector};
+use caribon::{Metric, Levenshtein, DamerauLevenshtein, JaroWinkler, AccentInsensitive};
+use caribon::{TerminalOptions, Palette};
 
 use std::error::Error;
 use std::io;
@@ -27,55 +29,143 @@ use std::io::Write;
 use std::io::BufRead;
 use std::collections::HashMap;
 
-fn print_stats(h: &HashMap<String, f32>, n_words: u32) {
+fn print_stats(h: &HashMap<String, f32>, n_words: u32, proper_nouns: &[String]) {
     let different_words = h.len();
     println!("Number of words: {}", n_words);
     println!("Number of different words: {}", different_words);
+    println!("Proper nouns: {}", proper_nouns.join(", "));
 }
 
-fn try_parse() -> Result<(), Box<Error>> {
+fn with_metric<M: Metric + 'static>(parser: Parser, metric: M, ignore_accents: bool) -> Parser {
+    if ignore_accents {
+        parser.with_metric(AccentInsensitive(metric))
+    } else {
+        parser.with_metric(metric)
+    }
+}
+
+fn try_parse() -> Result<(), Box<dyn Error>> {
     let mut config = Config::new_from_args();
-    let mut parser = try!(Parser::new(&config.lang));
+    let mut parser = Parser::new(&config.lang)?;
 
-    parser = parser.with_html(&config.input_format == "html")
+    let input_format = match &*config.input_format {
+        "html" => InputFormat::Html,
+        "markdown" => InputFormat::Markdown,
+        "latex" => InputFormat::Latex,
+        _ => InputFormat::Text,
+    };
+    let (distance_unit, max_distance) = match &*config.distance_unit {
+        "characters" => (DistanceUnit::Characters, 300),
+        "sentences" => (DistanceUnit::Sentences, 1),
+        "paragraphs" => (DistanceUnit::Paragraphs, 1),
+        "sections" => (DistanceUnit::Sections, 1),
+        _ => (DistanceUnit::Words, 50),
+    };
+    parser = parser.with_input_format(input_format)
                    .with_fuzzy(config.fuzzy)
                    .with_ignore_proper(config.ignore_proper)
-                   .with_max_distance(config.max_distance);
+                   .with_max_distance(config.max_distance.unwrap_or(max_distance))
+                   .with_distance_unit(distance_unit)
+                   .with_html_attributes(&config.html_attributes)
+                   .with_phrase_length(config.phrase_length.0, config.phrase_length.1)
+                   .with_phrase_skip_ignored(config.phrase_skip_ignored);
+    parser = parser.with_theme(config.theme.clone());
+    parser = match &*config.fuzzy_metric {
+        "damerau-levenshtein" => with_metric(parser, DamerauLevenshtein, config.ignore_accents),
+        "jaro-winkler" => with_metric(parser, JaroWinkler, config.ignore_accents),
+        _ => with_metric(parser, Levenshtein, config.ignore_accents),
+    };
 
-    if !config.ignored.is_empty() {
+    // Ignored words: the default list of the language (unless replaced by the user's
+    // own), then the ones of the project, then the ones added by the user
+    if !config.ignored.is_empty() || !config.ignored_files.is_empty() {
         parser = parser.with_ignored(&config.ignored);
+        for file in &config.ignored_files {
+            parser = parser.with_more_ignored_file(file)?;
+        }
+    }
+    // Only look for the project's words from the current directory if asked to, since it
+    // may have nothing to do with the text read on stdin
+    if config.project_ignored.unwrap_or(!config.input_filename.is_empty()) {
+        let start = if config.input_filename.is_empty() { "." } else { &config.input_filename };
+        if let Some(file) = Parser::find_project_ignored(start) {
+            parser = parser.with_more_ignored_file(file)?;
+        }
     }
     if !config.add_ignored.is_empty() {
         parser = parser.with_more_ignored(&config.add_ignored);
     }
+    for file in &config.add_ignored_files {
+        parser = parser.with_more_ignored_file(file)?;
+    }
+    for pattern in &config.ignored_patterns {
+        parser = parser.with_ignored_pattern(pattern)?;
+    }
+    if config.print_ignored {
+        for word in parser.ignored() {
+            writeln!(config.output, "{}", word)?;
+        }
+        for word in parser.ignored_stems() {
+            writeln!(config.output, "~{}", word)?;
+        }
+        for pattern in parser.ignored_patterns() {
+            writeln!(config.output, "/{}/", pattern)?;
+        }
+        return Ok(());
+    }
     if !config.ispell {
         let mut s = String::new();
-        try!(config.input.read_to_string(&mut s));
+        config.input.read_to_string(&mut s)?;
         
-        let mut ast = try!(parser.tokenize(&s));
+        let mut ast = parser.tokenize(&s)?;
         if config.print_stats {
         let (h, count) = parser.words_stats(&ast);
-            print_stats(&h, count);
+            print_stats(&h, count, &ast.proper_nouns);
+        }
+        let mut pipeline = Pipeline::new();
+        if let Some(threshold) = config.phrase_threshold {
+            pipeline.push(Box::new(PhraseDetector::new(&parser, threshold)));
         }
-        parser.detect_local(&mut ast, config.threshold);
+        pipeline.push(Box::new(LocalDetector::new(&parser, config.threshold)));
         if let Some(threshold) = config.global_threshold {
-            parser.detect_global(&mut ast, threshold);
+            pipeline.push(Box::new(GlobalDetector::new(&parser, threshold)));
         }
+        pipeline.run(&mut ast);
         let output = match &*config.output_format {
-            "html" => parser.ast_to_html(&mut ast, true),
-            "terminal" => parser.ast_to_terminal(&ast),
+            "html" => parser.ast_to_html(&ast, true),
+            "report" => parser.ast_to_report(&ast, config.threshold),
+            "terminal" => {
+                let options = match &*config.colour {
+                    "auto" => {
+                        // Only use escape codes if we print to a terminal
+                        let options = TerminalOptions::detect();
+                        let escapes = options.escapes && config.output_filename.is_empty();
+                        options.with_escapes(escapes)
+                    },
+                    "never" => TerminalOptions::new().with_escapes(false),
+                    "256" => TerminalOptions::new().with_palette(Palette::Ansi256),
+                    "truecolour" => TerminalOptions::new().with_palette(Palette::TrueColour),
+                    _ => TerminalOptions::new(),
+                };
+                parser.ast_to_terminal_with(&ast, &options.with_legend(config.legend)
+                                                          .with_gutter(config.gutter))
+            },
             "markdown" => parser.ast_to_markdown(&ast),
-            _ => return Err(Box::new(caribon::Error::new("Wrong output format: must be 'html, \
-                                                          'terminal', or 'markdown'"))),
+            "latex" => parser.ast_to_latex(&ast, &config.latex_command),
+            "json" => parser.ast_to_json(&ast),
+            _ => return Err(Box::new(caribon::Error::new("Wrong output format: must be 'html', \
+                                                          'report', 'terminal', 'markdown', 'latex' or 'json'"))),
         };
-        try!(config.output.write(&output.bytes().collect::<Vec<u8>>()));
+        config.output.write_all(output.as_bytes())?;
         Ok(())
     } else {
         config::disguise_as_ispell();
 
+        // Lines are chunks of the same document, so repetitions are detected across them
+        let mut session = parser.new_session();
         let stdin = io::stdin();
         for line in stdin.lock().lines() {
-            let mut line = try!(line);
+            let mut line = line?;
             let first = if let Some(c) = line.chars().next() {
                 c
             } else {
@@ -88,16 +178,16 @@ fn try_parse() -> Result<(), Box<Error>> {
                 '*' | '@' | '#' | '~' | '+' | '-' => continue,
                 '^' => {
                     offset = 1;
-                    line = (&line[1..]).to_owned();
+                    line = line[1..].to_owned();
                 },
                 _ => (),
             }
-            let mut ast = try!(parser.tokenize(&line));
-            parser.detect_local(&mut ast, config.threshold);
+            let mut ast = parser.tokenize(&line)?;
+            session.detect_local(&mut ast, config.threshold);
             let res = parser.ast_to_ispell(&ast, config.ispell_list, offset);
             print!("{}", res);
             if !config.ispell_list {
-                println!("");
+                println!();
             }
             // let mut f = File::create(&format!("/tmp/caribon{}.log", i)).unwrap();
             // f.write_all(&format!("input: {}\n***\noutput: {}\n", &line, res).as_bytes()).unwrap();
@@ -111,6 +201,6 @@ fn try_parse() -> Result<(), Box<Error>> {
 fn main() {
     match try_parse() {
         Ok(_) => {}
-        Err(e) => println!("{}", e.description()),
+        Err(e) => println!("{}", e),
     }
 }
@@ -15,7 +15,8 @@
 // You should have received a copy of the GNU Lesser General Public License
 // along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
 
-use super::Parser;
+use super::{Parser, FuzzyIndex};
+use super::strsim::levenshtein;
 extern crate test;
 use self::test::Bencher;
 
@@ -35,10 +36,33 @@ fn get_input() -> String {
     s
 }
 
+/// Generates a novel-sized text (about 100,000 words) with a large vocabulary
+fn get_novel() -> String {
+    const SYLLABLES: &[&str] = &["ca", "ri", "bon", "ta", "me", "lu", "po", "ver", "sin", "ga",
+                                 "dor", "e", "an", "tre", "mo", "li", "su", "pha", "rel", "o"];
+    let mut seed: u32 = 42;
+    let mut random = move |n: u32| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (seed >> 16) % n
+    };
+    // About 10,000 different words
+    let vocabulary: Vec<String> = (0..10000)
+        .map(|_| (0..2 + random(3)).map(|_| SYLLABLES[random(20) as usize]).collect())
+        .collect();
+    let mut s = String::new();
+    for i in 0..100000 {
+        // Common words are more frequent
+        let n = random(10000) * random(10000) / 10000;
+        s.push_str(&vocabulary[n as usize]);
+        s.push_str(if i % 15 == 14 { ". " } else { " " });
+    }
+    s
+}
+
 #[bench]
 fn bench_clone(b:&mut Bencher) {
     let s = get_input();
-    let mut parser = Parser::new("english").unwrap();
+    let parser = Parser::new("english").unwrap();
     let ast = parser.tokenize(&s).unwrap();
     b.iter(|| {
         ast.clone();
@@ -49,29 +73,29 @@ fn bench_clone(b:&mut Bencher) {
 #[bench]
 fn bench_html(b:&mut Bencher) {
     let s = get_input();
-    let mut parser = Parser::new("english").unwrap().with_html(true);
+    let parser = Parser::new("english").unwrap().with_html(true);
     let mut ast = parser.tokenize(&s).unwrap();
     parser.detect_local(&mut ast, 2.0);
     b.iter(|| {
-        parser.ast_to_html(&mut ast.clone(), false);
+        parser.ast_to_html(&ast, false);
     });
 }
 
 #[bench]
 fn bench_html2(b:&mut Bencher) {
     let s = get_input();
-    let mut parser = Parser::new("english").unwrap().with_html(false);
+    let parser = Parser::new("english").unwrap().with_html(false);
     let mut ast = parser.tokenize(&s).unwrap();
     parser.detect_local(&mut ast, 2.0);
     b.iter(|| {
-        parser.ast_to_html(&mut ast.clone(), false);
+        parser.ast_to_html(&ast, false);
     });
 }
 
 #[bench]
 fn bench_terminal(b:&mut Bencher) {
     let s = get_input();
-    let mut parser = Parser::new("english").unwrap();
+    let parser = Parser::new("english").unwrap();
     let mut ast = parser.tokenize(&s).unwrap();
     parser.detect_local(&mut ast, 2.0);
     b.iter(|| {
@@ -82,7 +106,7 @@ fn bench_terminal(b:&mut Bencher) {
 #[bench]
 fn bench_markdown(b:&mut Bencher) {
     let s = get_input();
-    let mut parser = Parser::new("english").unwrap();
+    let parser = Parser::new("english").unwrap();
     let mut ast = parser.tokenize(&s).unwrap();
     parser.detect_local(&mut ast, 2.0);
     b.iter(|| {
@@ -93,7 +117,7 @@ fn bench_markdown(b:&mut Bencher) {
 #[bench]
 fn bench_tokenize(b:&mut Bencher) {
     let s = get_input();
-    let mut parser = Parser::new("english").unwrap();
+    let parser = Parser::new("english").unwrap();
     b.iter(|| {
         parser.tokenize(&s).unwrap();
     });
@@ -103,7 +127,7 @@ fn bench_tokenize(b:&mut Bencher) {
 fn bench_local(b:&mut Bencher) {
     let s = get_input();
 //    let s = include_str!("../../README.md");
-    let mut parser = Parser::new("english").unwrap();
+    let parser = Parser::new("english").unwrap();
     let ast = parser.tokenize(&s).unwrap();    
     b.iter(|| {
         parser.detect_local(&mut ast.clone(), 1.9);
@@ -113,7 +137,7 @@ fn bench_local(b:&mut Bencher) {
 #[bench]
 fn bench_local_fuzzy(b:&mut Bencher) {
     let s = get_input();
-    let mut parser = Parser::new("english").unwrap().with_fuzzy(Some(0.5));
+    let parser = Parser::new("english").unwrap().with_fuzzy(Some(0.5));
     let ast = parser.tokenize(&s).unwrap();    
     b.iter(|| {
         parser.detect_local(&mut ast.clone(), 1.9);
@@ -124,10 +148,10 @@ fn bench_local_fuzzy(b:&mut Bencher) {
 fn bench_total(b:&mut Bencher) {
     let s = get_input();
     b.iter(|| {
-        let mut parser = Parser::new("english").unwrap().with_fuzzy(Some(0.5));
+        let parser = Parser::new("english").unwrap().with_fuzzy(Some(0.5));
         let mut ast = parser.tokenize(&s).unwrap();    
         parser.detect_local(&mut ast, 1.9);
-        parser.ast_to_html(&mut ast, true);
+        parser.ast_to_html(&ast, true);
     });
 }
 
@@ -136,7 +160,7 @@ fn bench_total(b:&mut Bencher) {
 fn bench_local_readme(b:&mut Bencher) {
     //    let s = get_input();
     let s = include_str!("../../README.md");
-    let mut parser = Parser::new("english").unwrap();
+    let parser = Parser::new("english").unwrap();
     let ast = parser.tokenize(&s).unwrap();    
     b.iter(|| {
         parser.detect_local(&mut ast.clone(), 1.9);
@@ -147,7 +171,7 @@ fn bench_local_readme(b:&mut Bencher) {
 fn bench_local_readme_fuzzy(b:&mut Bencher) {
     //    let s = get_input();
     let s = include_str!("../../README.md");
-    let mut parser = Parser::new("english").unwrap().with_fuzzy(Some(0.5));
+    let parser = Parser::new("english").unwrap().with_fuzzy(Some(0.5));
     let ast = parser.tokenize(&s).unwrap();    
     b.iter(|| {
         parser.detect_local(&mut ast.clone(), 1.9);
@@ -157,7 +181,7 @@ fn bench_local_readme_fuzzy(b:&mut Bencher) {
 #[bench]
 fn bench_global(b:&mut Bencher) {
     let s = get_input();
-    let mut parser = Parser::new("english").unwrap();
+    let parser = Parser::new("english").unwrap();
     let ast = parser.tokenize(&s).unwrap();
     b.iter(|| {
         parser.detect_global(&mut ast.clone(), 0.01);
@@ -166,3 +190,69 @@ fn bench_global(b:&mut Bencher) {
 
 
 
+
+#[bench]
+fn bench_local_novel(b:&mut Bencher) {
+    let s = get_novel();
+    let parser = Parser::new("english").unwrap();
+    let ast = parser.tokenize(&s).unwrap();
+    b.iter(|| {
+        parser.detect_local(&mut ast.clone(), 1.9);
+    });
+}
+
+#[bench]
+fn bench_local_novel_fuzzy(b:&mut Bencher) {
+    let s = get_novel();
+    let parser = Parser::new("english").unwrap().with_fuzzy(Some(0.25));
+    let ast = parser.tokenize(&s).unwrap();
+    b.iter(|| {
+        parser.detect_local(&mut ast.clone(), 1.9);
+    });
+}
+
+#[bench]
+fn bench_local_novel_fuzzy_long_distance(b:&mut Bencher) {
+    let s = get_novel();
+    let parser = Parser::new("english").unwrap()
+        .with_fuzzy(Some(0.25))
+        .with_max_distance(2000);
+    let ast = parser.tokenize(&s).unwrap();
+    b.iter(|| {
+        parser.detect_local(&mut ast.clone(), 1.9);
+    });
+}
+
+/// Looks up 1,000 words among the whole vocabulary of a novel with a `FuzzyIndex`
+#[bench]
+fn bench_fuzzy_index(b:&mut Bencher) {
+    let s = get_novel();
+    let words: Vec<&str> = s.split(|c: char| !c.is_alphabetic()).filter(|w| !w.is_empty()).collect();
+    let mut index = FuzzyIndex::new();
+    for word in &words {
+        index.insert(word);
+    }
+    b.iter(|| {
+        for word in words.iter().take(1000) {
+            index.closest(word, word.len() / 4);
+        }
+    });
+}
+
+/// Same as `bench_fuzzy_index`, but comparing each word to all the others
+#[bench]
+fn bench_fuzzy_linear(b:&mut Bencher) {
+    let s = get_novel();
+    let mut words: Vec<&str> = s.split(|c: char| !c.is_alphabetic()).filter(|w| !w.is_empty()).collect();
+    let queries: Vec<&str> = words.iter().take(1000).cloned().collect();
+    words.sort();
+    words.dedup();
+    b.iter(|| {
+        for word in &queries {
+            words.iter()
+                 .map(|w| (levenshtein(w, word), *w))
+                 .filter(|&(d, _)| d <= word.len() / 4)
+                 .min();
+        }
+    });
+}
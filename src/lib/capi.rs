@@ -0,0 +1,784 @@
+// Copyright (C) 2015, 2016 Élisabeth HENRY.
+//
+// This file is part of Caribon.
+//
+// Caribon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Caribon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
+
+//! C interface to the library.
+//!
+//! The corresponding header is `include/caribon.h`. A C program typically does:
+//!
+//! ```c
+//! caribon_parser *parser = caribon_parser_new("english");
+//! caribon_parser_set_max_distance(parser, 20);
+//! caribon_result *result = caribon_detect(parser, text, 1.9, 0.0);
+//! for (size_t i = 0; i < result->len; i++) {
+//!     caribon_repetition *r = &result->repetitions[i];
+//!     printf("%zu %zu %s\n", r->offset, r->length, r->colour);
+//! }
+//! caribon_result_free(result);
+//! caribon_parser_free(parser);
+//! ```
+//!
+//! Functions that can fail return `NULL` (or a negative value), and the error message
+//! can then be retrieved with `caribon_last_error`.
+//!
+//! There is a setter for each `with_*` method of `Parser`, except `with_word_rules` (the
+//! rules for the language are used) and `with_metric` with a custom `Metric`, which is a
+//! Rust trait: only the built-in metrics can be chosen from C. `with_ignored_file` is
+//! `caribon_parser_set_ignored(parser, "")` followed by `caribon_parser_add_ignored_file`.
+//!
+//! The header is written by hand, but the tests of this module check that it declares
+//! exactly the functions, structures and constants defined here, with the same types.
+//!
+//! The same thing, from Rust:
+//!
+//! ```rust
+//! use caribon::capi::*;
+//! use std::ffi::CString;
+//!
+//! let lang = CString::new("english").unwrap();
+//! let text = CString::new("It works, it really works").unwrap();
+//! unsafe {
+//!     let parser = caribon_parser_new(lang.as_ptr());
+//!     assert!(!parser.is_null());
+//!     let result = caribon_detect(parser, text.as_ptr(), 1.9, 0.0);
+//!     assert_eq!((*result).len, 2);
+//!     let first = &*(*result).repetitions;
+//!     assert_eq!((first.offset, first.length), (3, 5));
+//!     caribon_result_free(result);
+//!     caribon_parser_free(parser);
+//! }
+//! ```
+
+use parser::{Parser, Repetition, InputFormat, DistanceUnit};
+use error::Result;
+use detector::{Pipeline, LocalDetector, GlobalDetector, PhraseDetector};
+use metric::{Levenshtein, DamerauLevenshtein, JaroWinkler, AccentInsensitive};
+use theme::Theme;
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_float, c_int, c_uint};
+use std::panic;
+use std::ptr;
+
+/// Input formats, for `caribon_parser_set_input_format`
+pub const CARIBON_INPUT_TEXT: c_int = 0;
+pub const CARIBON_INPUT_HTML: c_int = 1;
+pub const CARIBON_INPUT_MARKDOWN: c_int = 2;
+pub const CARIBON_INPUT_LATEX: c_int = 3;
+
+/// Distance units, for `caribon_parser_set_distance_unit`
+pub const CARIBON_DISTANCE_WORDS: c_int = 0;
+pub const CARIBON_DISTANCE_CHARACTERS: c_int = 1;
+pub const CARIBON_DISTANCE_SENTENCES: c_int = 2;
+pub const CARIBON_DISTANCE_PARAGRAPHS: c_int = 3;
+pub const CARIBON_DISTANCE_SECTIONS: c_int = 4;
+
+/// Metrics, for `caribon_parser_set_metric`
+pub const CARIBON_METRIC_LEVENSHTEIN: c_int = 0;
+pub const CARIBON_METRIC_DAMERAU_LEVENSHTEIN: c_int = 1;
+pub const CARIBON_METRIC_JARO_WINKLER: c_int = 2;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: &str) {
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+/// Converts a C string to a `&str`, setting the error if it is not valid
+unsafe fn to_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        set_error("Unexpected NULL string");
+        return None;
+    }
+    match CStr::from_ptr(s).to_str() {
+        Ok(s) => Some(s),
+        Err(_) => {
+            set_error("String is not valid UTF-8");
+            None
+        }
+    }
+}
+
+/// Opaque parser handle
+pub struct CaribonParser {
+    /// Only `None` while a setter is running
+    parser: Option<Parser>,
+}
+
+impl CaribonParser {
+    /// Applies one of the `with_*` methods to the parser
+    unsafe fn update<F>(this: *mut CaribonParser, f: F) -> c_int
+        where F: FnOnce(Parser) -> Parser
+    {
+        if this.is_null() {
+            set_error("Unexpected NULL parser");
+            return -1;
+        }
+        let this = &mut *this;
+        match this.parser.take() {
+            Some(parser) => {
+                this.parser = Some(f(parser));
+                0
+            }
+            None => {
+                set_error("Invalid parser");
+                -1
+            }
+        }
+    }
+
+    /// Modifies the parser with a function that can fail, setting the error if it does
+    unsafe fn try_update<F>(this: *mut CaribonParser, f: F) -> c_int
+        where F: FnOnce(&mut Parser) -> Result<()>
+    {
+        if this.is_null() {
+            set_error("Unexpected NULL parser");
+            return -1;
+        }
+        match (*this).parser {
+            Some(ref mut parser) => match f(parser) {
+                Ok(()) => 0,
+                Err(e) => {
+                    set_error(&e.content);
+                    -1
+                }
+            },
+            None => {
+                set_error("Invalid parser");
+                -1
+            }
+        }
+    }
+}
+
+/// A repetition, as returned to C code
+#[repr(C)]
+pub struct CaribonRepetition {
+    /// The beginning (in characters) of the repetition
+    pub offset: usize,
+    /// The length (in characters) of the repetition
+    pub length: usize,
+    /// The beginning (in bytes) of the repetition
+    pub byte_offset: usize,
+    /// The length (in bytes) of the repetition
+    pub byte_length: usize,
+    /// The "severity" of the repetition
+    pub value: c_float,
+    /// How the repetition should be displayed (NUL-terminated)
+    pub colour: *mut c_char,
+}
+
+/// An array of repetitions
+#[repr(C)]
+pub struct CaribonResult {
+    pub repetitions: *mut CaribonRepetition,
+    pub len: usize,
+}
+
+/// Returns the message of the last error that happened in this thread, or `NULL`.
+///
+/// The string is owned by the library and remains valid until the next call to
+/// a function of the library.
+#[no_mangle]
+pub extern "C" fn caribon_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| match *e.borrow() {
+        Some(ref s) => s.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// Creates a new parser for the given language, or returns `NULL` if the language is
+/// not implemented.
+///
+/// # Safety
+///
+/// `lang` must be a valid, NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn caribon_parser_new(lang: *const c_char) -> *mut CaribonParser {
+    let lang = match to_str(lang) {
+        Some(lang) => lang,
+        None => return ptr::null_mut(),
+    };
+    match Parser::new(lang) {
+        Ok(parser) => Box::into_raw(Box::new(CaribonParser { parser: Some(parser) })),
+        Err(e) => {
+            set_error(&e.content);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Frees a parser created by `caribon_parser_new`.
+///
+/// # Safety
+///
+/// `parser` must have been returned by `caribon_parser_new` and not already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn caribon_parser_free(parser: *mut CaribonParser) {
+    if !parser.is_null() {
+        drop(Box::from_raw(parser));
+    }
+}
+
+/// Sets HTML detection in input (see `Parser::with_html`). Returns 0 on success.
+///
+/// # Safety
+///
+/// `parser` must be a valid parser.
+#[no_mangle]
+pub unsafe extern "C" fn caribon_parser_set_html(parser: *mut CaribonParser, html: c_int) -> c_int {
+    CaribonParser::update(parser, |p| p.with_html(html != 0))
+}
+
+/// Sets the format of the input text (see `Parser::with_input_format`), one of the
+/// `CARIBON_INPUT_*` constants. Returns 0 on success.
+///
+/// # Safety
+///
+/// `parser` must be a valid parser.
+#[no_mangle]
+pub unsafe extern "C" fn caribon_parser_set_input_format(parser: *mut CaribonParser,
+                                                         format: c_int)
+                                                         -> c_int {
+    let format = match format {
+        CARIBON_INPUT_TEXT => InputFormat::Text,
+        CARIBON_INPUT_HTML => InputFormat::Html,
+        CARIBON_INPUT_MARKDOWN => InputFormat::Markdown,
+        CARIBON_INPUT_LATEX => InputFormat::Latex,
+        _ => {
+            set_error("Invalid input format");
+            return -1;
+        }
+    };
+    CaribonParser::update(parser, |p| p.with_input_format(format))
+}
+
+/// Sets fuzzy string matching (see `Parser::with_fuzzy`); a negative value deactivates it.
+/// Returns 0 on success.
+///
+/// # Safety
+///
+/// `parser` must be a valid parser.
+#[no_mangle]
+pub unsafe extern "C" fn caribon_parser_set_fuzzy(parser: *mut CaribonParser, fuzzy: c_float) -> c_int {
+    let fuzzy = if fuzzy < 0.0 { None } else { Some(fuzzy) };
+    CaribonParser::update(parser, |p| p.with_fuzzy(fuzzy))
+}
+
+/// Sets max distance for repetitions (see `Parser::with_max_distance`). Returns 0 on success.
+///
+/// # Safety
+///
+/// `parser` must be a valid parser.
+#[no_mangle]
+pub unsafe extern "C" fn caribon_parser_set_max_distance(parser: *mut CaribonParser,
+                                                         max_distance: c_uint)
+                                                         -> c_int {
+    CaribonParser::update(parser, |p| p.with_max_distance(max_distance))
+}
+
+/// Sets the unit of the max distance (see `Parser::with_distance_unit`), one of the
+/// `CARIBON_DISTANCE_*` constants. Returns 0 on success.
+///
+/// # Safety
+///
+/// `parser` must be a valid parser.
+#[no_mangle]
+pub unsafe extern "C" fn caribon_parser_set_distance_unit(parser: *mut CaribonParser,
+                                                          unit: c_int)
+                                                          -> c_int {
+    let unit = match unit {
+        CARIBON_DISTANCE_WORDS => DistanceUnit::Words,
+        CARIBON_DISTANCE_CHARACTERS => DistanceUnit::Characters,
+        CARIBON_DISTANCE_SENTENCES => DistanceUnit::Sentences,
+        CARIBON_DISTANCE_PARAGRAPHS => DistanceUnit::Paragraphs,
+        CARIBON_DISTANCE_SECTIONS => DistanceUnit::Sections,
+        _ => {
+            set_error("Invalid distance unit");
+            return -1;
+        }
+    };
+    CaribonParser::update(parser, |p| p.with_distance_unit(unit))
+}
+
+/// Sets the metric used by fuzzy string matching (see `Parser::with_metric`), one of the
+/// `CARIBON_METRIC_*` constants. If `ignore_accents` is not 0, accents are not taken into
+/// account (see `AccentInsensitive`). Returns 0 on success.
+///
+/// # Safety
+///
+/// `parser` must be a valid parser.
+#[no_mangle]
+pub unsafe extern "C" fn caribon_parser_set_metric(parser: *mut CaribonParser,
+                                                   metric: c_int,
+                                                   ignore_accents: c_int)
+                                                   -> c_int {
+    let ignore_accents = ignore_accents != 0;
+    match metric {
+        CARIBON_METRIC_LEVENSHTEIN if ignore_accents => CaribonParser::update(parser, |p| p.with_metric(AccentInsensitive(Levenshtein))),
+        CARIBON_METRIC_LEVENSHTEIN => CaribonParser::update(parser, |p| p.with_metric(Levenshtein)),
+        CARIBON_METRIC_DAMERAU_LEVENSHTEIN if ignore_accents => CaribonParser::update(parser, |p| p.with_metric(AccentInsensitive(DamerauLevenshtein))),
+        CARIBON_METRIC_DAMERAU_LEVENSHTEIN => CaribonParser::update(parser, |p| p.with_metric(DamerauLevenshtein)),
+        CARIBON_METRIC_JARO_WINKLER if ignore_accents => CaribonParser::update(parser, |p| p.with_metric(AccentInsensitive(JaroWinkler))),
+        CARIBON_METRIC_JARO_WINKLER => CaribonParser::update(parser, |p| p.with_metric(JaroWinkler)),
+        _ => {
+            set_error("Invalid metric");
+            -1
+        }
+    }
+}
+
+/// Sets the theme (see `Parser::with_theme`), either the name of a built-in theme or the
+/// path of a theme file. Returns 0 on success.
+///
+/// # Safety
+///
+/// `parser` must be a valid parser and `theme` a valid, NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn caribon_parser_set_theme(parser: *mut CaribonParser,
+                                                  theme: *const c_char)
+                                                  -> c_int {
+    let theme = match to_str(theme) {
+        Some(theme) => theme,
+        None => return -1,
+    };
+    let theme = match Theme::named(theme) {
+        Some(theme) => theme,
+        None => match Theme::from_file(theme) {
+            Ok(theme) => theme,
+            Err(e) => {
+                set_error(&e.content);
+                return -1;
+            }
+        },
+    };
+    CaribonParser::update(parser, |p| p.with_theme(theme))
+}
+
+/// Sets the minimal and maximal number of words of expressions (see
+/// `Parser::with_phrase_length`). Returns 0 on success.
+///
+/// # Safety
+///
+/// `parser` must be a valid parser.
+#[no_mangle]
+pub unsafe extern "C" fn caribon_parser_set_phrase_length(parser: *mut CaribonParser,
+                                                          min: c_uint,
+                                                          max: c_uint)
+                                                          -> c_int {
+    CaribonParser::update(parser, |p| p.with_phrase_length(min as usize, max as usize))
+}
+
+/// Sets whether ignored words are skipped when detecting expressions (see
+/// `Parser::with_phrase_skip_ignored`). Returns 0 on success.
+///
+/// # Safety
+///
+/// `parser` must be a valid parser.
+#[no_mangle]
+pub unsafe extern "C" fn caribon_parser_set_phrase_skip_ignored(parser: *mut CaribonParser,
+                                                                skip: c_int)
+                                                                -> c_int {
+    CaribonParser::update(parser, |p| p.with_phrase_skip_ignored(skip != 0))
+}
+
+/// Sets the HTML attributes whose value is checked (see `Parser::with_html_attributes`).
+/// Returns 0 on success.
+///
+/// # Safety
+///
+/// `parser` must be a valid parser and `list` a valid, NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn caribon_parser_set_html_attributes(parser: *mut CaribonParser,
+                                                            list: *const c_char)
+                                                            -> c_int {
+    match to_str(list) {
+        Some(list) => CaribonParser::update(parser, |p| p.with_html_attributes(list)),
+        None => -1,
+    }
+}
+
+/// Sets whether proper nouns are ignored (see `Parser::with_ignore_proper`). Returns 0 on success.
+///
+/// # Safety
+///
+/// `parser` must be a valid parser.
+#[no_mangle]
+pub unsafe extern "C" fn caribon_parser_set_ignore_proper(parser: *mut CaribonParser,
+                                                          proper: c_int)
+                                                          -> c_int {
+    CaribonParser::update(parser, |p| p.with_ignore_proper(proper != 0))
+}
+
+/// Replaces the list of ignored words (see `Parser::with_ignored`). Returns 0 on success.
+///
+/// # Safety
+///
+/// `parser` must be a valid parser and `list` a valid, NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn caribon_parser_set_ignored(parser: *mut CaribonParser,
+                                                    list: *const c_char)
+                                                    -> c_int {
+    match to_str(list) {
+        Some(list) => CaribonParser::update(parser, |p| p.with_ignored(list)),
+        None => -1,
+    }
+}
+
+/// Adds words to the list of ignored words (see `Parser::with_more_ignored`). Returns 0 on success.
+///
+/// # Safety
+///
+/// `parser` must be a valid parser and `list` a valid, NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn caribon_parser_add_ignored(parser: *mut CaribonParser,
+                                                    list: *const c_char)
+                                                    -> c_int {
+    match to_str(list) {
+        Some(list) => CaribonParser::update(parser, |p| p.with_more_ignored(list)),
+        None => -1,
+    }
+}
+
+/// Adds a pattern of ignored words (see `Parser::with_ignored_pattern`). Returns 0 on
+/// success, or -1 if the pattern is invalid.
+///
+/// # Safety
+///
+/// `parser` must be a valid parser and `pattern` a valid, NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn caribon_parser_add_ignored_pattern(parser: *mut CaribonParser,
+                                                            pattern: *const c_char)
+                                                            -> c_int {
+    match to_str(pattern) {
+        Some(pattern) => CaribonParser::try_update(parser, |p| p.add_ignored(&[format!("/{}/", pattern)])),
+        None => -1,
+    }
+}
+
+/// Adds the words contained in a file to the list of ignored words (see
+/// `Parser::with_more_ignored_file`). Returns 0 on success, or -1 if the file can't be
+/// read or contains an invalid pattern (the entries before it are then still added).
+///
+/// # Safety
+///
+/// `parser` must be a valid parser and `path` a valid, NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn caribon_parser_add_ignored_file(parser: *mut CaribonParser,
+                                                         path: *const c_char)
+                                                         -> c_int {
+    match to_str(path) {
+        Some(path) => CaribonParser::try_update(parser, |p| p.add_ignored(&Parser::get_ignored_from_file(path)?)),
+        None => -1,
+    }
+}
+
+/// Tokenizes `text` and detects its repetitions.
+///
+/// Local repetitions are detected with `threshold`; if `global_threshold` is positive,
+/// global repetitions are also detected. Returns `NULL` on error. The result must be
+/// freed with `caribon_result_free`.
+///
+/// # Safety
+///
+/// `parser` must be a valid parser and `text` a valid, NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn caribon_detect(parser: *const CaribonParser,
+                                        text: *const c_char,
+                                        threshold: c_float,
+                                        global_threshold: c_float)
+                                        -> *mut CaribonResult {
+    caribon_detect_with_phrases(parser, text, threshold, global_threshold, 0.0)
+}
+
+/// Same as `caribon_detect`, but if `phrase_threshold` is positive, repeated expressions
+/// (see `PhraseDetector`) are also detected with this threshold.
+///
+/// # Safety
+///
+/// `parser` must be a valid parser and `text` a valid, NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn caribon_detect_with_phrases(parser: *const CaribonParser,
+                                                     text: *const c_char,
+                                                     threshold: c_float,
+                                                     global_threshold: c_float,
+                                                     phrase_threshold: c_float)
+                                                     -> *mut CaribonResult {
+    if parser.is_null() {
+        set_error("Unexpected NULL parser");
+        return ptr::null_mut();
+    }
+    let parser = match (*parser).parser {
+        Some(ref parser) => parser,
+        None => {
+            set_error("Invalid parser");
+            return ptr::null_mut();
+        }
+    };
+    let text = match to_str(text) {
+        Some(text) => text,
+        None => return ptr::null_mut(),
+    };
+
+    // The parser is only read, so it can't be left in an inconsistent state
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| -> Result<Vec<Repetition>> {
+        let mut ast = parser.tokenize(text)?;
+        let mut pipeline = Pipeline::new();
+        if phrase_threshold > 0.0 {
+            pipeline = pipeline.with_detector(PhraseDetector::new(parser, phrase_threshold));
+        }
+        pipeline = pipeline.with_detector(LocalDetector::new(parser, threshold));
+        if global_threshold > 0.0 {
+            pipeline = pipeline.with_detector(GlobalDetector::new(parser, global_threshold));
+        }
+        pipeline.run(&mut ast);
+        Ok(parser.ast_to_repetitions(&ast))
+    }));
+    let repetitions = match result {
+        Ok(Ok(repetitions)) => repetitions,
+        Ok(Err(e)) => {
+            set_error(&e.content);
+            return ptr::null_mut();
+        }
+        Err(_) => {
+            set_error("Internal error");
+            return ptr::null_mut();
+        }
+    };
+
+    let repetitions: Vec<CaribonRepetition> = repetitions.into_iter()
+        .map(|r| CaribonRepetition {
+            offset: r.span.offset,
+            length: r.span.length,
+            byte_offset: r.span.byte_offset,
+            byte_length: r.span.byte_length,
+            value: r.value,
+            colour: CString::new(r.colour).unwrap_or_default().into_raw(),
+        })
+        .collect();
+    let repetitions = repetitions.into_boxed_slice();
+    let len = repetitions.len();
+    Box::into_raw(Box::new(CaribonResult {
+        repetitions: Box::into_raw(repetitions) as *mut CaribonRepetition,
+        len,
+    }))
+}
+
+/// Frees a result returned by `caribon_detect`.
+///
+/// # Safety
+///
+/// `result` must have been returned by `caribon_detect` and not already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn caribon_result_free(result: *mut CaribonResult) {
+    if result.is_null() {
+        return;
+    }
+    let result = Box::from_raw(result);
+    let repetitions = Box::from_raw(ptr::slice_from_raw_parts_mut(result.repetitions, result.len));
+    for repetition in repetitions.iter() {
+        drop(CString::from_raw(repetition.colour));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    const HEADER: &str = include_str!("../../include/caribon.h");
+
+    /// The Rust part of this file, without the tests
+    fn source() -> &'static str {
+        include_str!("capi.rs").split("#[cfg(test)]").next().unwrap()
+    }
+
+    /// Converts a Rust type used in this module to its C equivalent
+    fn c_type(ty: &str) -> String {
+        let ty = ty.trim();
+        let c = match ty {
+            "c_int" => "int",
+            "c_uint" => "unsigned int",
+            "c_float" => "float",
+            "usize" => "size_t",
+            "*const c_char" => "const char *",
+            "*mut c_char" => "char *",
+            "*mut CaribonParser" => "caribon_parser *",
+            "*const CaribonParser" => "const caribon_parser *",
+            "*mut CaribonResult" => "caribon_result *",
+            "*mut CaribonRepetition" => "caribon_repetition *",
+            _ => panic!("no C equivalent for Rust type {}", ty),
+        };
+        c.to_owned()
+    }
+
+    /// Removes whitespace, so that declarations can be compared regardless of formatting
+    fn normalize(s: &str) -> String {
+        s.chars().filter(|c| !c.is_whitespace()).collect()
+    }
+
+    /// Removes C comments
+    fn without_comments(s: &str) -> String {
+        let mut res = String::new();
+        let mut rest = s;
+        while let Some(start) = rest.find("/*") {
+            res.push_str(&rest[..start]);
+            rest = match rest[start..].find("*/") {
+                Some(end) => &rest[start + end + 2..],
+                None => "",
+            };
+        }
+        res.push_str(rest);
+        res
+    }
+
+    fn rust_prototypes() -> Vec<String> {
+        let mut res = vec![];
+        for item in source().split("extern \"C\" fn ").skip(1) {
+            let item = &item[..item.find('{').unwrap()];
+            let open = item.find('(').unwrap();
+            let close = item.rfind(')').unwrap();
+            let name = item[..open].trim();
+            let args: Vec<String> = item[open + 1..close].split(',')
+                .map(str::trim)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| {
+                    let colon = arg.find(':').unwrap();
+                    format!("{} {}", c_type(&arg[colon + 1..]), &arg[..colon])
+                })
+                .collect();
+            let ret = match item[close..].find("->") {
+                Some(arrow) => c_type(&item[close + arrow + 2..]),
+                None => "void".to_owned(),
+            };
+            let args = if args.is_empty() { "void".to_owned() } else { args.join(", ") };
+            res.push(normalize(&format!("{} {}({})", ret, name, args)));
+        }
+        res.sort();
+        res
+    }
+
+    fn header_prototypes() -> Vec<String> {
+        let mut res: Vec<String> = without_comments(HEADER)
+            .split([';', '{', '}'])
+            .map(|decl| decl.lines().filter(|l| !l.trim_start().starts_with('#')).collect::<Vec<_>>().join(" "))
+            .filter(|decl| decl.contains("caribon_") && decl.contains('('))
+            .map(|decl| normalize(&decl))
+            .collect();
+        res.sort();
+        res
+    }
+
+    #[test]
+    fn header_declares_functions() {
+        let rust = rust_prototypes();
+        let header = header_prototypes();
+        assert!(rust.len() > 10);
+        for proto in &rust {
+            assert!(header.contains(proto), "missing or different in caribon.h: {}", proto);
+        }
+        for proto in &header {
+            assert!(rust.contains(proto), "not defined in capi.rs: {}", proto);
+        }
+    }
+
+    #[test]
+    fn header_defines_constants() {
+        let rust: BTreeMap<String, String> = source().lines()
+            .filter_map(|l| l.strip_prefix("pub const "))
+            .map(|l| {
+                let name = &l[..l.find(':').unwrap()];
+                let value = &l[l.find('=').unwrap() + 1..l.find(';').unwrap()];
+                (name.trim().to_owned(), value.trim().to_owned())
+            })
+            .collect();
+        let header: BTreeMap<String, String> = HEADER.lines()
+            .filter_map(|l| l.strip_prefix("#define CARIBON_"))
+            .filter(|l| !l.starts_with("H"))
+            .map(|l| {
+                let mut parts = l.split_whitespace();
+                (format!("CARIBON_{}", parts.next().unwrap()), parts.next().unwrap().to_owned())
+            })
+            .collect();
+        assert!(rust.len() > 10);
+        assert_eq!(rust, header);
+    }
+
+    #[test]
+    fn header_defines_structs() {
+        for &(rust_name, c_name) in &[("CaribonRepetition", "caribon_repetition"),
+                                      ("CaribonResult", "caribon_result")] {
+            let source = source();
+            let start = source.find(&format!("pub struct {} {{", rust_name)).unwrap();
+            let body = &source[start..];
+            let body = &body[body.find('{').unwrap() + 1..body.find('}').unwrap()];
+            let rust: Vec<String> = body.lines()
+                .filter_map(|l| l.trim().strip_prefix("pub "))
+                .map(|l| {
+                    let colon = l.find(':').unwrap();
+                    normalize(&format!("{} {}", c_type(l[colon + 1..].trim_end_matches(',')), &l[..colon]))
+                })
+                .collect();
+
+            let header = without_comments(HEADER);
+            let end = header.find(&format!("}} {};", c_name)).unwrap();
+            let body = &header[header[..end].rfind('{').unwrap() + 1..end];
+            let c: Vec<String> = body.split(';')
+                .map(normalize)
+                .filter(|field| !field.is_empty())
+                .collect();
+            assert_eq!(rust, c, "fields of {}", c_name);
+        }
+    }
+
+    #[test]
+    fn setters() {
+        let lang = CString::new("english").unwrap();
+        let text = CString::new("This is a good idea, a very good idea").unwrap();
+        unsafe {
+            let parser = caribon_parser_new(lang.as_ptr());
+            assert_eq!(caribon_parser_set_metric(parser, CARIBON_METRIC_JARO_WINKLER, 1), 0);
+            assert_eq!(caribon_parser_set_metric(parser, 42, 0), -1);
+            let theme = CString::new("no-such-theme").unwrap();
+            assert_eq!(caribon_parser_set_theme(parser, theme.as_ptr()), -1);
+            assert!(!caribon_last_error().is_null());
+            let theme = CString::new("colourblind").unwrap();
+            assert_eq!(caribon_parser_set_theme(parser, theme.as_ptr()), 0);
+            assert_eq!(caribon_parser_set_phrase_length(parser, 2, 3), 0);
+            assert_eq!(caribon_parser_set_phrase_skip_ignored(parser, 1), 0);
+            let attributes = CString::new("alt title").unwrap();
+            assert_eq!(caribon_parser_set_html_attributes(parser, attributes.as_ptr()), 0);
+
+            // A failed setter leaves the parser usable
+            let pattern = CString::new("(a").unwrap();
+            assert_eq!(caribon_parser_add_ignored_pattern(parser, pattern.as_ptr()), -1);
+            let pattern = CString::new("ide.").unwrap();
+            assert_eq!(caribon_parser_add_ignored_pattern(parser, pattern.as_ptr()), 0);
+
+            let result = caribon_detect_with_phrases(parser, text.as_ptr(), 1.9, 0.0, 1.9);
+            assert!(!result.is_null());
+            let repetitions = ::std::slice::from_raw_parts((*result).repetitions, (*result).len);
+            assert!(repetitions.iter().any(|r| r.offset == 10 && r.length == 4));
+            assert!(repetitions.iter().all(|r| r.offset != 15));
+            caribon_result_free(result);
+            caribon_parser_free(parser);
+        }
+    }
+}
@@ -0,0 +1,388 @@
+// Copyright (C) 2015, 2016 Élisabeth HENRY.
+//
+// This file is part of Caribon.
+//
+// Caribon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Caribon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
+
+use word::{Word, Ast, Highlight};
+use parser::{Parser, DistanceUnit};
+use session::Session;
+
+use std::collections::HashMap;
+
+/// A word that has been flagged as a repetition by a `Detector`.
+#[derive(Debug, Clone)]
+pub struct Finding {
+    /// Index of the word in `ast.words`
+    pub index: usize,
+    /// The repetition value, which is stored in the `Ast`
+    pub value: f32,
+    /// The severity of the repetition (see `Highlight::severity`)
+    pub severity: f32,
+    /// The stemmed string identifying the repetition (e.g. the fuzzy-matched stem)
+    pub stemmed: String,
+    /// Identifier of the repetition: findings of the same detector with the same group
+    /// are occurrences of the same repetition
+    pub group: usize,
+}
+
+/// A repetition detection algorithm.
+///
+/// A detector reads an `Ast` and returns the words that should be highlighted. It doesn't
+/// modify the `Ast` itself: this is done by `highlight` (or by a `Pipeline`), so
+/// detectors can be combined without having to know about each other.
+///
+/// `LocalDetector`, `GlobalDetector` and `PhraseDetector` are the built-in ones, but you can
+/// also implement your own.
+///
+/// # Example
+///
+/// ```rust
+/// use caribon::{Ast, Word, Detector, Finding, Pipeline, LocalDetector};
+///
+/// /// Flags every occurrence of "really"
+/// struct ReallyDetector;
+///
+/// impl Detector for ReallyDetector {
+///     fn name(&self) -> &'static str {
+///         "really"
+///     }
+///
+///     fn detect(&mut self, ast: &Ast) -> Vec<Finding> {
+///         let mut res = vec![];
+///         for (i, word) in ast.words.iter().enumerate() {
+///             if let Word::Tracked(ref s, ref stemmed, _, _) = *word {
+///                 if s.to_lowercase() == "really" {
+///                     res.push(Finding {
+///                         index: i,
+///                         value: 1.0,
+///                         severity: 1.0,
+///                         stemmed: stemmed.clone(),
+///                         group: 0,
+///                     });
+///                 }
+///             }
+///         }
+///         res
+///     }
+/// }
+///
+/// let parser = caribon::Parser::new("english").unwrap();
+/// let mut ast = parser.tokenize("A really good test, which works really well").unwrap();
+/// Pipeline::new()
+///     .with_detector(ReallyDetector)
+///     .with_detector(LocalDetector::new(&parser, 1.9))
+///     .run(&mut ast);
+/// assert_eq!(&parser.ast_to_markdown(&ast), "A **really** good test, which works **really** well");
+/// ```
+pub trait Detector {
+    /// The name of the detector, e.g. "local"
+    fn name(&self) -> &'static str;
+
+    /// Detects repetitions in the `Ast`.
+    ///
+    /// Returns the list of words that should be highlighted.
+    fn detect(&mut self, ast: &Ast) -> Vec<Finding>;
+}
+
+/// Highlights the words of an `Ast` according to a list of findings.
+///
+/// A word that has already been highlighted (e.g. by a previous detector) is left untouched.
+/// If the same word appears in multiple findings, the one with the highest value is used.
+///
+/// # Arguments
+///
+/// * `ast` – The `Ast` to modify
+/// * `detector` – The name of the detector that returned the findings
+/// * `findings` – The words to highlight
+pub fn highlight(ast: &mut Ast, detector: &'static str, findings: &[Finding]) {
+    let mut best: HashMap<usize, &Finding> = HashMap::new();
+    for finding in findings {
+        let replace = match best.get(&finding.index) {
+            None => true,
+            Some(other) => other.value < finding.value,
+        };
+        if replace {
+            best.insert(finding.index, finding);
+        }
+    }
+
+    for (i, finding) in best {
+        if let Some(&mut Word::Tracked(_, ref mut stemmed, ref mut v, ref mut option)) = ast.words.get_mut(i) {
+            if option.is_none() {
+                *stemmed = finding.stemmed.clone();
+                *v = finding.value;
+                *option = Some(Highlight {
+                    detector,
+                    severity: finding.severity,
+                    group: finding.group,
+                });
+            }
+        }
+    }
+}
+
+/// Runs multiple detectors on an `Ast`.
+///
+/// Detectors are run in the order they were added. When several of them flag the same word,
+/// the first one takes precedence.
+#[derive(Default)]
+pub struct Pipeline<'a> {
+    detectors: Vec<Box<dyn Detector + 'a>>,
+}
+
+impl<'a> Pipeline<'a> {
+    /// Creates a new, empty pipeline.
+    pub fn new() -> Pipeline<'a> {
+        Pipeline { detectors: vec![] }
+    }
+
+    /// Adds a detector at the end of the pipeline.
+    pub fn with_detector<D: Detector + 'a>(mut self, detector: D) -> Pipeline<'a> {
+        self.push(Box::new(detector));
+        self
+    }
+
+    /// Adds a boxed detector at the end of the pipeline.
+    pub fn push(&mut self, detector: Box<dyn Detector + 'a>) {
+        self.detectors.push(detector);
+    }
+
+    /// Returns the names of the detectors, in order.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.detectors.iter().map(|d| d.name()).collect()
+    }
+
+    /// Runs all detectors on the `Ast` and highlights the repetitions they find.
+    pub fn run(&mut self, ast: &mut Ast) {
+        for detector in &mut self.detectors {
+            let findings = detector.detect(ast);
+            highlight(ast, detector.name(), &findings);
+        }
+    }
+}
+
+/// Detects local repetitions, see `Parser::detect_local`.
+///
+/// Since it uses a `Session`, running it on multiple `Ast`s considers them as
+/// consecutive chunks of the same document.
+pub struct LocalDetector<'a> {
+    session: Session<'a>,
+    threshold: f32,
+}
+
+impl<'a> LocalDetector<'a> {
+    /// Creates a new local detector.
+    ///
+    /// # Arguments
+    ///
+    /// * `parser` – The parser containing the configuration
+    /// * `threshold` – The threshold to consider a repetition (e.g. 1.9)
+    pub fn new(parser: &'a Parser, threshold: f32) -> LocalDetector<'a> {
+        LocalDetector {
+            session: parser.new_session(),
+            threshold,
+        }
+    }
+}
+
+impl<'a> Detector for LocalDetector<'a> {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn detect(&mut self, ast: &Ast) -> Vec<Finding> {
+        self.session.find_local(ast, self.threshold)
+    }
+}
+
+/// Detects global repetitions, see `Parser::detect_global`.
+pub struct GlobalDetector<'a> {
+    parser: &'a Parser,
+    threshold: f32,
+}
+
+impl<'a> GlobalDetector<'a> {
+    /// Creates a new global detector.
+    ///
+    /// # Arguments
+    ///
+    /// * `parser` – The parser containing the configuration
+    /// * `threshold` – A threshold to highlight repetitions (e.g. 0.01)
+    pub fn new(parser: &'a Parser, threshold: f32) -> GlobalDetector<'a> {
+        GlobalDetector { parser, threshold }
+    }
+}
+
+impl<'a> Detector for GlobalDetector<'a> {
+    fn name(&self) -> &'static str {
+        "global"
+    }
+
+    fn detect(&mut self, ast: &Ast) -> Vec<Finding> {
+        let (h, count, clusters) = self.parser.clustered_stats(ast);
+        let mut res = vec![];
+
+        // If there are not enough words for the threshold, do nothing instead of
+        // underlining all words
+        if 1.0 / self.threshold >= (count as f32) {
+            return res;
+        }
+
+        // Each word value is the relative number of occurences
+        let offset = ast.get_body_offset();
+        let mut groups: HashMap<&str, usize> = HashMap::new();
+        for (i, word) in ast.get_body().iter().enumerate() {
+            if let Word::Tracked(_, ref stemmed, _, _) = *word {
+                let stemmed = clusters.get(stemmed).unwrap_or(stemmed);
+                let x = h.get(stemmed).expect("HashMap was not filled correctly") / (count as f32);
+                if x >= self.threshold {
+                    let n = groups.len();
+                    let group = *groups.entry(stemmed).or_insert(n);
+                    res.push(Finding {
+                        index: i + offset,
+                        value: x,
+                        severity: x / self.threshold,
+                        stemmed: stemmed.clone(),
+                        group,
+                    });
+                }
+            }
+        }
+        res
+    }
+}
+
+/// Detects repeated expressions, see `Parser::detect_phrases`.
+pub struct PhraseDetector<'a> {
+    parser: &'a Parser,
+    threshold: f32,
+}
+
+impl<'a> PhraseDetector<'a> {
+    /// Creates a new expression detector.
+    ///
+    /// # Arguments
+    ///
+    /// * `parser` – The parser containing the configuration
+    /// * `threshold` – The threshold to consider a repetition (e.g. 1.9)
+    pub fn new(parser: &'a Parser, threshold: f32) -> PhraseDetector<'a> {
+        PhraseDetector { parser, threshold }
+    }
+}
+
+impl<'a> Detector for PhraseDetector<'a> {
+    fn name(&self) -> &'static str {
+        "phrase"
+    }
+
+    fn detect(&mut self, ast: &Ast) -> Vec<Finding> {
+        let vec = ast.get_body();
+        let offset = ast.get_body_offset();
+        let (min, max) = self.parser.phrase_length;
+        let unit = self.parser.distance_unit;
+        let (positions, _) = unit.positions(ast);
+
+        // Words that can be part of an expression: (index in vec, key, is tracked)
+        let mut items: Vec<(usize, String, bool)> = vec![];
+        // Positions (in items) where a new sentence begins
+        let mut breaks: Vec<usize> = vec![0];
+        let mut sentence = None;
+        for (i, word) in vec.iter().enumerate() {
+            let item = match *word {
+                Word::Untracked(_) => continue,
+                Word::Ignored(_) if self.parser.phrase_skip_ignored => continue,
+                Word::Ignored(ref s) => (i, s.to_lowercase(), false),
+                Word::Tracked(_, ref stemmed, _, _) => (i, stemmed.clone(), true),
+            };
+            let s = ast.sentence_of(i + offset);
+            if s != sentence && !items.is_empty() {
+                breaks.push(items.len());
+            }
+            sentence = s;
+            items.push(item);
+        }
+        breaks.push(items.len());
+
+        // Position of an expression beginning at items[pos], to compute distances
+        let unit_pos = |pos: usize| if unit == DistanceUnit::Words {
+            pos as u32
+        } else {
+            positions[items[pos].0 + offset]
+        };
+
+        // (value, group) of each word
+        let mut values = vec![(0.0, 0); vec.len()];
+        let mut n_groups = 0;
+        for n in (min..max + 1).rev() {
+            // key -> (position of the last occurrence, positions of occurrences, group)
+            let mut h: HashMap<String, (usize, Vec<usize>, usize)> = HashMap::new();
+            for sentence in breaks.windows(2) {
+                let (begin, end) = (sentence[0], sentence[1]);
+                if end - begin < n {
+                    continue;
+                }
+                for pos in begin..end + 1 - n {
+                    let phrase = &items[pos..pos + n];
+                    if phrase.iter().filter(|&&(_, _, tracked)| tracked).count() < 2 {
+                        continue;
+                    }
+                    let key = phrase.iter()
+                                    .map(|(_, k, _)| k.as_str())
+                                    .collect::<Vec<_>>()
+                                    .join(" ");
+                    let (p_pos, mut subvec, mut group) = h.remove(&key).unwrap_or_default();
+                    if !subvec.is_empty() && pos < p_pos + n {
+                        // Overlaps with previous occurrence, e.g. "very very very"
+                        h.insert(key, (p_pos, subvec, group));
+                        continue;
+                    }
+                    if !subvec.is_empty() && unit_pos(pos) - unit_pos(p_pos) < self.parser.max_distance {
+                        subvec.push(pos);
+                        let v = subvec.len() as f32;
+                        for p in &subvec {
+                            for &(i, _, _) in &items[*p..*p + n] {
+                                if values[i].0 < v {
+                                    values[i] = (v, group);
+                                }
+                            }
+                        }
+                    } else {
+                        subvec = vec![pos];
+                        group = n_groups;
+                        n_groups += 1;
+                    }
+                    h.insert(key, (pos, subvec, group));
+                }
+            }
+        }
+
+        let mut res = vec![];
+        for (i, word) in vec.iter().enumerate() {
+            if let Word::Tracked(_, ref stemmed, _, _) = *word {
+                let (v, group) = values[i];
+                if v >= self.threshold {
+                    res.push(Finding {
+                        index: i + offset,
+                        value: v,
+                        severity: v / self.threshold,
+                        stemmed: stemmed.clone(),
+                        group,
+                    });
+                }
+            }
+        }
+        res
+    }
+}
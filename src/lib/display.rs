@@ -15,35 +15,142 @@
 // You should have received a copy of the GNU Lesser General Public License
 // along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
 
+use theme::Style;
+
 // Code to end shell colouring
-pub const SHELL_COLOUR_OFF: &'static str = "\x1B[0m";
+pub const SHELL_COLOUR_OFF: &str = "\x1B[0m";
 
 // Javascript function to print repetitions
-pub const SCRIPTS: &'static str = include_str!("html/scripts.js");
-
-/// Get a shell colour from a string
-pub fn get_shell_colour(colour: &str) -> Option<&'static str> {
-    match colour {
-        "red" => Some("\x1B[4;31m"),
-        "green" => Some("\x1B[4;32m"),
-        "cyan" => Some("\x1B[4;36m"),
-        "brown" => Some("\x1B[4;33m"),
-        "blue" => Some("\x1B[4;32m"),
-        "purple" => Some("\x1B[4;35m"),
-        "orange" => Some("\x1B[4;33m"),
-        _ => None,
+pub const SCRIPTS: &str = include_str!("html/scripts.js");
+
+// Stylesheet and Javascript code of the interactive report
+pub const REPORT_CSS: &str = include_str!("html/report.css");
+pub const REPORT_SCRIPTS: &str = include_str!("html/report.js");
+
+/// Get the CSS declarations to display a word with a style
+pub fn style_to_css(style: &Style) -> String {
+    let mut res = String::new();
+    if style.underline {
+        res.push_str("text-decoration: underline; ");
+    }
+    if let Some(ref colour) = style.colour {
+        res.push_str(&format!("color: {}; ", colour));
+    }
+    if let Some(ref colour) = style.background {
+        res.push_str(&format!("background-color: {}; ", colour));
+    }
+    if style.bold {
+        res.push_str("font-weight: bold; ");
+    }
+    if style.italic {
+        res.push_str("font-style: italic; ");
+    }
+    res.pop();
+    res
+}
+
+/// Escape a string so it can be put in an HTML document, as text or as the value of an
+/// attribute
+pub fn escape_html(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&#39;"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Escape a string so it can be put between quotes in a JSON document
+pub fn escape_json(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            c if (c as u32) < 0x20 => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
     }
+    res
 }
 
-/// Generate the style attribute according to x and threshold
-pub fn value_to_colour(x: f32, threshold: f32) -> &'static str {
-    if x < threshold {
-        panic!("value_to_colour called with x < threshold");
-    } else if x < 1.5 * threshold {
-        "green"
-    } else if x < 2.0 * threshold {
-        "orange"
+/// Convert a float to a JSON number (JSON doesn't allow NaN or infinity)
+pub fn float_to_json(x: f32) -> String {
+    if x.is_finite() {
+        format!("{}", x)
     } else {
-        "red"
+        "null".to_owned()
+    }
+}
+
+/// Convert an optional index to a JSON number, or `null`
+pub fn index_to_json(i: Option<usize>) -> String {
+    match i {
+        Some(i) => format!("{}", i),
+        None => "null".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::{Parser, InputFormat};
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(escape_html(""), "");
+        assert_eq!(escape_html("<a href=\"x\" title='y'>&amp;</a>"),
+                   "&lt;a href=&quot;x&quot; title=&#39;y&#39;&gt;&amp;amp;&lt;/a&gt;");
+        assert_eq!(escape_html("ça 𝄞\r\n"), "ça 𝄞\r\n");
+    }
+
+    #[test]
+    fn escapes_json() {
+        assert_eq!(escape_json(""), "");
+        assert_eq!(escape_json("\"a\\b\"\r\n\t\u{1}\u{1f}"), "\\\"a\\\\b\\\"\\r\\n\\t\\u0001\\u001f");
+        assert_eq!(escape_json("</script> ça 𝄞"), "</script> ça 𝄞");
+        assert_eq!(float_to_json(f32::NAN), "null");
+        assert_eq!(float_to_json(1.5), "1.5");
+        assert_eq!(index_to_json(None), "null");
+    }
+
+    #[test]
+    fn styles() {
+        let mut style = Style {
+            colour: None,
+            background: None,
+            bold: false,
+            italic: false,
+            underline: false,
+        };
+        assert_eq!(style_to_css(&style), "");
+        style.colour = Some("#ff0000".to_owned());
+        style.background = Some("yellow".to_owned());
+        style.bold = true;
+        style.italic = true;
+        style.underline = true;
+        assert_eq!(style_to_css(&style),
+                   "text-decoration: underline; color: #ff0000; background-color: yellow; \
+                    font-weight: bold; font-style: italic;");
+    }
+
+    #[test]
+    fn html_output_is_escaped() {
+        let parser = Parser::new("english").unwrap().with_input_format(InputFormat::Text);
+        let mut ast = parser.tokenize("<b onclick=\"x\">works</b> & works").unwrap();
+        parser.detect_local(&mut ast, 1.9);
+        let html = parser.ast_to_html(&ast, true);
+        assert!(!html.contains("<b onclick"));
+        assert!(html.contains(">onclick</span>=&quot;<span data-stem=\"x\">x</span>&quot;&gt;"));
+        assert!(html.contains("&gt; &amp; <span"));
     }
 }
@@ -0,0 +1,447 @@
+// Copyright (C) 2015, 2016 Élisabeth HENRY.
+//
+// This file is part of Caribon.
+//
+// Caribon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Caribon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::strsim::levenshtein;
+
+use std::collections::HashMap;
+
+/// Number of keys above which lookups use the tree instead of comparing the pattern to all keys
+const TREE_THRESHOLD: usize = 128;
+
+/// Returns a set of the characters of a string, as bits of an integer.
+///
+/// Different characters can share a bit, but the number of bits set in the mask of a string
+/// and not in the one of another one is still a lower bound of the Levenshtein distance
+/// between them, since each of these characters must be deleted or substituted.
+fn mask(chars: &[char]) -> u64 {
+    chars.iter().fold(0, |mask, &c| mask | 1 << (c as u32 % 64))
+}
+
+/// Returns a lower bound of the Levenshtein distance between two strings, from their
+/// characters and masks
+fn lower_bound(a: &[char], a_mask: u64, b: &[char], b_mask: u64) -> usize {
+    let length = if a.len() > b.len() { a.len() - b.len() } else { b.len() - a.len() };
+    let missing = (a_mask & !b_mask).count_ones().max((b_mask & !a_mask).count_ones());
+    length.max(missing as usize)
+}
+
+/// Returns the Levenshtein distance between two strings if it is at most `bound`.
+///
+/// This gives the same result as `strsim::levenshtein`, which it can't use since it has no
+/// way to stop as soon as the distance is known to be too high, as it is for most keys.
+fn levenshtein_within(a: &[char], b: &[char], bound: usize) -> Option<usize> {
+    // Common prefix and suffix don't change the distance
+    let prefix = a.iter().zip(b).take_while(|&(x, y)| x == y).count();
+    let (a, b) = (&a[prefix..], &b[prefix..]);
+    let suffix = a.iter().rev().zip(b.iter().rev()).take_while(|&(x, y)| x == y).count();
+    let (a, b) = (&a[..a.len() - suffix], &b[..b.len() - suffix]);
+    if a.is_empty() || b.is_empty() {
+        let d = a.len().max(b.len());
+        return if d <= bound { Some(d) } else { None };
+    }
+
+    // Distances between the beginning of `a` and the ones of `b` (on the stack if possible)
+    let mut buffer = [0; 32];
+    let mut heap;
+    let row: &mut [usize] = if b.len() < buffer.len() {
+        &mut buffer[..=b.len()]
+    } else {
+        heap = vec![0; b.len() + 1];
+        &mut heap
+    };
+    for (j, d) in row.iter_mut().enumerate() {
+        *d = j;
+    }
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        let mut min = row[0];
+        for (j, cb) in b.iter().enumerate() {
+            let d = (diagonal + (ca != cb) as usize).min(row[j] + 1).min(row[j + 1] + 1);
+            diagonal = row[j + 1];
+            row[j + 1] = d;
+            min = min.min(d);
+        }
+        // Distances can only grow from there
+        if min > bound {
+            return None;
+        }
+    }
+    Some(row[b.len()]).filter(|&d| d <= bound)
+}
+
+/// A node of the BK-tree
+struct Node {
+    key: String,
+    /// Characters of the key
+    chars: Vec<char>,
+    /// Characters of the key, see `mask`
+    mask: u64,
+    /// Whether the key is in the index, or has been removed
+    present: bool,
+    /// Children, with their distance to this node
+    children: Vec<(usize, usize)>,
+}
+
+/// An index of strings allowing to find the closest one to a pattern without comparing it
+/// to all of them.
+///
+/// It is a BK-tree using Levenshtein distance: thanks to the triangle inequality, a lookup
+/// only needs to compute the distance to a few keys. Since a BK-tree doesn't support
+/// removal, removed keys are only marked as such, and the tree is rebuilt when there are
+/// too many of them. When there are only a few keys, the tree is not built and the pattern
+/// is compared to all of them, which is faster.
+///
+/// # Example
+///
+/// ```rust
+/// use caribon::FuzzyIndex;
+///
+/// let mut index = FuzzyIndex::new();
+/// index.insert("caribou");
+/// index.insert("caribon");
+/// index.insert("rust");
+/// assert_eq!(index.closest("carbon", 2), Some((1, "caribon")));
+/// index.remove("caribon");
+/// assert_eq!(index.closest("carbon", 2), Some((2, "caribou")));
+/// assert_eq!(index.closest("carbon", 1), None);
+/// ```
+#[derive(Default)]
+pub struct FuzzyIndex {
+    nodes: Vec<Node>,
+    /// Position of each key in `nodes`
+    positions: HashMap<String, usize>,
+    /// Number of keys that are present
+    len: usize,
+    /// Whether the nodes are organized as a tree (else `children` are empty)
+    tree: bool,
+}
+
+impl FuzzyIndex {
+    /// Creates a new, empty index.
+    pub fn new() -> FuzzyIndex {
+        FuzzyIndex {
+            nodes: vec![],
+            positions: HashMap::new(),
+            len: 0,
+            tree: false,
+        }
+    }
+
+    /// Returns the number of keys in the index.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the index contains no key.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns true if the index contains `key`.
+    pub fn contains(&self, key: &str) -> bool {
+        match self.positions.get(key) {
+            Some(&i) => self.nodes[i].present,
+            None => false,
+        }
+    }
+
+    /// Returns an iterator over the keys, in no particular order.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.nodes.iter().filter(|node| node.present).map(|node| node.key.as_str())
+    }
+
+    /// Removes all keys.
+    pub fn clear(&mut self) {
+        *self = FuzzyIndex::new();
+    }
+
+    /// Adds a key to the index (doing nothing if it is already there).
+    pub fn insert(&mut self, key: &str) {
+        if let Some(&i) = self.positions.get(key) {
+            if !self.nodes[i].present {
+                self.nodes[i].present = true;
+                self.len += 1;
+            }
+            return;
+        }
+
+        let chars: Vec<char> = key.chars().collect();
+        self.nodes.push(Node {
+            key: key.to_owned(),
+            mask: mask(&chars),
+            chars,
+            present: true,
+            children: vec![],
+        });
+        self.positions.insert(key.to_owned(), self.nodes.len() - 1);
+        self.len += 1;
+        if self.tree {
+            self.attach(self.nodes.len() - 1);
+        } else if self.len >= TREE_THRESHOLD {
+            self.rebuild();
+        }
+    }
+
+    /// Removes a key from the index (doing nothing if it is not there).
+    pub fn remove(&mut self, key: &str) {
+        if !self.tree {
+            // Without a tree, nodes can be moved
+            if let Some(i) = self.positions.remove(key) {
+                self.nodes.swap_remove(i);
+                if let Some(node) = self.nodes.get(i) {
+                    *self.positions.get_mut(&node.key).unwrap() = i;
+                }
+                self.len -= 1;
+            }
+            return;
+        }
+        if let Some(&i) = self.positions.get(key) {
+            if self.nodes[i].present {
+                self.nodes[i].present = false;
+                self.len -= 1;
+                // Get rid of removed keys when they are the majority
+                if self.nodes.len() > 2 * self.len + 32 {
+                    self.rebuild();
+                }
+            }
+        }
+    }
+
+    /// Adds a node to the tree
+    fn attach(&mut self, new: usize) {
+        if new == 0 {
+            return;
+        }
+        let mut current = 0;
+        loop {
+            let d = levenshtein(&self.nodes[new].key, &self.nodes[current].key);
+            match self.nodes[current].children.iter().find(|&&(dist, _)| dist == d) {
+                Some(&(_, child)) => current = child,
+                None => {
+                    self.nodes[current].children.push((d, new));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Removes the nodes of removed keys, and builds the tree if there are enough keys
+    fn rebuild(&mut self) {
+        let nodes = ::std::mem::take(&mut self.nodes);
+        let len = nodes.iter().filter(|node| node.present).count();
+        self.clear();
+        self.tree = len >= TREE_THRESHOLD;
+        for node in nodes.into_iter().filter(|node| node.present) {
+            self.insert(&node.key);
+        }
+    }
+
+    /// Returns the keys at distance `max_distance` or less of `pattern`, with their distance.
+    pub fn find(&self, pattern: &str, max_distance: usize) -> Vec<(usize, &str)> {
+        let mut res = vec![];
+        self.search(pattern, max_distance, |d, key| res.push((d, key)));
+        res
+    }
+
+    /// Returns the closest key at distance `max_distance` or less of `pattern`, with its
+    /// distance. If several keys are at the same distance, the smallest one is returned.
+    pub fn closest(&self, pattern: &str, max_distance: usize) -> Option<(usize, &str)> {
+        let mut res: Option<(usize, &str)> = None;
+        self.search(pattern, max_distance, |d, key| if res.map_or(true, |best| (d, key) < best) {
+            res = Some((d, key));
+        });
+        res
+    }
+
+    /// Calls `f` with each key at distance `max_distance` or less of `pattern`, and its
+    /// distance
+    fn search<'a, F: FnMut(usize, &'a str)>(&'a self, pattern: &str, max_distance: usize, mut f: F) {
+        if max_distance == 0 {
+            if let Some((key, &i)) = self.positions.get_key_value(pattern) {
+                if self.nodes[i].present {
+                    f(0, key);
+                }
+            }
+            return;
+        }
+        let chars: Vec<char> = pattern.chars().collect();
+        let pattern_mask = mask(&chars);
+        if !self.tree {
+            for node in &self.nodes {
+                if lower_bound(&chars, pattern_mask, &node.chars, node.mask) > max_distance {
+                    continue;
+                }
+                if let Some(d) = levenshtein_within(&chars, &node.chars, max_distance) {
+                    f(d, &node.key);
+                }
+            }
+            return;
+        }
+
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            // Children are at distance `d - max_distance` or more of the pattern, so if `d`
+            // is higher than this, none of them can match
+            let farthest = node.children.iter().map(|&(dist, _)| dist).max().unwrap_or(0);
+            let bound = max_distance + farthest;
+            if lower_bound(&chars, pattern_mask, &node.chars, node.mask) > bound {
+                continue;
+            }
+            let d = match levenshtein_within(&chars, &node.chars, bound) {
+                Some(d) => d,
+                None => continue,
+            };
+            if node.present && d <= max_distance {
+                f(d, &node.key);
+            }
+            for &(dist, child) in &node.children {
+                if dist + max_distance >= d && dist <= d + max_distance {
+                    stack.push(child);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the keys found by an index, sorted
+    fn find(index: &FuzzyIndex, pattern: &str, max_distance: usize) -> Vec<(usize, String)> {
+        let mut res: Vec<(usize, String)> = index.find(pattern, max_distance)
+                                                 .into_iter()
+                                                 .map(|(d, key)| (d, key.to_owned()))
+                                                 .collect();
+        res.sort();
+        res
+    }
+
+    /// Returns the keys at distance `max_distance` or less of `pattern`, comparing it to all
+    fn brute_force(keys: &[String], pattern: &str, max_distance: usize) -> Vec<(usize, String)> {
+        let mut res: Vec<(usize, String)> = keys.iter()
+                                                .map(|key| (levenshtein(pattern, key), key.clone()))
+                                                .filter(|&(d, _)| d <= max_distance)
+                                                .collect();
+        res.sort();
+        res
+    }
+
+    #[test]
+    fn bounded_distance() {
+        let words = ["", "a", "ab", "ba", "caribon", "caribou", "carbon", "été", "ete", "𝄞𝄞",
+                     "highlight", "higlight", "abcdefghijklmnopqrstuvwxyzabcdefghij",
+                     "abcdefghijklmnopqrstuvwxyzabcdefgXij"];
+        for a in &words {
+            for b in &words {
+                let (a_chars, b_chars): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+                let d = levenshtein(a, b);
+                assert!(lower_bound(&a_chars, mask(&a_chars), &b_chars, mask(&b_chars)) <= d,
+                        "{} {}", a, b);
+                for bound in 0..d + 2 {
+                    assert_eq!(levenshtein_within(&a_chars, &b_chars, bound),
+                               if d <= bound { Some(d) } else { None },
+                               "{} {} {}", a, b, bound);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn empty_index() {
+        let mut index = FuzzyIndex::new();
+        assert!(index.is_empty());
+        assert_eq!(index.closest("", 3), None);
+        assert_eq!(index.closest("caribou", 3), None);
+        index.remove("caribou");
+        assert!(index.is_empty());
+    }
+
+    #[test]
+    fn empty_and_unicode_keys() {
+        let mut index = FuzzyIndex::new();
+        index.insert("");
+        index.insert("été");
+        index.insert("𝄞𝄞");
+        index.insert("𝄞𝄞");
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.closest("", 0), Some((0, "")));
+        assert_eq!(index.closest("a", 1), Some((1, "")));
+        // Distances are counted in characters, not bytes
+        assert_eq!(index.closest("ete", 2), Some((2, "été")));
+        assert_eq!(find(&index, "𝄞", 1), vec![(1, "".to_owned()), (1, "𝄞𝄞".to_owned())]);
+    }
+
+    #[test]
+    fn insert_and_remove() {
+        let mut index = FuzzyIndex::new();
+        index.insert("cat");
+        index.insert("cat");
+        index.insert("hat");
+        assert_eq!(index.len(), 2);
+        index.remove("cat");
+        index.remove("cat");
+        assert_eq!(index.len(), 1);
+        assert!(!index.contains("cat"));
+        assert_eq!(index.closest("cat", 1), Some((1, "hat")));
+        index.insert("cat");
+        assert!(index.contains("cat"));
+        assert_eq!(index.closest("cat", 1), Some((0, "cat")));
+        // Ties are broken by the smallest key
+        index.remove("cat");
+        index.insert("bat");
+        assert_eq!(index.closest("cat", 1), Some((1, "bat")));
+        index.clear();
+        assert!(index.is_empty());
+        assert_eq!(index.keys().count(), 0);
+    }
+
+    #[test]
+    fn tree_matches_brute_force() {
+        // Enough keys to build the tree, with removals to trigger rebuilds
+        let keys: Vec<String> = (0..600u32)
+            .map(|i| {
+                let s: String = (0..1 + i % 7).map(|j| (b'a' + ((i * 7 + j * 13) % 5) as u8) as char).collect();
+                format!("{}{}", s, i % 11)
+            })
+            .collect();
+        let mut index = FuzzyIndex::new();
+        for key in &keys {
+            index.insert(key);
+        }
+        let mut present: Vec<String> = keys.clone();
+        present.sort();
+        present.dedup();
+        assert_eq!(index.len(), present.len());
+        for key in keys.iter().step_by(3) {
+            index.remove(key);
+        }
+        present.retain(|key| index.contains(key));
+        assert!(index.tree);
+        assert_eq!(index.keys().count(), present.len());
+
+        for pattern in &["", "a", "abc1", "eeee10", "bdacebd3", "zzzzzzzzzz"] {
+            for max_distance in 0..4 {
+                assert_eq!(find(&index, pattern, max_distance),
+                           brute_force(&present, pattern, max_distance),
+                           "{} {}", pattern, max_distance);
+            }
+        }
+    }
+}
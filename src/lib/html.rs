@@ -0,0 +1,438 @@
+// Copyright (C) 2015, 2016 Élisabeth HENRY.
+//
+// This file is part of Caribon.
+//
+// Caribon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Caribon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Helpers to recognize HTML markup, used by `Parser::tokenize_html`.
+//!
+//! They follow the tokenization rules of the HTML5 specification
+//! (<https://html.spec.whatwg.org/multipage/parsing.html#tokenization>), without building a
+//! tree. Like browsers, they never fail: markup that is not closed at the end of the
+//! document extends to its end.
+
+use markdown::starts_with;
+
+/// Elements whose content is not parsed, and is not text
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style", "xmp", "iframe", "noembed", "noframes"];
+
+/// Named character references of HTML 4 (and `&apos;`), with the character they stand for
+const ENTITIES: &[(&str, char)] = &[("quot", '"'), ("amp", '&'), ("apos", '\''),
+                                    ("lt", '<'), ("gt", '>'), ("OElig", 'Œ'), ("oelig", 'œ'),
+                                    ("Scaron", 'Š'), ("scaron", 'š'), ("Yuml", 'Ÿ'), ("circ", 'ˆ'),
+                                    ("tilde", '˜'), ("ensp", '\u{2002}'), ("emsp", '\u{2003}'),
+                                    ("thinsp", '\u{2009}'), ("zwnj", '\u{200c}'),
+                                    ("zwj", '\u{200d}'), ("lrm", '\u{200e}'), ("rlm", '\u{200f}'),
+                                    ("ndash", '–'), ("mdash", '—'), ("lsquo", '‘'), ("rsquo", '’'),
+                                    ("sbquo", '‚'), ("ldquo", '“'), ("rdquo", '”'), ("bdquo", '„'),
+                                    ("dagger", '†'), ("Dagger", '‡'), ("permil", '‰'),
+                                    ("lsaquo", '‹'), ("rsaquo", '›'), ("euro", '€'),
+                                    ("nbsp", '\u{a0}'), ("iexcl", '¡'), ("cent", '¢'),
+                                    ("pound", '£'), ("curren", '¤'), ("yen", '¥'), ("brvbar", '¦'),
+                                    ("sect", '§'), ("uml", '¨'), ("copy", '©'), ("ordf", 'ª'),
+                                    ("laquo", '«'), ("not", '¬'), ("shy", '\u{ad}'), ("reg", '®'),
+                                    ("macr", '¯'), ("deg", '°'), ("plusmn", '±'), ("sup2", '²'),
+                                    ("sup3", '³'), ("acute", '´'), ("micro", 'µ'), ("para", '¶'),
+                                    ("middot", '·'), ("cedil", '¸'), ("sup1", '¹'), ("ordm", 'º'),
+                                    ("raquo", '»'), ("frac14", '¼'), ("frac12", '½'),
+                                    ("frac34", '¾'), ("iquest", '¿'), ("Agrave", 'À'),
+                                    ("Aacute", 'Á'), ("Acirc", 'Â'), ("Atilde", 'Ã'),
+                                    ("Auml", 'Ä'), ("Aring", 'Å'), ("AElig", 'Æ'), ("Ccedil", 'Ç'),
+                                    ("Egrave", 'È'), ("Eacute", 'É'), ("Ecirc", 'Ê'),
+                                    ("Euml", 'Ë'), ("Igrave", 'Ì'), ("Iacute", 'Í'),
+                                    ("Icirc", 'Î'), ("Iuml", 'Ï'), ("ETH", 'Ð'), ("Ntilde", 'Ñ'),
+                                    ("Ograve", 'Ò'), ("Oacute", 'Ó'), ("Ocirc", 'Ô'),
+                                    ("Otilde", 'Õ'), ("Ouml", 'Ö'), ("times", '×'),
+                                    ("Oslash", 'Ø'), ("Ugrave", 'Ù'), ("Uacute", 'Ú'),
+                                    ("Ucirc", 'Û'), ("Uuml", 'Ü'), ("Yacute", 'Ý'), ("THORN", 'Þ'),
+                                    ("szlig", 'ß'), ("agrave", 'à'), ("aacute", 'á'),
+                                    ("acirc", 'â'), ("atilde", 'ã'), ("auml", 'ä'), ("aring", 'å'),
+                                    ("aelig", 'æ'), ("ccedil", 'ç'), ("egrave", 'è'),
+                                    ("eacute", 'é'), ("ecirc", 'ê'), ("euml", 'ë'),
+                                    ("igrave", 'ì'), ("iacute", 'í'), ("icirc", 'î'),
+                                    ("iuml", 'ï'), ("eth", 'ð'), ("ntilde", 'ñ'), ("ograve", 'ò'),
+                                    ("oacute", 'ó'), ("ocirc", 'ô'), ("otilde", 'õ'),
+                                    ("ouml", 'ö'), ("divide", '÷'), ("oslash", 'ø'),
+                                    ("ugrave", 'ù'), ("uacute", 'ú'), ("ucirc", 'û'),
+                                    ("uuml", 'ü'), ("yacute", 'ý'), ("thorn", 'þ'), ("yuml", 'ÿ'),
+                                    ("fnof", 'ƒ'), ("Alpha", 'Α'), ("Beta", 'Β'), ("Gamma", 'Γ'),
+                                    ("Delta", 'Δ'), ("Epsilon", 'Ε'), ("Zeta", 'Ζ'), ("Eta", 'Η'),
+                                    ("Theta", 'Θ'), ("Iota", 'Ι'), ("Kappa", 'Κ'), ("Lambda", 'Λ'),
+                                    ("Mu", 'Μ'), ("Nu", 'Ν'), ("Xi", 'Ξ'), ("Omicron", 'Ο'),
+                                    ("Pi", 'Π'), ("Rho", 'Ρ'), ("Sigma", 'Σ'), ("Tau", 'Τ'),
+                                    ("Upsilon", 'Υ'), ("Phi", 'Φ'), ("Chi", 'Χ'), ("Psi", 'Ψ'),
+                                    ("Omega", 'Ω'), ("alpha", 'α'), ("beta", 'β'), ("gamma", 'γ'),
+                                    ("delta", 'δ'), ("epsilon", 'ε'), ("zeta", 'ζ'), ("eta", 'η'),
+                                    ("theta", 'θ'), ("iota", 'ι'), ("kappa", 'κ'), ("lambda", 'λ'),
+                                    ("mu", 'μ'), ("nu", 'ν'), ("xi", 'ξ'), ("omicron", 'ο'),
+                                    ("pi", 'π'), ("rho", 'ρ'), ("sigmaf", 'ς'), ("sigma", 'σ'),
+                                    ("tau", 'τ'), ("upsilon", 'υ'), ("phi", 'φ'), ("chi", 'χ'),
+                                    ("psi", 'ψ'), ("omega", 'ω'), ("thetasym", 'ϑ'),
+                                    ("upsih", 'ϒ'), ("piv", 'ϖ'), ("bull", '•'), ("hellip", '…'),
+                                    ("prime", '′'), ("Prime", '″'), ("oline", '‾'), ("frasl", '⁄'),
+                                    ("weierp", '℘'), ("image", 'ℑ'), ("real", 'ℜ'), ("trade", '™'),
+                                    ("alefsym", 'ℵ'), ("larr", '←'), ("uarr", '↑'), ("rarr", '→'),
+                                    ("darr", '↓'), ("harr", '↔'), ("crarr", '↵'), ("lArr", '⇐'),
+                                    ("uArr", '⇑'), ("rArr", '⇒'), ("dArr", '⇓'), ("hArr", '⇔'),
+                                    ("forall", '∀'), ("part", '∂'), ("exist", '∃'), ("empty", '∅'),
+                                    ("nabla", '∇'), ("isin", '∈'), ("notin", '∉'), ("ni", '∋'),
+                                    ("prod", '∏'), ("sum", '∑'), ("minus", '−'), ("lowast", '∗'),
+                                    ("radic", '√'), ("prop", '∝'), ("infin", '∞'), ("ang", '∠'),
+                                    ("and", '∧'), ("or", '∨'), ("cap", '∩'), ("cup", '∪'),
+                                    ("int", '∫'), ("there4", '∴'), ("sim", '∼'), ("cong", '≅'),
+                                    ("asymp", '≈'), ("ne", '≠'), ("equiv", '≡'), ("le", '≤'),
+                                    ("ge", '≥'), ("sub", '⊂'), ("sup", '⊃'), ("nsub", '⊄'),
+                                    ("sube", '⊆'), ("supe", '⊇'), ("oplus", '⊕'), ("otimes", '⊗'),
+                                    ("perp", '⊥'), ("sdot", '⋅'), ("lceil", '⌈'), ("rceil", '⌉'),
+                                    ("lfloor", '⌊'), ("rfloor", '⌋'), ("lang", '〈'), ("rang", '〉'),
+                                    ("loz", '◊'), ("spades", '♠'), ("clubs", '♣'), ("hearts", '♥'),
+                                    ("diams", '♦')];
+
+/// Characters that numeric character references from 0x80 to 0x9F stand for, as in
+/// Windows-1252
+const WINDOWS_1252: [u32; 32] = [0x20AC, 0x81, 0x201A, 0x192, 0x201E, 0x2026, 0x2020, 0x2021,
+                                 0x2C6, 0x2030, 0x160, 0x2039, 0x152, 0x8D, 0x17D, 0x8F, 0x90,
+                                 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014, 0x2DC,
+                                 0x2122, 0x161, 0x203A, 0x153, 0x9D, 0x17E, 0x178];
+
+/// A start or end tag
+#[derive(Debug)]
+pub struct Tag {
+    /// The name of the tag, in lowercase
+    pub name: String,
+    /// Whether it is an end tag (e.g. `</p>`)
+    pub end: bool,
+    /// The name (in lowercase) of each attribute, with the position of the beginning and
+    /// the end of its value, without quotes
+    pub attributes: Vec<(String, usize, usize)>,
+    /// The length of the tag
+    pub length: usize,
+}
+
+/// Returns true if `c` is whitespace for HTML
+fn is_space(c: char) -> bool {
+    c == ' ' || c == '\t' || c == '\n' || c == '\r' || c == '\x0C'
+}
+
+/// Returns the length until the first occurrence of `end` (included), or the whole length
+fn length_until(chars: &[char], start: usize, end: &str) -> usize {
+    let mut i = start;
+    while i < chars.len() {
+        if starts_with(&chars[i..], end) {
+            return i + end.chars().count();
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+/// Returns the length of a comment (`<!-- ... -->`), a CDATA section, a doctype or
+/// another declaration (`<!...>`), a processing instruction (`<?...>`) or an invalid end
+/// tag (e.g. `</ foo>`), or 0 if `chars` doesn't start with one of them
+pub fn declaration_length(chars: &[char]) -> usize {
+    if starts_with(chars, "<!--") {
+        // "<!-->" and "<!--->" are (empty) comments
+        if starts_with(&chars[4..], ">") {
+            return 5;
+        } else if starts_with(&chars[4..], "->") {
+            return 6;
+        }
+        let mut i = 4;
+        while i < chars.len() {
+            if starts_with(&chars[i..], "-->") {
+                return i + 3;
+            } else if starts_with(&chars[i..], "--!>") {
+                return i + 4;
+            }
+            i += 1;
+        }
+        chars.len()
+    } else if starts_with(chars, "<![CDATA[") {
+        length_until(chars, 9, "]]>")
+    } else if starts_with(chars, "<!") || starts_with(chars, "<?") {
+        length_until(chars, 2, ">")
+    } else if starts_with(chars, "</") {
+        match chars.get(2) {
+            Some(c) if c.is_ascii_alphabetic() => 0,
+            Some(_) => length_until(chars, 2, ">"),
+            None => 0,
+        }
+    } else {
+        0
+    }
+}
+
+/// Reads a start or end tag, or returns `None` if `chars` doesn't start with one (a `<`
+/// that is not followed by a letter, or by `/` and a letter, is just text)
+pub fn tag(chars: &[char]) -> Option<Tag> {
+    let end = starts_with(chars, "</");
+    let mut i = if end { 2 } else { 1 };
+    match chars.get(i) {
+        Some(c) if c.is_ascii_alphabetic() => (),
+        _ => return None,
+    }
+
+    let start = i;
+    while i < chars.len() && !is_space(chars[i]) && chars[i] != '/' && chars[i] != '>' {
+        i += 1;
+    }
+    let name = chars[start..i].iter().collect::<String>().to_lowercase();
+
+    let mut attributes = vec![];
+    loop {
+        while i < chars.len() && (is_space(chars[i]) || chars[i] == '/') {
+            i += 1;
+        }
+        if i >= chars.len() || chars[i] == '>' {
+            break;
+        }
+
+        // An attribute name can start with '='
+        let start = i;
+        i += 1;
+        while i < chars.len() && !is_space(chars[i]) && !"/>=".contains(chars[i]) {
+            i += 1;
+        }
+        let attribute = chars[start..i].iter().collect::<String>().to_lowercase();
+        let mut j = i;
+        while j < chars.len() && is_space(chars[j]) {
+            j += 1;
+        }
+        if chars.get(j) != Some(&'=') {
+            attributes.push((attribute, i, i));
+            continue;
+        }
+        i = j + 1;
+        while i < chars.len() && is_space(chars[i]) {
+            i += 1;
+        }
+        let (value_start, value_end) = match chars.get(i) {
+            Some(&quote) if quote == '"' || quote == '\'' => {
+                let start = i + 1;
+                i = start;
+                while i < chars.len() && chars[i] != quote {
+                    i += 1;
+                }
+                let end = i;
+                i = (i + 1).min(chars.len());
+                (start, end)
+            }
+            _ => {
+                let start = i;
+                while i < chars.len() && !is_space(chars[i]) && chars[i] != '>' {
+                    i += 1;
+                }
+                (start, i)
+            }
+        };
+        attributes.push((attribute, value_start, value_end));
+    }
+
+    Some(Tag {
+        name,
+        end,
+        attributes,
+        length: (i + 1).min(chars.len()),
+    })
+}
+
+/// Returns true if the content of an element is neither text nor markup (e.g. `script`)
+pub fn is_raw_text(name: &str) -> bool {
+    RAW_TEXT_ELEMENTS.contains(&name)
+}
+
+/// Returns the length of the content of a raw text element (see `is_raw_text`), until its
+/// end tag
+pub fn raw_text_length(chars: &[char], name: &str) -> usize {
+    let end: Vec<char> = format!("</{}", name).chars().collect();
+    let mut i = 0;
+    while i + end.len() <= chars.len() {
+        let matches = chars[i..i + end.len()].iter()
+                                              .zip(&end)
+                                              .all(|(a, b)| a.to_ascii_lowercase() == *b);
+        if matches {
+            match chars.get(i + end.len()) {
+                Some(&c) if is_space(c) || c == '/' || c == '>' => return i,
+                None => return i,
+                _ => (),
+            }
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+/// Reads a character reference (e.g. `&eacute;`, `&#233;` or `&#xE9;`), returning its
+/// length and the character it stands for, or `None` if `chars` doesn't start with one
+pub fn entity(chars: &[char]) -> Option<(usize, char)> {
+    if chars.first() != Some(&'&') {
+        return None;
+    }
+    let end = chars.iter().take(34).position(|&c| c == ';')?;
+    let name: String = chars[1..end].iter().collect();
+    let c = if let Some(number) = name.strip_prefix('#') {
+        let (digits, radix) = match number.strip_prefix('x').or_else(|| number.strip_prefix('X')) {
+            Some(hex) => (hex, 16),
+            None => (number, 10),
+        };
+        if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+            return None;
+        }
+        // Invalid references stand for the replacement character
+        match u32::from_str_radix(digits, radix) {
+            Ok(n @ 0x80..=0x9F) => char::from_u32(WINDOWS_1252[n as usize - 0x80]).unwrap(),
+            Ok(0) | Err(_) => '\u{FFFD}',
+            Ok(n) => char::from_u32(n).unwrap_or('\u{FFFD}'),
+        }
+    } else {
+        ENTITIES.iter().find(|entity| entity.0 == name)?.1
+    };
+    Some((end + 1, c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::{Parser, InputFormat};
+    use word::Word;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    /// Returns the tracked words of an HTML document, with their stem
+    fn tracked(s: &str) -> Vec<(String, String)> {
+        let parser = Parser::new("english").unwrap()
+                                           .with_input_format(InputFormat::Html)
+                                           .with_html_attributes("alt");
+        let ast = parser.tokenize(s).unwrap();
+        let text: String = ast.words.iter().map(|w| w.as_str()).collect();
+        assert_eq!(text, s);
+        ast.words.iter()
+           .filter_map(|w| match *w {
+               Word::Tracked(ref s, ref stem, _, _) => Some((s.clone(), stem.clone())),
+               _ => None,
+           })
+           .collect()
+    }
+
+    fn tracked_words(s: &str) -> Vec<String> {
+        tracked(s).into_iter().map(|w| w.0).collect()
+    }
+
+    #[test]
+    fn empty_input() {
+        assert_eq!(declaration_length(&[]), 0);
+        assert!(tag(&[]).is_none());
+        assert_eq!(raw_text_length(&[], "script"), 0);
+        assert!(entity(&[]).is_none());
+        assert!(tracked("").is_empty());
+    }
+
+    #[test]
+    fn declarations() {
+        assert_eq!(declaration_length(&chars("<!-- a -- b --> c")), 15);
+        assert_eq!(declaration_length(&chars("<!--> c")), 5);
+        assert_eq!(declaration_length(&chars("<!---> c")), 6);
+        assert_eq!(declaration_length(&chars("<!-- a --!> c")), 11);
+        assert_eq!(declaration_length(&chars("<![CDATA[a]]> c")), 13);
+        assert_eq!(declaration_length(&chars("<!DOCTYPE html>\r\n")), 15);
+        assert_eq!(declaration_length(&chars("<?xml version=\"1.0\"?> c")), 21);
+        assert_eq!(declaration_length(&chars("</ a> c")), 5);
+        assert_eq!(declaration_length(&chars("</a> c")), 0);
+        assert_eq!(declaration_length(&chars("<a> c")), 0);
+        // Unterminated
+        assert_eq!(declaration_length(&chars("<!--")), 4);
+        assert_eq!(declaration_length(&chars("<!-- a -")), 8);
+        assert_eq!(declaration_length(&chars("<![CDATA[a]]")), 12);
+        assert_eq!(declaration_length(&chars("<!DOCTYPE")), 9);
+        assert_eq!(declaration_length(&chars("</")), 0);
+    }
+
+    #[test]
+    fn tags() {
+        let t = tag(&chars("<IMG Alt=\"a b\" title='c'\r\n data-x=d checked/> e")).unwrap();
+        assert_eq!((&*t.name, t.end, t.length), ("img", false, 45));
+        let names: Vec<&str> = t.attributes.iter().map(|a| &*a.0).collect();
+        assert_eq!(names, vec!["alt", "title", "data-x", "checked"]);
+        assert_eq!((t.attributes[0].1, t.attributes[0].2), (10, 13));
+        assert_eq!((t.attributes[1].1, t.attributes[1].2), (22, 23));
+        assert_eq!((t.attributes[2].1, t.attributes[2].2), (34, 35));
+        assert_eq!(t.attributes[3].1, t.attributes[3].2);
+
+        let t = tag(&chars("</P >")).unwrap();
+        assert_eq!((&*t.name, t.end, t.length), ("p", true, 5));
+        let t = tag(&chars("<a =b>")).unwrap();
+        assert_eq!(&*t.attributes[0].0, "=b");
+        assert!(tag(&chars("< a>")).is_none());
+        assert!(tag(&chars("<3")).is_none());
+        assert!(tag(&chars("a")).is_none());
+
+        // Unterminated
+        let t = tag(&chars("<a title=\"b>c")).unwrap();
+        assert_eq!((t.attributes[0].1, t.attributes[0].2, t.length), (10, 13, 13));
+        let t = tag(&chars("<a title=b")).unwrap();
+        assert_eq!((t.attributes[0].1, t.attributes[0].2, t.length), (9, 10, 10));
+        assert_eq!(tag(&chars("<br")).unwrap().length, 3);
+    }
+
+    #[test]
+    fn raw_text() {
+        assert!(is_raw_text("script"));
+        assert!(!is_raw_text("p"));
+        assert_eq!(raw_text_length(&chars("a < b </SCRIPT> c"), "script"), 6);
+        assert_eq!(raw_text_length(&chars("</scripts></script\n>"), "script"), 10);
+        assert_eq!(raw_text_length(&chars("a</script"), "script"), 1);
+        // Unterminated
+        assert_eq!(raw_text_length(&chars("a </scrip"), "script"), 9);
+    }
+
+    #[test]
+    fn entities() {
+        assert_eq!(entity(&chars("&eacute; a")), Some((8, 'é')));
+        assert_eq!(entity(&chars("&#233;")), Some((6, 'é')));
+        assert_eq!(entity(&chars("&#xE9;")), Some((6, 'é')));
+        assert_eq!(entity(&chars("&#X1D11E;")), Some((9, '𝄞')));
+        assert_eq!(entity(&chars("&#150;")), Some((6, '–')));
+        assert_eq!(entity(&chars("&#0;")), Some((4, '\u{FFFD}')));
+        assert_eq!(entity(&chars("&#xD800;")), Some((8, '\u{FFFD}')));
+        assert_eq!(entity(&chars("&#99999999999;")), Some((14, '\u{FFFD}')));
+        assert_eq!(entity(&chars("&#;")), None);
+        assert_eq!(entity(&chars("&#x;")), None);
+        assert_eq!(entity(&chars("&#12a;")), None);
+        assert_eq!(entity(&chars("&unknown;")), None);
+        assert_eq!(entity(&chars("a&amp;")), None);
+        // Unterminated
+        assert_eq!(entity(&chars("&amp")), None);
+    }
+
+    #[test]
+    fn documents() {
+        assert_eq!(tracked_words("<!DOCTYPE html>\r\n<html><head><title>Cats</title>\
+                                  <style>p { color: red }</style></head>\r\n\
+                                  <body><p>Dogs <img alt=\"birds\" src=\"fish.png\"> \
+                                  <!-- cows --> <script>if (a < b) ants()</script></p>\
+                                  </body></html>"),
+                   vec!["Dogs", "birds"]);
+        // Nested elements and entities
+        assert_eq!(tracked("<div><p><em>caf&eacute; <b>na&#xEF;ve</b></em></p></div>"),
+                   vec![("caf&eacute;".to_owned(), "café".to_owned()),
+                        ("na&#xEF;ve".to_owned(), "naïv".to_owned())]);
+        // Unterminated markup
+        assert_eq!(tracked_words("cats <!-- dogs"), vec!["cats"]);
+        assert_eq!(tracked_words("cats <a href='dogs"), vec!["cats"]);
+        assert_eq!(tracked_words("cats <script>dogs"), vec!["cats"]);
+        assert_eq!(tracked_words("cats < dogs &amp"), vec!["cats", "dogs", "amp"]);
+    }
+}
@@ -0,0 +1,185 @@
+(function () {
+    "use strict";
+    var data = JSON.parse(document.getElementById("caribon-data").textContent);
+    var threshold = data.threshold;
+    var words = Array.prototype.slice.call(document.querySelectorAll("#caribon-text span[data-stem]"));
+    // Detectors and bands ("detector/index") that are unchecked
+    var hiddenDetectors = {};
+    var hiddenBands = {};
+    // Position of the next occurrence to jump to, for each group
+    var cursors = {};
+    var current = null;
+
+    // Returns the index of the line of the theme used for a severity, or -1
+    function band(detector, severity) {
+        var rules = data.theme[detector] || [];
+        var best = -1;
+        for (var i = 0; i < rules.length; i++) {
+            if (rules[i].min <= severity) {
+                best = i;
+            }
+        }
+        return best;
+    }
+
+    // Returns the highlight of a word with the current threshold, or null: highlights of
+    // other detectors are kept, local ones are computed from the embedded values
+    function highlightOf(word) {
+        var detector = word.getAttribute("data-detector");
+        if (detector && detector !== "local") {
+            return { detector: detector,
+                     group: detector + ":" + word.getAttribute("data-group"),
+                     severity: parseFloat(word.getAttribute("data-severity")) };
+        }
+        var value = parseFloat(word.getAttribute("data-local") || "0");
+        if (value > 0 && value >= threshold) {
+            return { detector: "local",
+                     group: "local:" + word.getAttribute("data-local-group"),
+                     severity: value / threshold };
+        }
+        return null;
+    }
+
+    function jump(word) {
+        if (current) {
+            current.classList.remove("caribon-current");
+        }
+        current = word;
+        word.classList.add("caribon-current");
+        word.scrollIntoView({ block: "center" });
+    }
+
+    function renderGroups(groups) {
+        var list = document.getElementById("caribon-groups");
+        while (list.firstChild) {
+            list.removeChild(list.firstChild);
+        }
+        var keys = Object.keys(groups);
+        keys.sort(function (a, b) {
+            return groups[b].severity - groups[a].severity ||
+                   groups[b].words.length - groups[a].words.length;
+        });
+        keys.forEach(function (key) {
+            var group = groups[key];
+            var item = document.createElement("li");
+            var label = document.createElement("span");
+            label.setAttribute("style", group.css);
+            label.textContent = group.label;
+            var detail = document.createElement("span");
+            detail.className = "caribon-detector";
+            detail.textContent = " ×" + group.words.length + " (" + group.detector + ")";
+            item.appendChild(label);
+            item.appendChild(detail);
+            item.addEventListener("click", function () {
+                var i = (cursors[key] || 0) % group.words.length;
+                cursors[key] = i + 1;
+                jump(group.words[i]);
+            });
+            list.appendChild(item);
+        });
+        document.getElementById("caribon-count").textContent = keys.length;
+    }
+
+    // Recolours the text and rebuilds the list of repetitions
+    function update() {
+        var groups = {};
+        words.forEach(function (word) {
+            var highlight = highlightOf(word);
+            var css = "";
+            if (highlight && !hiddenDetectors[highlight.detector]) {
+                var i = band(highlight.detector, highlight.severity);
+                var rule = i >= 0 ? data.theme[highlight.detector][i] : null;
+                if (rule && rule.css !== null && !hiddenBands[highlight.detector + "/" + i]) {
+                    css = rule.css;
+                    var group = groups[highlight.group];
+                    if (!group) {
+                        group = groups[highlight.group] = { label: word.textContent.toLowerCase(),
+                                                            detector: highlight.detector,
+                                                            css: css, severity: 0, words: [] };
+                    }
+                    if (highlight.severity > group.severity) {
+                        group.severity = highlight.severity;
+                        group.css = css;
+                    }
+                    group.words.push(word);
+                }
+            }
+            if (css) {
+                word.setAttribute("style", css);
+            } else {
+                word.removeAttribute("style");
+            }
+        });
+        renderGroups(groups);
+    }
+
+    function checkbox(text, css, onChange) {
+        var label = document.createElement("label");
+        var input = document.createElement("input");
+        input.type = "checkbox";
+        input.checked = true;
+        input.addEventListener("change", function () { onChange(input.checked); update(); });
+        var span = document.createElement("span");
+        if (css) {
+            span.setAttribute("style", css);
+        }
+        span.textContent = text;
+        label.appendChild(input);
+        label.appendChild(span);
+        return label;
+    }
+
+    // Toggles for each detector and each of its bands
+    var toggles = document.getElementById("caribon-toggles");
+    Object.keys(data.theme).sort().forEach(function (detector) {
+        toggles.appendChild(checkbox(detector, null, function (on) { hiddenDetectors[detector] = !on; }));
+        var list = document.createElement("ul");
+        data.theme[detector].forEach(function (rule, i) {
+            if (rule.css === null) {
+                return;
+            }
+            var item = document.createElement("li");
+            item.appendChild(checkbox(rule.label, rule.css, function (on) {
+                hiddenBands[detector + "/" + i] = !on;
+            }));
+            list.appendChild(item);
+        });
+        toggles.appendChild(list);
+    });
+
+    var slider = document.getElementById("caribon-threshold");
+    slider.addEventListener("input", function () {
+        threshold = parseFloat(slider.value);
+        document.getElementById("caribon-threshold-value").textContent = slider.value;
+        update();
+    });
+
+    // Clicking on a word jumps to the next occurrence of its stem
+    document.getElementById("caribon-text").addEventListener("click", function (event) {
+        var target = event.target;
+        if (!target.hasAttribute || !target.hasAttribute("data-stem")) {
+            return;
+        }
+        var stem = target.getAttribute("data-stem");
+        var same = words.filter(function (word) { return word.getAttribute("data-stem") === stem; });
+        jump(same[(same.indexOf(target) + 1) % same.length]);
+    });
+
+    // Highlights the words with the same stem as the one under the mouse
+    function hover(event, on) {
+        var target = event.target;
+        if (!target.hasAttribute || !target.hasAttribute("data-stem")) {
+            return;
+        }
+        var stem = target.getAttribute("data-stem");
+        words.forEach(function (word) {
+            if (word.getAttribute("data-stem") === stem) {
+                word.classList.toggle("caribon-hover", on);
+            }
+        });
+    }
+    document.addEventListener("mouseover", function (event) { hover(event, true); });
+    document.addEventListener("mouseout", function (event) { hover(event, false); });
+
+    update();
+})();
@@ -1,17 +1,23 @@
+<style>
+    .caribon-hover { background-color: pink !important; }
+</style>
 <script>
-    function on(name) {
-        var elements = document.getElementsByClassName(name);
-        for (var i = 0; i < elements.length; i++) {
-            var elem = elements[i];
-            elem.style.backgroundColor = "pink";
+    (function () {
+        // Highlights (or not) the words with the same stem as the one under the mouse
+        function toggle(event, on) {
+            var target = event.target;
+            if (!target.hasAttribute || !target.hasAttribute("data-stem")) {
+                return;
+            }
+            var stem = target.getAttribute("data-stem");
+            var elements = document.querySelectorAll("span[data-stem]");
+            for (var i = 0; i < elements.length; i++) {
+                if (elements[i].getAttribute("data-stem") === stem) {
+                    elements[i].classList.toggle("caribon-hover", on);
+                }
+            }
         }
-    }
-    function off(name) {
-        var elements = document.getElementsByClassName(name);
-        for (var i = 0; i < elements.length; i++) {
-            var elem = elements[i];
-            elem.style.backgroundColor = "white";
-        }
-    }
+        document.addEventListener("mouseover", function (event) { toggle(event, true); });
+        document.addEventListener("mouseout", function (event) { toggle(event, false); });
+    })();
 </script>
-
@@ -0,0 +1,113 @@
+// Copyright (C) 2015, 2016 Élisabeth HENRY.
+//
+// This file is part of Caribon.
+//
+// Caribon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Caribon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Rules deciding which words are ignored, used by `Parser::tokenize_word`.
+
+use super::stemmer::Stemmer;
+
+use error::{Error, Result};
+use regex::Regex;
+use std::collections::HashSet;
+
+/// Ignored words, matched by their lowercase form, by their stem, or by a pattern.
+///
+/// Entries are given as strings: `~word` ignores all the words with the same stem as
+/// `word`, `/pattern/` ignores all the words matching a regular expression, and any other
+/// entry is ignored as is.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreList {
+    /// Words ignored as is, in the order they were added
+    words: Vec<String>,
+    /// Same words, for fast lookup
+    word_set: HashSet<String>,
+    /// Words whose stem is ignored, as they were given
+    stem_words: Vec<String>,
+    /// Stems of `stem_words`
+    stems: HashSet<String>,
+    /// Patterns of ignored words, as they were given and compiled to match whole words
+    patterns: Vec<(String, Regex)>,
+}
+
+impl IgnoreList {
+    /// Removes all the entries
+    pub fn clear(&mut self) {
+        *self = IgnoreList::default();
+    }
+
+    /// Adds an entry, using `stemmer` to compute stems (or the lowercase word if it is `None`)
+    ///
+    /// Returns an error if the entry is an invalid pattern.
+    pub fn add(&mut self, entry: &str, stemmer: &mut Option<Stemmer>) -> Result<()> {
+        if entry.len() >= 2 && entry.starts_with('/') && entry.ends_with('/') {
+            let pattern = &entry[1..entry.len() - 1];
+            match Regex::new(&format!("^(?:{})$", pattern)) {
+                Ok(regex) => self.patterns.push((pattern.to_owned(), regex)),
+                Err(e) => return Err(Error::new(&format!("Invalid pattern /{}/: {}", pattern, e))),
+            }
+        } else {
+            self.add_word(entry, stemmer);
+        }
+        Ok(())
+    }
+
+    /// Adds a word, or a stem if it is preceded by `~`, using `stemmer` as in `add`
+    ///
+    /// Unlike `add`, the entry is never considered as a pattern, so this can't fail.
+    pub fn add_word(&mut self, entry: &str, stemmer: &mut Option<Stemmer>) {
+        if let Some(word) = entry.strip_prefix('~') {
+            let word = word.to_lowercase();
+            if !word.is_empty() && !self.stem_words.contains(&word) {
+                let stem = match *stemmer {
+                    Some(ref mut stemmer) => stemmer.stem(&word),
+                    None => word.clone(),
+                };
+                self.stems.insert(stem);
+                self.stem_words.push(word);
+            }
+        } else {
+            let word = entry.to_lowercase();
+            if !word.is_empty() && self.word_set.insert(word.clone()) {
+                self.words.push(word);
+            }
+        }
+    }
+
+    /// Returns true if a word (in lowercase) is ignored as is or because of a pattern
+    pub fn matches_word(&self, word: &str) -> bool {
+        self.word_set.contains(word) || self.patterns.iter().any(|p| p.1.is_match(word))
+    }
+
+    /// Returns true if words with this stem are ignored
+    pub fn matches_stem(&self, stem: &str) -> bool {
+        self.stems.contains(stem)
+    }
+
+    /// Returns the words ignored as is
+    pub fn words(&self) -> &[String] {
+        &self.words
+    }
+
+    /// Returns the words whose stem is ignored
+    pub fn stem_words(&self) -> &[String] {
+        &self.stem_words
+    }
+
+    /// Returns the patterns of ignored words
+    pub fn patterns(&self) -> Vec<&str> {
+        self.patterns.iter().map(|p| &*p.0).collect()
+    }
+}
@@ -0,0 +1,325 @@
+// Copyright (C) 2015, 2016 Élisabeth HENRY.
+//
+// This file is part of Caribon.
+//
+// Caribon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Caribon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Helpers to recognize LaTeX syntax, used by `Parser::tokenize_latex`.
+//!
+//! Functions returning a length return 0 if the syntax was not recognized.
+
+use markdown::{starts_with, line_length};
+
+/// Commands whose first arguments are not prose, with the number of such arguments
+const SKIPPED_ARGUMENTS: &[(&str, usize)] = &[("cite", 1), ("citep", 1), ("citet", 1),
+                                              ("citeauthor", 1), ("citeyear", 1), ("nocite", 1),
+                                              ("parencite", 1), ("textcite", 1),
+                                              ("autocite", 1), ("footcite", 1), ("ref", 1),
+                                              ("eqref", 1), ("pageref", 1), ("autoref", 1),
+                                              ("cref", 1), ("Cref", 1), ("nameref", 1),
+                                              ("vref", 1), ("label", 1), ("url", 1),
+                                              ("href", 1), ("includegraphics", 1),
+                                              ("input", 1), ("include", 1), ("includeonly", 1),
+                                              ("usepackage", 1), ("RequirePackage", 1),
+                                              ("documentclass", 1), ("bibliography", 1),
+                                              ("bibliographystyle", 1), ("addbibresource", 1),
+                                              ("bibitem", 1), ("newcommand", 2),
+                                              ("renewcommand", 2), ("providecommand", 2),
+                                              ("newenvironment", 3), ("renewenvironment", 3),
+                                              ("newtheorem", 2), ("color", 1),
+                                              ("textcolor", 1), ("colorbox", 1),
+                                              ("hspace", 1), ("vspace", 1), ("setlength", 2),
+                                              ("setcounter", 2), ("addtocounter", 2),
+                                              ("fontsize", 2), ("ensuremath", 1),
+                                              ("hypersetup", 1), ("graphicspath", 1),
+                                              ("pagestyle", 1), ("thispagestyle", 1)];
+
+/// Environments whose content is not prose (mostly maths and code)
+const SKIPPED_ENVIRONMENTS: &[&str] = &["equation", "align", "gather", "multline", "flalign",
+                                        "alignat", "eqnarray", "displaymath", "math",
+                                        "verbatim", "Verbatim", "lstlisting", "minted",
+                                        "tikzpicture", "comment"];
+
+/// Environments whose first arguments are not prose, with the number of such arguments
+const ENVIRONMENT_ARGUMENTS: &[(&str, usize)] = &[("tabular", 1), ("tabular*", 2),
+                                                  ("tabularx", 2), ("array", 1),
+                                                  ("minipage", 1), ("thebibliography", 1),
+                                                  ("multicols", 1), ("wrapfigure", 2)];
+
+/// Returns true if the document has a preamble, i.e. contains `\begin{document}`
+pub fn has_preamble(chars: &[char]) -> bool {
+    (0..chars.len()).any(|i| starts_with(&chars[i..], "\\begin{document}"))
+}
+
+/// Returns the length of a comment, up to (but not including) the end of line
+pub fn comment_length(chars: &[char]) -> usize {
+    let n = line_length(chars);
+    if chars.get(n - 1) == Some(&'\n') {
+        n - 1
+    } else {
+        n
+    }
+}
+
+/// Returns the length of a control sequence, e.g. `\emph`, `\section*` or `\%`
+pub fn control_sequence_length(chars: &[char]) -> usize {
+    let n = chars[1..].iter().take_while(|c| c.is_ascii_alphabetic()).count();
+    if n == 0 {
+        return if chars.len() > 1 { 2 } else { 1 };
+    }
+    if chars.get(n + 1) == Some(&'*') {
+        n + 2
+    } else {
+        n + 1
+    }
+}
+
+/// Returns the length of a group starting with `open`, e.g. `{foo {bar}}`
+pub fn group_length(chars: &[char], open: char, close: char) -> usize {
+    if chars.first() != Some(&open) {
+        return 0;
+    }
+    let mut depth = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            c if c == open => depth += 1,
+            c if c == close => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+    0
+}
+
+/// Returns the length of optional arguments and of the first `n` mandatory arguments
+pub fn arguments_length(chars: &[char], n: usize) -> usize {
+    let mut len = 0;
+    let mut n = n;
+    loop {
+        let spaces = chars[len..].iter().take_while(|&&c| c == ' ').count();
+        let rest = &chars[len + spaces..];
+        let group = match rest.first() {
+            Some(&'[') => group_length(rest, '[', ']'),
+            Some(&'{') if n > 0 => {
+                n -= 1;
+                group_length(rest, '{', '}')
+            }
+            _ => 0,
+        };
+        if group == 0 {
+            return len;
+        }
+        len += spaces + group;
+    }
+}
+
+/// Returns the number of arguments of a command that are not prose
+pub fn skipped_arguments(name: &str) -> usize {
+    let name = name.trim_end_matches('*');
+    SKIPPED_ARGUMENTS.iter()
+                     .find(|&&(s, _)| s == name)
+                     .map(|&(_, n)| n)
+                     .unwrap_or(0)
+}
+
+/// Returns the length of inline or display maths: `$...$`, `$$...$$`, `\(...\)` or `\[...\]`
+pub fn math_length(chars: &[char]) -> usize {
+    let (open, close) = if starts_with(chars, "$$") {
+        ("$$", "$$")
+    } else if starts_with(chars, "$") {
+        ("$", "$")
+    } else if starts_with(chars, "\\(") {
+        ("\\(", "\\)")
+    } else if starts_with(chars, "\\[") {
+        ("\\[", "\\]")
+    } else {
+        return 0;
+    };
+    let mut i = open.len();
+    while i < chars.len() {
+        if starts_with(&chars[i..], close) {
+            return i + close.len();
+        }
+        if chars[i] == '\\' {
+            i += 1;
+        }
+        i += 1;
+    }
+    0
+}
+
+/// Parses `\begin{name}` or `\end{name}`, returning its length and the name of the
+/// environment
+pub fn environment(chars: &[char]) -> (usize, String) {
+    let n = control_sequence_length(chars);
+    let spaces = chars[n..].iter().take_while(|&&c| c == ' ').count();
+    let group = group_length(&chars[n + spaces..], '{', '}');
+    if group < 2 {
+        return (n, String::new());
+    }
+    let len = n + spaces + group;
+    (len, chars[n + spaces + 1..len - 1].iter().collect())
+}
+
+/// Returns true if the content of the environment is not prose
+pub fn is_skipped_environment(name: &str) -> bool {
+    SKIPPED_ENVIRONMENTS.contains(&name.trim_end_matches('*'))
+}
+
+/// Returns the number of arguments of an environment that are not prose
+pub fn environment_arguments(name: &str) -> usize {
+    ENVIRONMENT_ARGUMENTS.iter()
+                         .find(|&&(s, _)| s == name)
+                         .map(|&(_, n)| n)
+                         .unwrap_or(0)
+}
+
+/// Returns the length up to and including `\end{name}`, or the remaining length if
+/// the environment is not closed
+pub fn environment_end_length(chars: &[char], name: &str) -> usize {
+    let end = format!("\\end{{{}}}", name);
+    for i in 0..chars.len() {
+        if starts_with(&chars[i..], &end) {
+            return i + end.chars().count();
+        }
+    }
+    chars.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::{Parser, InputFormat};
+    use word::Word;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    /// Returns the tracked words of a LaTeX document
+    fn tracked(s: &str) -> Vec<String> {
+        let parser = Parser::new("english").unwrap().with_input_format(InputFormat::Latex);
+        let ast = parser.tokenize(s).unwrap();
+        ast.words.iter()
+           .filter_map(|w| match *w {
+               Word::Tracked(ref s, _, _, _) => Some(s.clone()),
+               _ => None,
+           })
+           .collect()
+    }
+
+    #[test]
+    fn empty_input() {
+        assert!(!has_preamble(&[]));
+        assert_eq!(group_length(&[], '{', '}'), 0);
+        assert_eq!(arguments_length(&[], 2), 0);
+        assert_eq!(math_length(&[]), 0);
+        assert_eq!(environment_end_length(&[], "verbatim"), 0);
+        assert!(tracked("").is_empty());
+    }
+
+    #[test]
+    fn control_sequences() {
+        assert_eq!(control_sequence_length(&chars("\\emph{x}")), 5);
+        assert_eq!(control_sequence_length(&chars("\\section*{x}")), 9);
+        assert_eq!(control_sequence_length(&chars("\\% x")), 2);
+        assert_eq!(control_sequence_length(&chars("\\")), 1);
+        assert_eq!(skipped_arguments("cite"), 1);
+        assert_eq!(skipped_arguments("newcommand*"), 2);
+        assert_eq!(skipped_arguments("emph"), 0);
+    }
+
+    #[test]
+    fn groups() {
+        assert_eq!(group_length(&chars("{a {b} c}d"), '{', '}'), 9);
+        assert_eq!(group_length(&chars("{a \\} b}"), '{', '}'), 8);
+        assert_eq!(group_length(&chars("x{a}"), '{', '}'), 0);
+        // Unterminated
+        assert_eq!(group_length(&chars("{a {b}"), '{', '}'), 0);
+        assert_eq!(group_length(&chars("{a \\}"), '{', '}'), 0);
+
+        assert_eq!(arguments_length(&chars("[opt] {a}{b} c"), 1), 9);
+        assert_eq!(arguments_length(&chars("[opt] {a}{b} c"), 2), 12);
+        assert_eq!(arguments_length(&chars("{a}"), 0), 0);
+        assert_eq!(arguments_length(&chars("[opt"), 1), 0);
+        assert_eq!(arguments_length(&chars("[opt]{a"), 1), 5);
+    }
+
+    #[test]
+    fn maths() {
+        assert_eq!(math_length(&chars("$x$ y")), 3);
+        assert_eq!(math_length(&chars("$$x$$ y")), 5);
+        assert_eq!(math_length(&chars("\\(x\\) y")), 5);
+        assert_eq!(math_length(&chars("\\[x\\] y")), 5);
+        assert_eq!(math_length(&chars("$x \\$ y$ z")), 8);
+        assert_eq!(math_length(&chars("x")), 0);
+        // Unterminated
+        assert_eq!(math_length(&chars("$x y")), 0);
+        assert_eq!(math_length(&chars("$$x$")), 0);
+        assert_eq!(math_length(&chars("\\[x")), 0);
+    }
+
+    #[test]
+    fn environments() {
+        assert_eq!(environment(&chars("\\begin{itemize} x")), (15, "itemize".to_owned()));
+        assert_eq!(environment(&chars("\\begin {tabular}{ll}")), (16, "tabular".to_owned()));
+        assert_eq!(environment(&chars("\\end{align*}")), (12, "align*".to_owned()));
+        assert_eq!(environment(&chars("\\begin")), (6, String::new()));
+        assert_eq!(environment(&chars("\\begin{}")), (8, String::new()));
+        assert_eq!(environment(&chars("\\begin{itemize")), (6, String::new()));
+        assert!(is_skipped_environment("align*"));
+        assert!(!is_skipped_environment("itemize"));
+        assert_eq!(environment_arguments("tabular*"), 2);
+
+        assert_eq!(environment_end_length(&chars("x \\end{verbatim} y"), "verbatim"), 16);
+        // Unterminated
+        assert_eq!(environment_end_length(&chars("x \\end{verb"), "verbatim"), 11);
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(comment_length(&chars("% foo\nbar")), 5);
+        assert_eq!(comment_length(&chars("% foo")), 5);
+        assert_eq!(comment_length(&chars("%")), 1);
+        assert_eq!(tracked("% cats\r\ndogs % cats\r\n"), vec!["dogs"]);
+    }
+
+    #[test]
+    fn documents() {
+        assert_eq!(tracked("\\documentclass{article}\r\n\\title{Cats}\r\n\
+                            \\begin{document}\r\nDogs\r\n\\end{document}\r\nBirds"),
+                   vec!["Dogs"]);
+        // Nested environments
+        assert_eq!(tracked("\\begin{figure}\\begin{itemize}\\item cats \
+                            \\begin{equation}\\begin{array}{c} x \\end{array}\\end{equation} \
+                            \\begin{tabular}{ll} dogs \\end{tabular}\\end{itemize}\\end{figure}"),
+                   vec!["cats", "dogs"]);
+        assert_eq!(tracked("\\emph{cats \\textbf{dogs}} \\cite{birds} \\ref {fish}"),
+                   vec!["cats", "dogs"]);
+        // Unterminated markup
+        assert_eq!(tracked("cats $dogs"), vec!["cats", "dogs"]);
+        assert_eq!(tracked("cats \\emph{dogs"), vec!["cats", "dogs"]);
+        assert_eq!(tracked("cats \\cite{dogs"), vec!["cats", "dogs"]);
+        assert_eq!(tracked("cats \\begin{verbatim} dogs"), vec!["cats"]);
+        assert_eq!(tracked("\\begin{document} cats"), vec!["cats"]);
+        assert_eq!(tracked("cats \\"), vec!["cats"]);
+    }
+}
@@ -25,11 +25,11 @@
 //!
 //! ```
 //! use caribon::Parser;
-//! let mut parser = Parser::new("english").unwrap(); //creates a new parser
+//! let parser = Parser::new("english").unwrap(); //creates a new parser
 //! let mut ast = parser.tokenize("Some text where you want to detect repetitions").unwrap();
 //! parser.detect_local(&mut ast, 1.5);
 //! parser.detect_global(&mut ast, 0.01); // wouldn't actually make much sense on a string so small
-//! let html = parser.ast_to_html(&mut ast, true);
+//! let html = parser.ast_to_html(&ast, true);
 //! println!("{}", html);
 //! ```
 //!
@@ -49,7 +49,7 @@
 //!
 //! ```
 //! use caribon::Parser;
-//! let mut parser = Parser::new("english").unwrap()
+//! let parser = Parser::new("english").unwrap()
 //! .with_html(true)
 //! .with_ignore_proper(true)
 //! .with_max_distance(20)
@@ -77,17 +77,42 @@
 //! parser.detect_global(&mut ast, 0.01);
 //! ```
 //!
+//! `detect_local` considers that the `Ast` is a whole document. If you receive a document by chunks
+//! (e.g. line by line), you can instead create a `Session` that will remember the previous chunks:
+//!
+//! ```ignore
+//! let mut session = parser.new_session();
+//! session.detect_local(&mut first_chunk, 1.9);
+//! session.detect_local(&mut second_chunk, 1.9);
+//! ```
+//!
+//! Since all detection state is in the `Session`, a `Parser` can be reused on as many documents
+//! as you want, and even be shared between threads.
+//!
 //! Each of these algorithms takes a theshold as argument, which is the minimal "amount of repetition" to
 //! underline a word (in `detect_local`, this number is simply the number of occurrence of a word in a window of
 //! `parser.max_distance` words, whereas, for `detect_global` it is the ratio of appeareance of a particular word).
 //!
+//! These methods are shortcuts: each algorithm is actually implemented by a type implementing the
+//! `Detector` trait (`LocalDetector`, `GlobalDetector` and `PhraseDetector`). If you want to use
+//! several of them, or your own, you can chain them in a `Pipeline`:
+//!
+//! ```ignore
+//! Pipeline::new()
+//!     .with_detector(LocalDetector::new(&parser, 1.9))
+//!     .with_detector(GlobalDetector::new(&parser, 0.01))
+//!     .run(&mut ast);
+//! ```
+//!
+//! When two detectors flag the same word, the first one takes precedence.
+//!
 //! Once you have detected those repetitions, the final step is to print them.
 //! `ast_to_html` does this. Besides a reference to an `Ast`, it takes one argument: a
 //! boolean that tests whether the HTML code must be a standalone file or not (you will probably
 //! want to set it to true).
 //!
 //! ```ignore
-//! let html = parser.ast_to_html(&mut ast, true);
+//! let html = parser.ast_to_html(&ast, true);
 //! ```
 //!
 //! There are two other "outputting" methods: `ast_to_terminal` and `ast_to_markdown`:
@@ -100,6 +125,15 @@
 //! Both actually outputs texts; `ast_to_terminal` uses terminal color codes to highlight repetitions when the
 //! string is displayed on a terminal, while `ast_to_markdown` uses markdown strong emphasis to highlight repetitions.
 //!
+//! `ast_to_report` also outputs HTML, but as an interactive report, with a list of the repetitions,
+//! some statistics and a slider to change the threshold of local repetitions.
+//!
+//! If you want to process the repetitions in another program, `ast_to_json` returns a JSON document
+//! describing each repetition (position, line and column, detector, value, other occurrences...).
+//!
+//! The library can also be called from C (or any language with a C FFI), see the `capi` module
+//! and the `include/caribon.h` header.
+//!
 //!
 
 // Uncomment this if you use nightly and want to run benchmarks
@@ -108,16 +142,43 @@
 
 extern crate stemmer;
 extern crate strsim;
+extern crate regex;
 
 mod error;
 mod word;
 mod parser;
+mod session;
+mod fuzzy;
+mod metric;
+mod theme;
+mod detector;
 mod display;
+mod terminal;
+mod markdown;
+mod latex;
+mod html;
+mod stopwords;
+mod ignored;
+mod proper;
+mod segment;
+pub mod capi;
 
 
 pub use error::Error;
 pub use error::Result;
 pub use word::Word;
 pub use word::Ast;
+pub use word::Highlight;
+pub use word::Span;
+pub use word::WordRules;
 pub use parser::Parser;
+pub use parser::InputFormat;
+pub use parser::DistanceUnit;
 pub use parser::Repetition;
+pub use parser::PROJECT_IGNORED_FILE;
+pub use session::Session;
+pub use fuzzy::FuzzyIndex;
+pub use theme::{Theme, Style};
+pub use terminal::{TerminalOptions, Palette};
+pub use metric::{Metric, Levenshtein, DamerauLevenshtein, JaroWinkler, AccentInsensitive};
+pub use detector::{Detector, Finding, Pipeline, LocalDetector, GlobalDetector, PhraseDetector, highlight};
@@ -0,0 +1,412 @@
+// Copyright (C) 2015, 2016 Élisabeth HENRY.
+//
+// This file is part of Caribon.
+//
+// Caribon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Caribon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Helpers to recognize Markdown syntax, used by `Parser::tokenize_markdown`.
+//!
+//! Functions taking a line expect it to include its final newline, if any. Functions
+//! returning a length return 0 if the syntax was not recognized.
+
+/// Returns true if `chars` starts with `s`
+pub fn starts_with(chars: &[char], s: &str) -> bool {
+    s.chars().enumerate().all(|(i, c)| chars.get(i) == Some(&c))
+}
+
+/// Returns the length of the first line, including the newline
+pub fn line_length(chars: &[char]) -> usize {
+    match chars.iter().position(|&c| c == '\n') {
+        Some(i) => i + 1,
+        None => chars.len(),
+    }
+}
+
+pub fn is_blank(line: &[char]) -> bool {
+    line.iter().all(|c| c.is_whitespace())
+}
+
+/// Returns the indentation of a line, tabs counting as four spaces
+pub fn indentation(line: &[char]) -> usize {
+    let mut n = 0;
+    for &c in line {
+        match c {
+            ' ' => n += 1,
+            '\t' => n += 4,
+            _ => break,
+        }
+    }
+    n
+}
+
+/// Returns the length of a YAML front matter at the beginning of a document
+pub fn front_matter_length(chars: &[char]) -> usize {
+    let mut len = line_length(chars);
+    if !starts_with(chars, "---\n") && !starts_with(chars, "---\r\n") {
+        return 0;
+    }
+    while len < chars.len() {
+        let n = line_length(&chars[len..]);
+        let line: String = chars[len..len + n].iter().collect();
+        len += n;
+        if line.trim_end() == "---" || line.trim_end() == "..." {
+            return len;
+        }
+    }
+    0
+}
+
+/// If the line opens a fenced code block, returns the fence character and length
+pub fn code_fence(line: &[char]) -> Option<(char, usize)> {
+    let indent = line.iter().take_while(|&&c| c == ' ').count();
+    if indent > 3 {
+        return None;
+    }
+    let c = match line.get(indent) {
+        Some(&c) if c == '`' || c == '~' => c,
+        _ => return None,
+    };
+    let n = line[indent..].iter().take_while(|&&x| x == c).count();
+    if n < 3 || (c == '`' && line[indent + n..].contains(&'`')) {
+        return None;
+    }
+    Some((c, n))
+}
+
+/// Returns true if the line closes a fenced code block opened by `fence`
+pub fn closes_fence(line: &[char], fence: (char, usize)) -> bool {
+    let (c, n) = fence;
+    let indent = line.iter().take_while(|&&c| c == ' ').count();
+    let count = line[indent..].iter().take_while(|&&x| x == c).count();
+    indent <= 3 && count >= n && is_blank(&line[indent + count..])
+}
+
+/// Returns true if the line is a link reference definition, e.g. `[foo]: http://foo.org`
+pub fn is_link_definition(line: &[char]) -> bool {
+    let indent = line.iter().take_while(|&&c| c == ' ').count();
+    if indent > 3 || line.get(indent) != Some(&'[') {
+        return false;
+    }
+    match line[indent..].iter().position(|&c| c == ']') {
+        Some(i) => line.get(indent + i + 1) == Some(&':'),
+        None => false,
+    }
+}
+
+/// Block syntax found at the beginning of a line
+pub struct BlockPrefix {
+    /// Length of the syntax, including following whitespace
+    pub length: usize,
+    /// Whether there is a list item marker
+    pub list: bool,
+    /// Whether there is a heading marker
+    pub heading: bool,
+}
+
+/// Recognizes indentation, block quotes, list item markers, task boxes and heading markers
+pub fn block_prefix(line: &[char]) -> BlockPrefix {
+    fn spaces(line: &[char], i: usize) -> usize {
+        line[i..].iter().take_while(|&&c| c == ' ' || c == '\t').count()
+    }
+    fn ends_marker(line: &[char], i: usize) -> bool {
+        matches!(line.get(i), None | Some(&' ') | Some(&'\t') | Some(&'\n'))
+    }
+
+    let mut prefix = BlockPrefix {
+        length: 0,
+        list: false,
+        heading: false,
+    };
+    let mut i = spaces(line, 0);
+    while line.get(i) == Some(&'>') {
+        i += 1;
+        i += spaces(line, i);
+    }
+    match line.get(i) {
+        Some(&'-') | Some(&'*') | Some(&'+') if ends_marker(line, i + 1) => {
+            prefix.list = true;
+            i += 1;
+        }
+        Some(c) if c.is_ascii_digit() => {
+            let n = line[i..].iter().take_while(|c| c.is_ascii_digit()).count();
+            match line.get(i + n) {
+                Some(&'.') | Some(&')') if n <= 9 && ends_marker(line, i + n + 1) => {
+                    prefix.list = true;
+                    i += n + 1;
+                }
+                _ => (),
+            }
+        }
+        Some(&'#') => {
+            let n = line[i..].iter().take_while(|&&c| c == '#').count();
+            if n <= 6 && ends_marker(line, i + n) {
+                prefix.heading = true;
+                i += n;
+            }
+        }
+        _ => (),
+    }
+    i += spaces(line, i);
+    if prefix.list {
+        // Task list item, e.g. "- [x] done"
+        let task = ["[ ]", "[x]", "[X]"].iter().any(|s| starts_with(&line[i..], s));
+        if task && ends_marker(line, i + 3) {
+            i += 3;
+            i += spaces(line, i);
+        }
+    }
+    prefix.length = i;
+    prefix
+}
+
+/// Returns the length of an inline code span starting with a backtick, or of the
+/// backticks if the span is not closed
+pub fn inline_code_length(chars: &[char]) -> usize {
+    let n = chars.iter().take_while(|&&c| c == '`').count();
+    let mut i = n;
+    while i < chars.len() {
+        if chars[i] == '`' {
+            let m = chars[i..].iter().take_while(|&&c| c == '`').count();
+            if m == n {
+                return i + m;
+            }
+            i += m;
+        } else {
+            i += 1;
+        }
+    }
+    n
+}
+
+/// Returns the length of the end of a link, starting with the closing bracket of its
+/// text, e.g. `](http://foo.org "Title")` or `][foo]`
+pub fn link_end_length(chars: &[char]) -> usize {
+    let line = &chars[..line_length(chars)];
+    match line.get(1) {
+        Some(&'(') => {
+            let mut depth = 0;
+            for (i, &c) in line.iter().enumerate().skip(1) {
+                match c {
+                    '(' => depth += 1,
+                    ')' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return i + 1;
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            1
+        }
+        Some(&'[') => {
+            match line[2..].iter().position(|&c| c == ']') {
+                Some(i) => i + 3,
+                None => 1,
+            }
+        }
+        _ => 1,
+    }
+}
+
+/// Returns the length of an HTML comment, an HTML tag or an autolink
+pub fn html_length(chars: &[char]) -> usize {
+    if starts_with(chars, "<!--") {
+        let mut i = 4;
+        while i < chars.len() {
+            if starts_with(&chars[i..], "-->") {
+                return i + 3;
+            }
+            i += 1;
+        }
+        return 0;
+    }
+    match chars.get(1) {
+        Some(&c) if c.is_alphabetic() || c == '/' || c == '!' || c == '?' => (),
+        _ => return 0,
+    }
+    let line = &chars[..line_length(chars)];
+    match line.iter().position(|&c| c == '>') {
+        Some(i) => i + 1,
+        None => 0,
+    }
+}
+
+/// Returns the length of an HTML entity, e.g. `&amp;` or `&#233;`
+pub fn entity_length(chars: &[char]) -> usize {
+    let n = chars[1..].iter()
+                      .take(32)
+                      .take_while(|&&c| c.is_ascii_alphanumeric() || c == '#')
+                      .count();
+    if n > 0 && chars.get(n + 1) == Some(&';') {
+        n + 2
+    } else {
+        0
+    }
+}
+
+/// Returns the length of a bare URL, e.g. `https://foo.org/bar`
+pub fn url_length(chars: &[char]) -> usize {
+    if !["http://", "https://", "ftp://", "mailto:", "www."].iter().any(|s| starts_with(chars, s)) {
+        return 0;
+    }
+    let mut n = chars.iter().take_while(|&&c| !c.is_whitespace() && c != '<').count();
+    // Trailing punctuation is probably not part of the URL
+    while n > 0 && ".,:;!?\"'*_~)".contains(chars[n - 1]) {
+        n -= 1;
+    }
+    n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::{Parser, InputFormat};
+    use word::Word;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    /// Returns the tracked words of a Markdown document, checking that the text is kept
+    /// intact
+    fn tracked(s: &str) -> Vec<String> {
+        let parser = Parser::new("english").unwrap().with_input_format(InputFormat::Markdown);
+        let ast = parser.tokenize(s).unwrap();
+        let text: String = ast.words.iter().map(|w| w.as_str()).collect();
+        assert_eq!(text, s);
+        ast.words.iter()
+           .filter_map(|w| match *w {
+               Word::Tracked(ref s, _, _, _) => Some(s.clone()),
+               _ => None,
+           })
+           .collect()
+    }
+
+    #[test]
+    fn empty_input() {
+        assert_eq!(line_length(&[]), 0);
+        assert!(is_blank(&[]));
+        assert_eq!(front_matter_length(&[]), 0);
+        assert_eq!(code_fence(&[]), None);
+        assert!(!is_link_definition(&[]));
+        assert_eq!(block_prefix(&[]).length, 0);
+        assert_eq!(inline_code_length(&[]), 0);
+        assert_eq!(url_length(&[]), 0);
+        assert!(tracked("").is_empty());
+    }
+
+    #[test]
+    fn lines() {
+        assert_eq!(line_length(&chars("foo\nbar")), 4);
+        assert_eq!(line_length(&chars("foo\r\nbar")), 5);
+        assert_eq!(line_length(&chars("foo")), 3);
+        assert!(is_blank(&chars(" \t\r\n")));
+        assert_eq!(indentation(&chars("\t  x")), 6);
+    }
+
+    #[test]
+    fn front_matter() {
+        assert_eq!(front_matter_length(&chars("---\ntitle: x\n---\ntext")), 17);
+        assert_eq!(front_matter_length(&chars("---\r\ntitle: x\r\n...\r\ntext")), 20);
+        assert_eq!(front_matter_length(&chars("text\n---\n")), 0);
+        // Unterminated
+        assert_eq!(front_matter_length(&chars("---\ntitle: x\n")), 0);
+        assert_eq!(tracked("---\r\ntitle: cats\r\n---\r\ndogs"), vec!["dogs"]);
+    }
+
+    #[test]
+    fn fences() {
+        assert_eq!(code_fence(&chars("```rust\n")), Some(('`', 3)));
+        assert_eq!(code_fence(&chars("   ~~~~\r\n")), Some(('~', 4)));
+        assert_eq!(code_fence(&chars("    ```\n")), None);
+        assert_eq!(code_fence(&chars("``\n")), None);
+        assert_eq!(code_fence(&chars("``` a ` b\n")), None);
+        assert!(closes_fence(&chars("````\r\n"), ('`', 3)));
+        assert!(!closes_fence(&chars("``\n"), ('`', 3)));
+        assert!(!closes_fence(&chars("~~~\n"), ('`', 3)));
+        assert!(!closes_fence(&chars("``` x\n"), ('`', 3)));
+
+        assert_eq!(tracked("cats\r\n```\r\ndogs\r\n```\r\nbirds"), vec!["cats", "birds"]);
+        // Unterminated: the code block runs until the end of the document
+        assert_eq!(tracked("cats\n~~~\ndogs\n```\nbirds"), vec!["cats"]);
+        // Indented code
+        assert_eq!(tracked("cats\n\n    dogs\n\n    birds\nfish"), vec!["cats", "fish"]);
+        assert_eq!(tracked("- cats\n\n    dogs"), vec!["cats", "dogs"]);
+    }
+
+    #[test]
+    fn block_prefixes() {
+        let prefix = block_prefix(&chars("> > - [x] done"));
+        assert_eq!((prefix.length, prefix.list, prefix.heading), (10, true, false));
+        let prefix = block_prefix(&chars("### Title"));
+        assert_eq!((prefix.length, prefix.list, prefix.heading), (4, false, true));
+        let prefix = block_prefix(&chars("12) item"));
+        assert_eq!((prefix.length, prefix.list, prefix.heading), (4, true, false));
+        let prefix = block_prefix(&chars("-\r\n"));
+        assert!(!prefix.list);
+        let prefix = block_prefix(&chars("#hashtag"));
+        assert_eq!((prefix.length, prefix.heading), (0, false));
+        let prefix = block_prefix(&chars("1234567890. x"));
+        assert!(!prefix.list);
+        assert!(is_link_definition(&chars("  [foo]: http://foo.org")));
+        assert!(!is_link_definition(&chars("[foo] bar")));
+        assert!(!is_link_definition(&chars("[foo")));
+    }
+
+    #[test]
+    fn inline() {
+        assert_eq!(inline_code_length(&chars("`a` b")), 3);
+        assert_eq!(inline_code_length(&chars("``a ` b`` c")), 9);
+        // Unterminated
+        assert_eq!(inline_code_length(&chars("``a ` b")), 2);
+
+        assert_eq!(link_end_length(&chars("](http://foo.org/(x)) y")), 21);
+        assert_eq!(link_end_length(&chars("][foo] y")), 6);
+        assert_eq!(link_end_length(&chars("] y")), 1);
+        // Unterminated
+        assert_eq!(link_end_length(&chars("](http://foo\n)")), 1);
+        assert_eq!(link_end_length(&chars("][foo")), 1);
+
+        assert_eq!(html_length(&chars("<!-- a\nb --> c")), 12);
+        assert_eq!(html_length(&chars("<a href=\"x\"> c")), 12);
+        assert_eq!(html_length(&chars("< 3")), 0);
+        // Unterminated
+        assert_eq!(html_length(&chars("<!-- a")), 0);
+        assert_eq!(html_length(&chars("<a\n>")), 0);
+
+        assert_eq!(entity_length(&chars("&amp; x")), 5);
+        assert_eq!(entity_length(&chars("&#233;")), 6);
+        assert_eq!(entity_length(&chars("& x;")), 0);
+        assert_eq!(entity_length(&chars("&amp")), 0);
+
+        assert_eq!(url_length(&chars("https://foo.org/bar). x")), 19);
+        assert_eq!(url_length(&chars("www.foo.org<")), 11);
+        assert_eq!(url_length(&chars("foo.org")), 0);
+    }
+
+    #[test]
+    fn documents() {
+        assert_eq!(tracked("# Cats\r\n\r\n* dogs `birds` [fish](http://fish.org)\r\n\
+                            <span title=\"cows\">ants</span> &amp; \\*bees\\* https://owls.org"),
+                   vec!["Cats", "dogs", "fish", "ants", "bees"]);
+        // Nested blocks
+        assert_eq!(tracked("> - > cats\n>   1. dogs"), vec!["cats", "dogs"]);
+        // Unterminated markup
+        assert_eq!(tracked("cats `dogs"), vec!["cats", "dogs"]);
+        assert_eq!(tracked("[cats](dogs"), vec!["cats", "dogs"]);
+        assert_eq!(tracked("cats <!-- dogs"), vec!["cats", "dogs"]);
+        assert_eq!(tracked("cats \\"), vec!["cats"]);
+    }
+}
@@ -0,0 +1,199 @@
+// Copyright (C) 2015, 2016 Élisabeth HENRY.
+//
+// This file is part of Caribon.
+//
+// Caribon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Caribon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::strsim;
+
+use fuzzy::FuzzyIndex;
+
+/// A way to compare strings, used for fuzzy string matching (see `Parser::with_metric`).
+///
+/// # Example
+///
+/// ```rust
+/// use caribon::{Parser, Metric};
+///
+/// /// Words match if they start with the same two letters
+/// struct SameStart;
+///
+/// impl Metric for SameStart {
+///     fn difference(&self, pattern: &str, key: &str) -> f32 {
+///         if pattern.chars().take(2).eq(key.chars().take(2)) { 0.0 } else { 1.0 }
+///     }
+/// }
+///
+/// let parser = Parser::new("english").unwrap()
+///     .with_fuzzy(Some(0.5))
+///     .with_metric(SameStart);
+/// let mut ast = parser.tokenize("caribou carrot").unwrap();
+/// parser.detect_local(&mut ast, 1.9);
+/// assert_eq!(&parser.ast_to_markdown(&ast), "**caribou** **carrot**");
+/// ```
+pub trait Metric: Send + Sync {
+    /// Returns the difference between `pattern` and `key`, from 0.0 if they are identical
+    /// to (usually) 1.0. They match if it is lower or equal to the value given to
+    /// `Parser::with_fuzzy`.
+    fn difference(&self, pattern: &str, key: &str) -> f32;
+
+    /// Returns the key of `index` that is the closest to `pattern`, if their difference is
+    /// lower or equal to `max_difference`.
+    ///
+    /// If several keys are at the same difference, the smallest one is returned. The default
+    /// implementation compares `pattern` to all keys.
+    fn closest<'a>(&self, index: &'a FuzzyIndex, pattern: &str, max_difference: f32) -> Option<&'a str> {
+        index.keys()
+             .map(|key| (self.difference(pattern, key), key))
+             .filter(|&(d, _)| d <= max_difference)
+             .min_by(|a, b| a.0.total_cmp(&b.0).then_with(|| a.1.cmp(b.1)))
+             .map(|(_, key)| key)
+    }
+}
+
+/// Levenshtein distance divided by the number of characters of the pattern (the default metric).
+///
+/// This is the only metric for which lookups take full advantage of `FuzzyIndex`.
+pub struct Levenshtein;
+
+impl Metric for Levenshtein {
+    fn difference(&self, pattern: &str, key: &str) -> f32 {
+        strsim::levenshtein(pattern, key) as f32 / pattern.chars().count() as f32
+    }
+
+    fn closest<'a>(&self, index: &'a FuzzyIndex, pattern: &str, max_difference: f32) -> Option<&'a str> {
+        let max_distance = (max_difference * pattern.chars().count() as f32) as usize;
+        index.closest(pattern, max_distance).map(|(_, key)| key)
+    }
+}
+
+/// Damerau-Levenshtein distance (where transposing two letters counts as one edit) divided
+/// by the number of characters of the pattern.
+pub struct DamerauLevenshtein;
+
+impl Metric for DamerauLevenshtein {
+    fn difference(&self, pattern: &str, key: &str) -> f32 {
+        strsim::damerau_levenshtein(pattern, key) as f32 / pattern.chars().count() as f32
+    }
+
+    fn closest<'a>(&self, index: &'a FuzzyIndex, pattern: &str, max_difference: f32) -> Option<&'a str> {
+        // A transposition is two edits for Levenshtein distance, so candidates are at most
+        // twice as far with it
+        let max_distance = (max_difference * pattern.chars().count() as f32) as usize;
+        index.find(pattern, 2 * max_distance)
+             .into_iter()
+             .map(|(_, key)| (strsim::damerau_levenshtein(pattern, key), key))
+             .filter(|&(d, _)| d <= max_distance)
+             .min()
+             .map(|(_, key)| key)
+    }
+}
+
+/// One minus the Jaro-Winkler similarity, which favours strings with a common prefix.
+pub struct JaroWinkler;
+
+impl Metric for JaroWinkler {
+    fn difference(&self, pattern: &str, key: &str) -> f32 {
+        1.0 - strsim::jaro_winkler(pattern, key) as f32
+    }
+}
+
+/// Wraps another metric, ignoring accents (e.g. "é" and "e" are the same letter).
+///
+/// # Example
+///
+/// ```rust
+/// use caribon::{Metric, Levenshtein, AccentInsensitive};
+///
+/// assert_eq!(Levenshtein.difference("éte", "été"), 1.0 / 3.0);
+/// assert_eq!(AccentInsensitive(Levenshtein).difference("éte", "été"), 0.0);
+/// ```
+pub struct AccentInsensitive<M: Metric>(pub M);
+
+impl<M: Metric> Metric for AccentInsensitive<M> {
+    fn difference(&self, pattern: &str, key: &str) -> f32 {
+        self.0.difference(&remove_accents(pattern), &remove_accents(key))
+    }
+}
+
+/// Replaces accented latin letters by their base letter
+fn remove_accents(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => res.push('a'),
+            'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' | 'Ā' | 'Ă' | 'Ą' => res.push('A'),
+            'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => res.push('c'),
+            'Ç' | 'Ć' | 'Ĉ' | 'Ċ' | 'Č' => res.push('C'),
+            'ď' | 'đ' => res.push('d'),
+            'Ď' | 'Đ' => res.push('D'),
+            'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => res.push('e'),
+            'È' | 'É' | 'Ê' | 'Ë' | 'Ē' | 'Ĕ' | 'Ė' | 'Ę' | 'Ě' => res.push('E'),
+            'ĝ' | 'ğ' | 'ġ' | 'ģ' => res.push('g'),
+            'Ĝ' | 'Ğ' | 'Ġ' | 'Ģ' => res.push('G'),
+            'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' => res.push('i'),
+            'Ì' | 'Í' | 'Î' | 'Ï' | 'Ĩ' | 'Ī' | 'Ĭ' | 'Į' => res.push('I'),
+            'ł' | 'ĺ' | 'ľ' => res.push('l'),
+            'Ł' | 'Ĺ' | 'Ľ' => res.push('L'),
+            'ñ' | 'ń' | 'ň' => res.push('n'),
+            'Ñ' | 'Ń' | 'Ň' => res.push('N'),
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => res.push('o'),
+            'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' | 'Ō' | 'Ŏ' | 'Ő' => res.push('O'),
+            'ŕ' | 'ř' => res.push('r'),
+            'Ŕ' | 'Ř' => res.push('R'),
+            'ś' | 'ŝ' | 'ş' | 'š' => res.push('s'),
+            'Ś' | 'Ŝ' | 'Ş' | 'Š' => res.push('S'),
+            'ţ' | 'ť' => res.push('t'),
+            'Ţ' | 'Ť' => res.push('T'),
+            'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => res.push('u'),
+            'Ù' | 'Ú' | 'Û' | 'Ü' | 'Ũ' | 'Ū' | 'Ŭ' | 'Ů' | 'Ű' | 'Ų' => res.push('U'),
+            'ý' | 'ÿ' => res.push('y'),
+            'Ý' | 'Ÿ' => res.push('Y'),
+            'ź' | 'ż' | 'ž' => res.push('z'),
+            'Ź' | 'Ż' | 'Ž' => res.push('Z'),
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A metric that doesn't know how to compare words that start differently
+    struct Partial;
+
+    impl Metric for Partial {
+        fn difference(&self, pattern: &str, key: &str) -> f32 {
+            if pattern.chars().next() == key.chars().next() {
+                (pattern.len() as f32 - key.len() as f32).abs()
+            } else {
+                f32::NAN
+            }
+        }
+    }
+
+    #[test]
+    fn closest_ignores_nan() {
+        let mut index = FuzzyIndex::new();
+        for key in &["apple", "apples", "banana", "cherry"] {
+            index.insert(key);
+        }
+        assert_eq!(Partial.closest(&index, "applet", 1.0), Some("apples"));
+        assert_eq!(Partial.closest(&index, "berry", 10.0), Some("banana"));
+        assert_eq!(Partial.closest(&index, "date", 10.0), None);
+        assert_eq!(Partial.closest(&index, "apple", f32::NAN), None);
+    }
+}
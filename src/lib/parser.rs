@@ -16,18 +16,32 @@
 // along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
 
 use super::stemmer::Stemmer;
-use super::strsim::levenshtein;
 
-use word::{Word, Ast};
+use word::{Word, Ast, Highlight, Span, WordRules, is_apostrophe, is_hyphen};
 use error::{Error, Result};
+use session::Session;
+use fuzzy::FuzzyIndex;
+use metric::{Metric, Levenshtein};
+use detector::{Detector, GlobalDetector, PhraseDetector, highlight};
 use std::collections::HashMap;
-use display::{get_shell_colour, value_to_colour, SHELL_COLOUR_OFF, SCRIPTS};
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+use markdown;
+use latex;
+use html;
+use stopwords;
+use proper;
+use segment;
+use ignored::IgnoreList;
+use display::{style_to_css, escape_html, escape_json, float_to_json, index_to_json, SCRIPTS, REPORT_CSS, REPORT_SCRIPTS};
+use terminal::{self, TerminalOptions};
+use theme::{Theme, Style, label, rgb};
 
 type TokenizeResult<'a> = Result<(&'a [char], Word)>;
 
-const IGNORED_FR: &'static str = "la le les pas ne nos des de du ils elles il elle se on nous vous leur \
-                                  leurs et un une t s à d l je tu en";
-const IGNORED_EN: &'static str = "it s i of the a you we she he they them its their";
+/// Name of the file containing the ignored words of a project
+pub const PROJECT_IGNORED_FILE: &str = ".caribon-ignore";
 
 /// A detected repetition
 #[derive(Debug)]
@@ -36,36 +50,111 @@ pub struct Repetition {
     pub offset: usize,
     /// The length (in characters) of the repetition
     pub length: usize,
-    /// The "severity" of the repetition
+    /// The repetition value
     pub value: f32,
-    /// How the repetition should be displayed
-    pub colour: &'static str,
+    /// The colour of the repetition, according to the theme (empty if it has none)
+    pub colour: String,
+    /// The position of the repetition in the source text
+    pub span: Span,
+}
+
+/// Format of the text given to `Parser::tokenize`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputFormat {
+    /// Plain text
+    Text,
+    /// HTML: tags, comments, scripts, styles and escape codes are not tracked, and only the
+    /// body is checked
+    Html,
+    /// Markdown: syntax, URLs, inline code and code blocks are not tracked
+    Markdown,
+    /// LaTeX: commands, maths, comments, references and preamble are not tracked
+    Latex,
+}
+
+/// Unit in which the distance between two occurrences of a word is counted, see
+/// `Parser::with_distance_unit`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DistanceUnit {
+    /// Words that are not markup or punctuation, ignored or not
+    Words,
+    /// Characters of the text, without markup
+    Characters,
+    /// Sentences (see `Ast::sentences`)
+    Sentences,
+    /// Paragraphs (see `Ast::paragraphs`)
+    Paragraphs,
+    /// Sections, which begin at each heading (see `Ast::sections`)
+    Sections,
+}
+
+impl DistanceUnit {
+    /// Returns the position of each word of `ast` in this unit, and the position where the
+    /// next chunk of the same document begins.
+    ///
+    /// The next chunk begins a new sentence, but continues the last paragraph and section.
+    pub(crate) fn positions(self, ast: &Ast) -> (Vec<u32>, u32) {
+        let ranges = match self {
+            DistanceUnit::Words | DistanceUnit::Characters => {
+                let mut res = Vec::with_capacity(ast.words.len());
+                let mut current = 0;
+                for word in &ast.words {
+                    res.push(current);
+                    current += match *word {
+                        Word::Untracked(_) if self == DistanceUnit::Words => 0,
+                        _ if self == DistanceUnit::Words => 1,
+                        Word::Untracked(ref s) if s.starts_with('<') || s.starts_with('\\') => 0,
+                        _ => word.as_str().chars().count() as u32,
+                    };
+                }
+                return (res, current);
+            },
+            DistanceUnit::Sentences => &ast.sentences,
+            DistanceUnit::Paragraphs => &ast.paragraphs,
+            DistanceUnit::Sections => &ast.sections,
+        };
+        let mut res = vec![0; ast.words.len()];
+        for (n, &(begin, end)) in ranges.iter().enumerate() {
+            for p in res.iter_mut().take(end).skip(begin) {
+                *p = n as u32;
+            }
+        }
+        let next = if self == DistanceUnit::Sentences { ranges.len() } else { ranges.len().saturating_sub(1) };
+        (res, next as u32)
+    }
 }
 
 /// Parser which can load a string, detects repetition on it and outputs an HTML file.
-#[repr(C)]
+///
+/// A `Parser` only contains configuration, so it can be reused on as many documents
+/// as needed, and shared between threads.
 pub struct Parser {
-    /// The stemmer 
-    stemmer: Option<Stemmer>,
-    /// List of ignored words: we don't want to count repetitions on them
-    ignored: Vec<String>,
-    /// Whether there is HTML in the input text
-    html: bool,
+    /// The stemming algorithm, or `None` if stemming is disabled
+    stemmer: Option<String>,
+    /// Ignored words: we don't want to count repetitions on them
+    ignored: IgnoreList,
+    /// Format of the input text
+    input_format: InputFormat,
     /// Ignores proper nouns
     ignore_proper: bool,
-    /// Max distance to consider a repetition, only used for detect_local
-    max_distance: u32,
+    /// Max distance to consider a repetition, only used for detect_local and detect_phrases
+    pub(crate) max_distance: u32,
+    /// Unit of max_distance
+    pub(crate) distance_unit: DistanceUnit,
     /// Triggers fuzzy string matching
-    fuzzy: Option<f32>,
-
-    /// current pos in words
-    pos: u32,
-    /// Hashmap of repetitions
-    hash: HashMap<String, (u32, Vec<usize>)>,
-    /// Mapping between non-ignored and ignored words
-    pos_to_i: Vec<usize>,
-    /// number of words that have been processed before
-    offset: usize,
+    pub(crate) fuzzy: Option<f32>,
+    /// Metric used for fuzzy string matching
+    metric: Box<dyn Metric>,
+    /// Styles used to display repetitions
+    theme: Theme,
+    /// Minimal and maximal number of words of an expression, only used for detect_phrases
+    pub(crate) phrase_length: (usize, usize),
+    /// Whether ignored words are skipped when building expressions
+    pub(crate) phrase_skip_ignored: bool,
+    /// HTML attributes whose value is checked
+    html_attributes: Vec<String>,
+    /// Rules deciding which characters are part of a word
+    word_rules: WordRules,
 }
 
 impl Parser {
@@ -78,6 +167,9 @@ impl Parser {
 
     /// Returns a vector of ignored words from a string.
     ///
+    /// Words preceded by `~` are kept as is, so that they are matched by stem (see
+    /// `with_more_ignored`).
+    ///
     /// # Arguments
     ///
     /// * `list` – A space or comma separated string, containing words that
@@ -86,25 +178,88 @@ impl Parser {
     /// # Example
     ///
     /// ```rust
-    /// let v = caribon::Parser::get_ignored_from_string("some, words; to ignore");
-    /// assert_eq!(v.len(), 4);
+    /// let v = caribon::Parser::get_ignored_from_string("some, words; to ~ignore");
+    /// assert_eq!(v, vec!["some", "words", "to", "~ignore"]);
     /// ```
     pub fn get_ignored_from_string(list: &str) -> Vec<String> {
-        list.split(|c: char| !c.is_alphabetic())
+        list.split(|c: char| !(c.is_alphanumeric() || is_apostrophe(c) || is_hyphen(c) || c == '~'))
             .filter(|s| !s.is_empty())
-            .map(|s| s.to_owned())
+            .map(|s| s.replace('’', "'"))
             .collect()
     }
 
+    /// Returns a vector of ignored words from a file.
+    ///
+    /// The file contains words separated by newlines (or, as with
+    /// `get_ignored_from_string`, anything that isn't part of a word), and `#` starts a
+    /// comment that runs until the end of the line. A line starting and ending with `/`
+    /// (e.g. `/[0-9]+(st|nd|rd|th)/`) is a pattern, kept as is (see `with_ignored_pattern`).
+    pub fn get_ignored_from_file<P: AsRef<Path>>(path: P) -> Result<Vec<String>> {
+        let path = path.as_ref();
+        let content = match fs::read_to_string(path) {
+            Ok(s) => stopwords::without_comments(&s),
+            Err(e) => return Err(Error::new(&format!("Could not read ignored words file {}: {}",
+                                                     path.display(), e))),
+        };
+        let mut res = vec![];
+        for line in content.lines() {
+            let line = line.trim();
+            if line.len() >= 2 && line.starts_with('/') && line.ends_with('/') {
+                res.push(line.to_owned());
+            } else {
+                res.extend(Parser::get_ignored_from_string(line));
+            }
+        }
+        Ok(res)
+    }
+
+    /// Looks for the file containing the ignored words of a project.
+    ///
+    /// This file is named `.caribon-ignore`, and is searched in the directory of `path`
+    /// (or in `path` itself if it is a directory), then in its parent directories.
+    ///
+    /// Returns the path of the first file found, or `None`.
+    pub fn find_project_ignored<P: AsRef<Path>>(path: P) -> Option<PathBuf> {
+        let path = path.as_ref();
+        let start = if path.is_dir() {
+            path
+        } else {
+            match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            }
+        };
+        let start = start.canonicalize().unwrap_or_else(|_| start.to_path_buf());
+        start.ancestors()
+            .map(|dir| dir.join(PROJECT_IGNORED_FILE))
+            .find(|file| file.is_file())
+    }
+
+    /// Returns a vector containing all languages that have a built-in list of ignored words.
+    ///
+    /// Every language supported by the stemmer has one.
+    pub fn list_ignored_languages() -> Vec<&'static str> {
+        stopwords::languages()
+    }
+
     /// Returns a vector containing the default ignored words for this language.
+    ///
+    /// These are the built-in stop words (articles, pronouns, auxiliaries...) of the
+    /// language. `lang` can be the name of the language, as given to `Parser::new`, or its
+    /// ISO 639-1 code. If there is no list for this language, the vector is empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let german = caribon::Parser::get_ignored_from_lang("german");
+    /// assert!(german.contains(&"der".to_owned()));
+    /// assert_eq!(german, caribon::Parser::get_ignored_from_lang("de"));
+    /// assert!(caribon::Parser::get_ignored_from_lang("no_stemmer").is_empty());
+    /// ```
     pub fn get_ignored_from_lang(lang: &str) -> Vec<String> {
-        if lang.starts_with("fr") {
-            Parser::get_ignored_from_string(IGNORED_FR)
-        } else if lang.starts_with("en") {
-            Parser::get_ignored_from_string(IGNORED_EN)
-            
-        } else {
-            vec![]
+        match stopwords::list(lang) {
+            Some(list) => Parser::get_ignored_from_string(&list),
+            None => vec![],
         }
     }
 
@@ -137,8 +292,8 @@ impl Parser {
         if lang == "no_stemmer" {
             stemmer = None;
         } else {
-            stemmer = Stemmer::new(lang);
-            if stemmer.is_none() {
+            stemmer = Some(lang.to_owned());
+            if Stemmer::new(lang).is_none() {
                 return Err(Error {
                     content: format!("Language {} is not implemented.\nSupported languages: {}",
                                      lang,
@@ -146,36 +301,40 @@ impl Parser {
                 });
             }
         }
-        let ignored = Parser::get_ignored_from_lang(lang);
-        Ok(Parser {
-            stemmer: stemmer,
-            ignored: ignored,
-            html: true,
+        let mut parser = Parser {
+            stemmer,
+            ignored: IgnoreList::default(),
+            input_format: InputFormat::Html,
             ignore_proper: false,
             max_distance: 50,
+            distance_unit: DistanceUnit::Words,
             fuzzy: None,
-            pos: 1,
-            hash: HashMap::new(),
-            pos_to_i: vec!(0),
-            offset: 0,
-        })
+            phrase_length: (2, 4),
+            phrase_skip_ignored: false,
+            metric: Box::new(Levenshtein),
+            theme: Theme::default(),
+            html_attributes: vec![],
+            word_rules: WordRules::for_language(lang),
+        };
+        parser.add_ignored(&Parser::get_ignored_from_lang(lang))?;
+        Ok(parser)
     }
 
     /// Sets fuzzy string matching (default None)
     ///
     /// If sets to Some(x), instead of just using equality to compare string,
-    /// the Parser will use Levenhstein distance.
+    /// the Parser will use a metric (by default, Levenhstein distance, see `with_metric`).
     ///
     /// # Arguments
     ///
     /// * `fuzzy` – `None` to deactivate fuzzy matching, or `Some(x)` to activate it. x must be between
-    /// 0.0 and 1.0 as it corresponds to the relative distance, e.g "Caribon" has a length of 7 so if
-    /// fuzzy is set with `Some(0.5)`, it will requires a maximal distance of 3 (actually 3.5 but distance is Integer)
+    ///   0.0 and 1.0 as it corresponds to the relative distance, e.g "Caribon" has a length of 7 so if
+    ///   fuzzy is set with `Some(0.5)`, it will requires a maximal distance of 3 (actually 3.5 but distance is Integer)
     ///
     /// # Example
     ///
     /// ```rust
-    /// let mut parser = caribon::Parser::new("english").unwrap()
+    /// let parser = caribon::Parser::new("english").unwrap()
     ///                                             .with_fuzzy(Some(0.25));
     /// let mut ast = parser.tokenize("trust Rust").unwrap();
     /// parser.detect_local(&mut ast, 1.9);
@@ -187,18 +346,66 @@ impl Parser {
         self
     }
 
+    /// Sets the metric used for fuzzy string matching (default `Levenshtein`).
+    ///
+    /// This has no effect unless fuzzy string matching is activated with `with_fuzzy`.
+    ///
+    /// # Arguments
+    ///
+    /// * `metric` – One of `Levenshtein`, `DamerauLevenshtein` or `JaroWinkler`, possibly wrapped
+    ///   in `AccentInsensitive`, or your own implementation of `Metric`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use caribon::{Parser, DamerauLevenshtein};
+    ///
+    /// let parser = Parser::new("english").unwrap()
+    ///     .with_fuzzy(Some(0.2))
+    ///     .with_metric(DamerauLevenshtein);
+    /// let mut ast = parser.tokenize("weird wierd").unwrap();
+    /// parser.detect_local(&mut ast, 1.9);
+    /// assert_eq!(&parser.ast_to_markdown(&ast), "**weird** **wierd**");
+    /// ```
+    pub fn with_metric<M: Metric + 'static>(mut self, metric: M) -> Parser {
+        self.metric = Box::new(metric);
+        self
+    }
+
+    /// Sets the theme used to display repetitions (default `Theme::default()`).
+    ///
+    /// It is used by all `ast_to_*` methods: highlights that the theme doesn't display are
+    /// treated as if they weren't there.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use caribon::{Parser, Theme};
+    ///
+    /// let parser = Parser::new("english").unwrap()
+    ///     .with_theme(Theme::parse("* = purple bold").unwrap());
+    /// let mut ast = parser.tokenize("It works, it works").unwrap();
+    /// parser.detect_local(&mut ast, 1.9);
+    /// assert!(parser.ast_to_html(&ast, false).contains("style=\"color: purple; font-weight: bold;\""));
+    /// ```
+    pub fn with_theme(mut self, theme: Theme) -> Parser {
+        self.theme = theme;
+        self
+    }
+
     /// Sets max distance for repetitions (default 50).
     ///
     /// # Arguments
     ///
-    /// `max_dist` – A number corresponding to a number of words. If two
-    ///              occurences of a same word are separated by more than
-    ///              this distance, it will not be counted as a repetition.
+    /// `max_dist` – A number corresponding to a number of words (or of another unit, see
+    ///              `with_distance_unit`). If two occurences of a same word are
+    ///              separated by this distance or more, it will not be counted as a
+    ///              repetition.
     ///
     /// # Examples
     ///
     /// ```rust
-    /// let mut parser = caribon::Parser::new("english").unwrap()
+    /// let parser = caribon::Parser::new("english").unwrap()
     ///                                             .with_max_distance(20);
     /// let mut ast = parser.tokenize("This word is repeated in a few words").unwrap();
     /// parser.detect_local(&mut ast, 1.9);
@@ -207,7 +414,7 @@ impl Parser {
     /// ```
     ///
     /// ```rust
-    /// let mut parser = caribon::Parser::new("english").unwrap()
+    /// let parser = caribon::Parser::new("english").unwrap()
     ///                                             .with_max_distance(2);
     /// let mut ast = parser.tokenize("This word is repeated in a few words").unwrap();
     /// parser.detect_local(&mut ast, 1.9);
@@ -219,158 +426,560 @@ impl Parser {
         self
     }
 
+    /// Sets the unit of the max distance for repetitions (default `DistanceUnit::Words`).
+    ///
+    /// With sentences, paragraphs or sections, the distance between two words is the
+    /// number of boundaries between them, so a max distance of 1 only counts
+    /// repetitions inside the same sentence, paragraph or section, and a max distance
+    /// of 3 also counts them in the next two ones. Characters are counted in the text
+    /// between the beginnings of the two words, without markup.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use caribon::{Parser, DistanceUnit};
+    /// let parser = Parser::new("english").unwrap()
+    ///                                    .with_distance_unit(DistanceUnit::Paragraphs)
+    ///                                    .with_max_distance(1);
+    /// let mut ast = parser.tokenize("A word is a word.\n\nThis word is in another paragraph.").unwrap();
+    /// parser.detect_local(&mut ast, 1.9);
+    /// assert_eq!(&parser.ast_to_markdown(&ast),
+    ///            "A **word** is a **word**.\n\nThis word is in another paragraph.");
+    /// ```
+    pub fn with_distance_unit(mut self, unit: DistanceUnit) -> Parser {
+        self.distance_unit = unit;
+        self
+    }
+
+    /// Sets the minimal and maximal length (in words) of expressions (default 2 and 4).
+    ///
+    /// Only used by `detect_phrases`.
+    ///
+    /// # Arguments
+    ///
+    /// * `min` – The minimal number of words of an expression (at least 2).
+    /// * `max` – The maximal number of words of an expression.
+    pub fn with_phrase_length(mut self, min: usize, max: usize) -> Parser {
+        let min = if min < 2 { 2 } else { min };
+        let max = if max < min { min } else { max };
+        self.phrase_length = (min, max);
+        self
+    }
+
+    /// Sets whether ignored words are skipped when detecting expressions (default false).
+    ///
+    /// If set to `true`, ignored words (such as "the" or "of") are not part of the
+    /// expressions, so "end of the day" and "end of a day" will be considered the same
+    /// expression. If set to `false`, they must match too.
+    pub fn with_phrase_skip_ignored(mut self, skip: bool) -> Parser {
+        self.phrase_skip_ignored = skip;
+        self
+    }
+
     /// Sets HTML detection in input (default true).
     ///
     /// You should set it to `false` if a text is text-formatted, and to
     /// `true` if it contains HTML. 
     pub fn with_html(mut self, html: bool) -> Parser {
-        self.html = html;
+        self.input_format = if html { InputFormat::Html } else { InputFormat::Text };
+        self
+    }
+
+    /// Sets the format of the input text (default `InputFormat::Html`).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use caribon::{Parser, InputFormat};
+    /// let parser = Parser::new("english").unwrap()
+    ///                                    .with_input_format(InputFormat::Markdown);
+    /// let mut ast = parser.tokenize("See [this link](https://link.org) and `this link`: link!").unwrap();
+    /// parser.detect_local(&mut ast, 1.9);
+    /// assert_eq!(&parser.ast_to_markdown(&ast),
+    ///            "See [this **link**](https://link.org) and `this link`: **link**!");
+    /// ```
+    pub fn with_input_format(mut self, format: InputFormat) -> Parser {
+        self.input_format = format;
+        self
+    }
+
+    /// Sets the HTML attributes whose value is checked for repetitions, such as `alt` or
+    /// `title` (default: none).
+    ///
+    /// These words can't be highlighted in HTML outputs, but are displayed by other ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `list` – A comma or whitespace separated list of attribute names.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let parser = caribon::Parser::new("english").unwrap().with_html_attributes("alt");
+    /// let mut ast = parser.tokenize("<script>var x = \"<b>\";</script>\
+    ///                                <!-- <b>comment</b> -->\
+    ///                                <img src=\"image.png\" alt=\"An image > a picture\">\
+    ///                                <p title=\"image\">Image</p>").unwrap();
+    /// parser.detect_local(&mut ast, 1.9);
+    /// assert_eq!(&parser.ast_to_markdown(&ast),
+    ///            "<script>var x = \"<b>\";</script>\
+    ///             <!-- <b>comment</b> -->\
+    ///             <img src=\"image.png\" alt=\"An **image** > a picture\">\
+    ///             <p title=\"image\">**Image**</p>");
+    /// ```
+    pub fn with_html_attributes(mut self, list: &str) -> Parser {
+        self.html_attributes = list.split(|c: char| c == ',' || c.is_whitespace())
+                                   .filter(|s| !s.is_empty())
+                                   .map(|s| s.to_lowercase())
+                                   .collect();
+        self
+    }
+
+    /// Sets the rules deciding which characters are part of a word (default: the rules for
+    /// the language of the parser, see `WordRules::for_language`).
+    pub fn with_word_rules(mut self, rules: WordRules) -> Parser {
+        self.word_rules = rules;
         self
     }
 
     /// Sets whether repetition detection should ignore proper nouns (default false).
     ///
-    /// Basically, if set to `true`, words that start with a capital and are not at the beginning of
-    /// a sentence won't be counted for repetitions. Currently, there are still counted if they are in the beginning of
-    /// a sentence, but with most texts it won't be enough to highligth them as repetitions.
+    /// Proper nouns are found with the whole document: a word (or a sequence of words, such
+    /// as "New York" or "Charles de Gaulle") that starts with a capital in the middle of a
+    /// sentence is a name, and so are its other capitalised occurrences, including at the
+    /// beginning of a sentence. A word that is written in lowercase at least as often is
+    /// not a name, though.
+    ///
+    /// Sentences are the ones found by `tokenize` (see `Ast::sentences`).
+    ///
+    /// The names that are found are in `Ast::proper_nouns`, even if this is set to `false`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let parser = caribon::Parser::new("english").unwrap().with_ignore_proper(true);
+    /// let mut ast = parser.tokenize("New York is big. I like New York, and new ideas. New \
+    ///                                ideas, new ideas!").unwrap();
+    /// parser.detect_local(&mut ast, 1.9);
+    /// assert_eq!(ast.proper_nouns, vec!["New York"]);
+    /// assert_eq!(&parser.ast_to_markdown(&ast),
+    ///            "New York is big. I like New York, and **new** **ideas**. **New** \
+    ///             **ideas**, **new** **ideas**!");
+    /// ```
     pub fn with_ignore_proper(mut self, proper: bool) -> Parser {
         self.ignore_proper = proper;
         self
     }
 
+    /// Adds entries (words, `~` followed by a word or `/pattern/`) to the ignored words
+    pub(crate) fn add_ignored(&mut self, entries: &[String]) -> Result<()> {
+        let mut stemmer = self.stemmer.as_ref().and_then(|lang| Stemmer::new(lang));
+        for entry in entries {
+            self.ignored.add(entry, &mut stemmer)?;
+        }
+        Ok(())
+    }
+
     /// Sets the ignored list with a list of words contained in the argument string.
     ///
-    /// This method *replaces* the default list of ignored words. If you want to *add*
-    /// ignored words to the default list of a language, use `with_ignored` instead.
+    /// This method *replaces* the default list of ignored words (and the stems and
+    /// patterns added previously). If you want to *add* ignored words to the default list
+    /// of a language, use `with_more_ignored` instead.
     ///
     /// # Arguments
     ///
     /// * `list` – A comma or whitespace separated list of words that should be ignored.
     pub fn with_ignored(mut self, list: &str) -> Parser {
-        self.ignored = Parser::get_ignored_from_string(list);
-        self
+        self.ignored.clear();
+        self.with_more_ignored(list)
     }
 
     /// Appends a list of words contained in the argument string to the list of ignored words
     ///
+    /// Words are compared in lowercase. A word preceded by `~` (e.g. `~play`) is matched
+    /// by stem, so that all its forms are ignored (e.g. "plays", "played" and "playing"),
+    /// at least the ones the stemmer recognizes: irregular forms (e.g. "has" for "have")
+    /// must still be added separately.
+    ///
     /// # Arguments
     ///
     /// * `list` – A comma or whitespace separated list of words that should be ignored.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let parser = caribon::Parser::new("english").unwrap()
+    ///     .with_more_ignored("~play");
+    /// let mut ast = parser.tokenize("He played, he plays and he will play again.").unwrap();
+    /// parser.detect_local(&mut ast, 1.5);
+    /// assert_eq!(&parser.ast_to_markdown(&ast), "He played, he plays and he will play again.");
+    /// ```
     pub fn with_more_ignored(mut self, list: &str) -> Parser {
-        list.split(|c: char| !c.is_alphabetic())
-            .fold((), |_, s| {
-                self.ignored.push(s.to_owned());
-            });
+        // '/' is a separator for `get_ignored_from_string`, so there are only words and stems
+        let mut stemmer = self.stemmer.as_ref().and_then(|lang| Stemmer::new(lang));
+        for entry in Parser::get_ignored_from_string(list) {
+            self.ignored.add_word(&entry, &mut stemmer);
+        }
         self
     }
 
-    /// When we know it is the beginning of an escape character (e.g. &nbsp;)
+    /// Adds a pattern of ignored words.
+    ///
+    /// Words (in lowercase) that entirely match this regular expression are ignored. The
+    /// syntax is the one of the `regex` crate.
+    ///
+    /// Returns an error if the pattern is invalid.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let parser = caribon::Parser::new("english").unwrap()
+    ///     .with_ignored_pattern("(one|two|three|four)(teen|ty)?").unwrap();
+    /// let mut ast = parser.tokenize("Fourteen cats and fourteen dogs, three cats and four dogs").unwrap();
+    /// parser.detect_local(&mut ast, 1.5);
+    /// assert_eq!(&parser.ast_to_markdown(&ast),
+    ///            "Fourteen **cats** and fourteen **dogs**, three **cats** and four **dogs**");
+    ///
+    /// assert!(caribon::Parser::new("english").unwrap().with_ignored_pattern("(a").is_err());
+    /// ```
+    pub fn with_ignored_pattern(mut self, pattern: &str) -> Result<Parser> {
+        self.add_ignored(&[format!("/{}/", pattern)])?;
+        Ok(self)
+    }
+
+    /// Sets the ignored list with the words contained in a file.
+    ///
+    /// Like `with_ignored`, this *replaces* the default list of ignored words. See
+    /// `get_ignored_from_file` for the format of the file.
+    ///
+    /// Returns an error if the file can't be read or contains an invalid pattern.
+    pub fn with_ignored_file<P: AsRef<Path>>(mut self, path: P) -> Result<Parser> {
+        self.ignored.clear();
+        self.with_more_ignored_file(path)
+    }
+
+    /// Appends the words contained in a file to the list of ignored words.
+    ///
+    /// See `get_ignored_from_file` for the format of the file.
+    ///
+    /// Returns an error if the file can't be read or contains an invalid pattern.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use caribon::Parser;
+    ///
+    /// let mut parser = Parser::new("english").unwrap();
+    /// if let Some(path) = Parser::find_project_ignored("chapters/01.md") {
+    ///     parser = parser.with_more_ignored_file(path).unwrap();
+    /// }
+    /// ```
+    pub fn with_more_ignored_file<P: AsRef<Path>>(mut self, path: P) -> Result<Parser> {
+        self.add_ignored(&Parser::get_ignored_from_file(path)?)?;
+        Ok(self)
+    }
+
+    /// Returns the list of words that are ignored as is.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let parser = caribon::Parser::new("english").unwrap()
+    ///     .with_more_ignored("caribou");
+    /// assert!(parser.ignored().contains(&"the".to_owned()));
+    /// assert!(parser.ignored().contains(&"caribou".to_owned()));
+    /// ```
+    pub fn ignored(&self) -> &[String] {
+        self.ignored.words()
+    }
+
+    /// Returns the list of words whose all forms are ignored (see `with_more_ignored`).
+    pub fn ignored_stems(&self) -> &[String] {
+        self.ignored.stem_words()
+    }
+
+    /// Returns the list of patterns of ignored words (see `with_ignored_pattern`).
+    pub fn ignored_patterns(&self) -> Vec<&str> {
+        self.ignored.patterns()
+    }
+
+    /// When we know it is the beginning of an escape character (e.g. &nbsp;), which is
+    /// `Untracked` (as is a '&' that doesn't begin one)
     fn tokenize_escape<'b>(&self, c: &'b [char]) -> TokenizeResult<'b> {
-        let mut res = String::new();
-        let mut chars: &[char] = c;
+        let n = html::entity(c).map_or(1, |(n, _)| n);
+        Ok((&c[n..], Word::Untracked(c[..n].iter().collect())))
+    }
 
-        loop {
-            if chars.is_empty() {
-                return Err(Error::new("Error reading HTML: ill-formed escape code. Maybe this \
-                                       is not an HTML file?"));
+    /// Returns the length and the value of the first character, decoding escape codes if
+    /// the input is HTML
+    fn next_char(&self, chars: &[char]) -> Option<(usize, char)> {
+        match chars.first() {
+            None => None,
+            Some(&'&') if self.input_format == InputFormat::Html => {
+                Some(html::entity(chars).unwrap_or((1, '&')))
             }
-            let c = chars[0];
+            Some(&c) => Some((1, c)),
+        }
+    }
+
+    /// Returns a string with its escape codes decoded if the input is HTML
+    fn decode(&self, s: &str) -> String {
+        let chars: Vec<char> = s.chars().collect();
+        let mut chars: &[char] = &chars;
+        let mut res = String::new();
+        while let Some((n, c)) = self.next_char(chars) {
             res.push(c);
-            chars = &chars[1..];
-            if c == ';' {
-                return Ok((chars, Word::Untracked(res)));
-            }
+            chars = &chars[n..];
         }
+        res
     }
 
+    /// Returns true if a word begins at `chars` (see `WordRules`)
+    fn is_word_start(&self, chars: &[char]) -> bool {
+        match self.next_char(chars) {
+            Some((_, c)) => self.word_rules.is_word_char(c),
+            None => false,
+        }
+    }
+
+    /// Tokenizes HTML markup, when `chars` starts with '<'.
+    ///
+    /// Tags, comments and declarations are `Untracked`, and so is the content of elements
+    /// such as scripts, except for the value of the attributes set by `with_html_attributes`.
+    /// If this '<' is not markup, it is `Untracked` too.
     fn tokenize_html<'b>(&self,
+                         stemmer: &mut Option<Stemmer>,
                          c: &'b [char],
                          ast: &mut Ast,
                          in_body: &mut bool)
-                         -> TokenizeResult<'b> {
-        let mut res = String::new();
+                         -> Result<&'b [char]> {
         let mut chars: &[char] = c;
-        let mut brackets = 1;
-        let mut was_tag_found = false;
+        let n = html::declaration_length(chars);
+        if n > 0 {
+            ast.words.push(Word::Untracked(chars[..n].iter().collect()));
+            return Ok(&chars[n..]);
+        }
+        let tag = match html::tag(chars) {
+            Some(tag) => tag,
+            None => {
+                ast.words.push(Word::Untracked("<".to_owned()));
+                return Ok(&chars[1..]);
+            }
+        };
+
+        match (&*tag.name, tag.end) {
+            ("head", false) => {
+                ast.mark_begin_head();
+                *in_body = false;
+            }
+            ("body", false) => {
+                ast.mark_begin_body();
+                *in_body = true;
+            }
+            ("body", true) => {
+                ast.mark_end_body();
+                *in_body = false;
+            }
+            ("html", false) => {
+                *in_body = false;
+            }
+            _ => (),
+        }
 
-        res.push(chars[0]);
-        chars = &chars[1..];
+        // Only attributes of elements in the body are checked, and the tags marking the
+        // body must remain a single word
+        let mut pos = 0;
+        if *in_body && !tag.end && tag.name != "body" {
+            for &(ref name, start, end) in &tag.attributes {
+                if start == end || !self.html_attributes.contains(name) {
+                    continue;
+                }
+                ast.words.push(Word::Untracked(chars[pos..start].iter().collect()));
+                let begin = ast.words.len();
+                self.tokenize_attribute(stemmer, &chars[start..end], ast)?;
+                ast.attributes.push((begin, ast.words.len()));
+                pos = end;
+            }
+        }
+        ast.words.push(Word::Untracked(chars[pos..tag.length].iter().collect()));
+        chars = &chars[tag.length..];
+
+        if !tag.end && html::is_raw_text(&tag.name) {
+            let n = html::raw_text_length(chars, &tag.name);
+            if n > 0 {
+                ast.words.push(Word::Untracked(chars[..n].iter().collect()));
+                chars = &chars[n..];
+            }
+        }
+        Ok(chars)
+    }
 
-        // Inner function to determine if a char is part of a possible tag name
-        fn is_tag(c: char) -> bool {
-            c == '[' || c == ']' || c == '/' || c.is_alphabetic()
+    /// Tokenizes the value of an HTML attribute
+    fn tokenize_attribute(&self, stemmer: &mut Option<Stemmer>, value: &[char], ast: &mut Ast) -> Result<()> {
+        let mut chars: &[char] = value;
+        while !chars.is_empty() {
+            if self.is_word_start(chars) {
+                let (cs, word) = self.tokenize_word(stemmer, chars, true)?;
+                chars = cs;
+                ast.words.push(word);
+            } else {
+                let mut n = 0;
+                while n < chars.len() && !self.is_word_start(&chars[n..]) {
+                    n += html::entity(&chars[n..]).map_or(1, |(len, _)| len);
+                }
+                ast.words.push(Word::Untracked(chars[..n].iter().collect()));
+                chars = &chars[n..];
+            }
         }
+        Ok(())
+    }
+
+    fn tokenize_whitespace<'b>(&self, c: &'b [char]) -> TokenizeResult<'b> {
+        let mut res = String::new();
+        let mut chars: &[char] = c;
 
         loop {
             if chars.is_empty() {
-                return Err(Error {
-                    content: format!("Error reading HTML: unclosed tag. Maybe this is not an HTML \
-                                      file?
-Details: the following was not closed: {}",
-                                     res),
-                });
+                break;
             }
             let c = chars[0];
+            if ((c == '<' || c == '&') && self.input_format == InputFormat::Html) || self.is_word_start(chars) {
+                break;
+            }
+            chars = &chars[1..];
             res.push(c);
-            if !was_tag_found && is_tag(c) {
-                was_tag_found = true;
+        }
 
-                let tag: String = chars.iter()
-                                       .take_while(|c: &&char| is_tag(**c))
-                                       .map(|c| c.to_lowercase().collect::<String>())
-                                       .fold(String::new(), |acc, x| acc + &x);
-                match &*tag {
-                    "head" => {
-                        ast.mark_begin_head();
-                        *in_body = false;
-                    }
-                    "body" => {
-                        ast.mark_begin_body();
-                        *in_body = true;
-                    }
-                    "/body" => {
-                        ast.mark_end_body();
-                        *in_body = false;
-                    }
-                    "html" => {
-                        *in_body = false;
+        Ok((chars, Word::Untracked(res)))
+    }
+
+    /// Tokenizes non-alphabetic characters that are not Markdown syntax
+    fn tokenize_markdown_text<'b>(&self, c: &'b [char]) -> TokenizeResult<'b> {
+        let mut res = String::new();
+        let mut chars: &[char] = c;
+
+        loop {
+            if chars.is_empty() {
+                break;
+            }
+            let c = chars[0];
+            if !res.is_empty() && (self.is_word_start(chars) || "\n`\\]<&".contains(c)) {
+                break;
+            }
+            chars = &chars[1..];
+            res.push(c);
+        }
+
+        Ok((chars, Word::Untracked(res)))
+    }
+
+    /// Tokenizes a Markdown document.
+    ///
+    /// Block syntax (heading markers, quotes, list markers), code blocks, inline code,
+    /// link destinations, URLs, HTML tags and escapes are `Untracked`, so only the prose
+    /// is checked for repetitions, while the text is kept intact.
+    fn tokenize_markdown(&self, stemmer: &mut Option<Stemmer>, v_chars: &[char]) -> Result<Ast> {
+        let mut chars: &[char] = v_chars;
+        let mut ast = Ast::new();
+        let mut line_start = true;
+        // Whether the previous line was blank
+        let mut prev_blank = true;
+        // Whether we are in a list, where indented lines are not code blocks
+        let mut in_list = false;
+
+        let n = markdown::front_matter_length(chars);
+        if n > 0 {
+            ast.words.push(Word::Untracked(chars[..n].iter().collect()));
+            chars = &chars[n..];
+        }
+
+        while !chars.is_empty() {
+            if line_start {
+                // Block syntax
+                line_start = false;
+                let n = markdown::line_length(chars);
+                let line = &chars[..n];
+                let len = if let Some(fence) = markdown::code_fence(line) {
+                    // Fenced code block, until the closing fence or the end of the document
+                    let mut len = n;
+                    while len < chars.len() {
+                        let m = markdown::line_length(&chars[len..]);
+                        len += m;
+                        if markdown::closes_fence(&chars[len - m..len], fence) {
+                            break;
+                        }
                     }
-                    "[cdata[" => {
-                        // Special loop for CDATA
-                        chars = &chars[1..];
-                        loop {
-                            if chars.len() < 2 {
-                                return Err(Error::new("CDATA field not terminated properly"));
-                            }
-                            res.push(chars[0]);
-                            if chars[0] == ']' && chars[1] == ']' {
-                                res.push(chars[1]);
-                                chars = &chars[2..];
-                                break;
-                            } else {
-                                chars = &chars[1..];
-                            }
+                    prev_blank = false;
+                    line_start = true;
+                    len
+                } else if markdown::is_blank(line) {
+                    prev_blank = true;
+                    line_start = true;
+                    n
+                } else if prev_blank && !in_list && markdown::indentation(line) >= 4 {
+                    // Indented code block, until a line that is not indented
+                    let mut len = n;
+                    while len < chars.len() {
+                        let m = markdown::line_length(&chars[len..]);
+                        let next = &chars[len..len + m];
+                        if !markdown::is_blank(next) && markdown::indentation(next) < 4 {
+                            break;
                         }
-                        continue;
+                        len += m;
+                    }
+                    line_start = true;
+                    len
+                } else if markdown::is_link_definition(line) {
+                    prev_blank = false;
+                    line_start = true;
+                    n
+                } else {
+                    let prefix = markdown::block_prefix(line);
+                    if prefix.list {
+                        in_list = true;
+                    } else if prev_blank && markdown::indentation(line) == 0 {
+                        in_list = false;
                     }
-                    _ => (),
+                    prev_blank = false;
+                    prefix.length
+                };
+                if len > 0 {
+                    ast.words.push(Word::Untracked(chars[..len].iter().collect()));
+                    chars = &chars[len..];
                 }
+                continue;
             }
-            chars = &chars[1..];
-            if c == '<' {
-                brackets += 1;
-            }
-            if c == '>' {
-                brackets -= 1;
-                if brackets == 0 {
-                    break;
+
+            // Inline syntax
+            let c = chars[0];
+            let len = match c {
+                '\n' => {
+                    line_start = true;
+                    1
                 }
-            }
+                '`' => markdown::inline_code_length(chars),
+                '\\' if chars.len() > 1 && chars[1].is_ascii_punctuation() => 2,
+                ']' => markdown::link_end_length(chars),
+                '<' => markdown::html_length(chars),
+                '&' => markdown::entity_length(chars),
+                _ if c.is_alphabetic() => markdown::url_length(chars),
+                _ => 0,
+            };
+            let (cs, word) = if len > 0 {
+                (&chars[len..], Word::Untracked(chars[..len].iter().collect()))
+            } else if self.is_word_start(chars) {
+                self.tokenize_word(stemmer, chars, true)?
+            } else {
+                self.tokenize_markdown_text(chars)?
+            };
+            chars = cs;
+            ast.words.push(word);
         }
-        Ok((chars, Word::Untracked(res)))
+        Ok(ast)
     }
 
-    fn tokenize_whitespace<'b>(&self, c: &'b [char], is_begin: &mut bool) -> TokenizeResult<'b> {
+    /// Tokenizes non-alphabetic characters that are not LaTeX syntax
+    fn tokenize_latex_text<'b>(&self, c: &'b [char]) -> TokenizeResult<'b> {
         let mut res = String::new();
         let mut chars: &[char] = c;
 
@@ -379,71 +988,144 @@ Details: the following was not closed: {}",
                 break;
             }
             let c = chars[0];
-            if ((c == '<' || c == '&') && self.html) || c.is_alphabetic() {
+            if !res.is_empty() && (self.is_word_start(chars) || "\\%$".contains(c)) {
                 break;
             }
             chars = &chars[1..];
             res.push(c);
-            if c == '.' {
-                *is_begin = true;
-            }
         }
 
         Ok((chars, Word::Untracked(res)))
     }
 
-    /// Return true if `s` is a proper noun, false else
-    fn is_proper_noun(&self, s: &str, is_begin: bool) -> bool {
-        if self.ignore_proper {
-            if !is_begin {
-                let o = s.chars().next();
-                match o {
-                    None => false,
-                    Some(c) => c.is_uppercase(),
+    /// Tokenizes a LaTeX document.
+    ///
+    /// Control sequences, comments, maths, code and the arguments of some commands
+    /// (e.g. `\cite` or `\ref`) are `Untracked`, as is the preamble, so only the prose is
+    /// checked for repetitions. The arguments of other commands (e.g. `\emph` or `\section`)
+    /// are tracked.
+    fn tokenize_latex(&self, stemmer: &mut Option<Stemmer>, v_chars: &[char]) -> Result<Ast> {
+        let mut chars: &[char] = v_chars;
+        let mut ast = Ast::new();
+        // If there is a \begin{document}, what comes before is the preamble
+        let mut in_body = !latex::has_preamble(v_chars);
+
+        while !chars.is_empty() {
+            let c = chars[0];
+            let len = match c {
+                '%' => latex::comment_length(chars),
+                '$' => latex::math_length(chars),
+                '\\' if chars.get(1) == Some(&'(') || chars.get(1) == Some(&'[') => {
+                    latex::math_length(chars)
                 }
+                '\\' => {
+                    let n = latex::control_sequence_length(chars);
+                    let name: String = chars[1..n].iter().collect();
+                    match &*name {
+                        "begin" => {
+                            let (len, env) = latex::environment(chars);
+                            if env == "document" {
+                                ast.mark_begin_body();
+                                in_body = true;
+                                len
+                            } else if latex::is_skipped_environment(&env) {
+                                len + latex::environment_end_length(&chars[len..], &env)
+                            } else {
+                                let n = latex::environment_arguments(&env);
+                                len + latex::arguments_length(&chars[len..], n)
+                            }
+                        }
+                        "end" => {
+                            let (len, env) = latex::environment(chars);
+                            if env == "document" {
+                                ast.mark_end_body();
+                                in_body = false;
+                            }
+                            len
+                        }
+                        _ => {
+                            n + latex::arguments_length(&chars[n..], latex::skipped_arguments(&name))
+                        }
+                    }
+                }
+                _ => 0,
+            };
+            let (cs, word) = if len > 0 {
+                (&chars[len..], Word::Untracked(chars[..len].iter().collect()))
+            } else if self.is_word_start(chars) {
+                self.tokenize_word(stemmer, chars, in_body)?
             } else {
-                // Technically a proper noun could be at the beginning of a sentence :s
-                false
-            }
-        } else {
-            false
+                self.tokenize_latex_text(chars)?
+            };
+            chars = cs;
+            ast.words.push(word);
         }
+        Ok(ast)
     }
 
-    fn tokenize_word<'b>(&mut self,
+    /// Tokenizes a word, when `chars` starts with one (see `is_word_start`)
+    fn tokenize_word<'b>(&self,
+                         stemmer: &mut Option<Stemmer>,
                          c: &'b [char],
-                         is_begin: &mut bool,
                          in_body: bool)
                          -> TokenizeResult<'b> {
+        let rules = &self.word_rules;
         let mut res = String::new();
-        let mut chars: &[char] = c;
-
-        loop {
-            if chars.is_empty() {
+        // The word, with its escape codes decoded and its apostrophes normalized
+        let mut text = String::new();
+        let mut chars: &[char] = c;
+        let mut elision = false;
+
+        while let Some((n, c)) = self.next_char(chars) {
+            if rules.is_word_char(c) {
+                res.extend(&chars[..n]);
+                text.push(c);
+                chars = &chars[n..];
+                continue;
+            }
+            // An apostrophe or a hyphen is only part of a word if it is followed by a letter
+            let followed = match self.next_char(&chars[n..]) {
+                Some((_, next)) => rules.is_word_char(next),
+                None => false,
+            };
+            if !followed {
                 break;
             }
-            let c = chars[0];
-            if !c.is_alphabetic() {
+            let joins = (is_apostrophe(c) && rules.apostrophes) || (is_hyphen(c) && rules.hyphens);
+            if is_apostrophe(c) && rules.elisions.contains(&text.to_lowercase()) {
+                elision = true;
+            } else if !joins {
+                break;
+            }
+            res.extend(&chars[..n]);
+            text.push(if is_apostrophe(c) { '\'' } else { c });
+            chars = &chars[n..];
+            if elision {
                 break;
             }
-            res.push(c);
-            chars = &chars[1..];
         }
 
-        let lower_s: Vec<String> = res.chars()
-                                      .map(|c| c.to_lowercase().collect::<String>())
-                                      .collect();
+        let lower_s: Vec<String> = text.chars()
+                                       .map(|c| c.to_lowercase().collect::<String>())
+                                       .collect();
         let lower_s = lower_s.concat();
+        if !text.chars().any(|c| c.is_alphabetic()) {
+            // Numbers are not words
+            return Ok((chars, Word::Untracked(res)));
+        }
         let word = if !in_body {
             // We are not in body, so words are all untracked
             Word::Untracked(res)
-        } else if self.ignored.contains(&lower_s) || self.is_proper_noun(&res, *is_begin) {
+        } else if elision || self.ignored.matches_word(&lower_s) {
             Word::Ignored(res)
         } else {
-            Word::Tracked(res, self.stem(&lower_s), 0.0, None)
+            let stemmed = Parser::stem(stemmer, &lower_s);
+            if self.ignored.matches_stem(&stemmed) {
+                Word::Ignored(res)
+            } else {
+                Word::Tracked(res, stemmed, 0.0, None)
+            }
         };
-
-        *is_begin = false;
         Ok((chars, word))
     }
 
@@ -455,25 +1137,62 @@ Details: the following was not closed: {}",
     /// # Arguments
     ///
     /// * `s` – The string to tokenize.
-    pub fn tokenize(&mut self, s: &str) -> Result<Ast> {
+    ///
+    /// # Example
+    ///
+    /// With HTML input, escape codes are decoded to compare words, but kept as they are in
+    /// the output:
+    ///
+    /// ```rust
+    /// let parser = caribon::Parser::new("french").unwrap();
+    /// let mut ast = parser.tokenize("Une r&eacute;p&eacute;tition, une r&#233;p&#xE9;tition").unwrap();
+    /// parser.detect_local(&mut ast, 1.9);
+    /// assert_eq!(&parser.ast_to_markdown(&ast),
+    ///            "Une **r&eacute;p&eacute;tition**, une **r&#233;p&#xE9;tition**");
+    /// ```
+    pub fn tokenize(&self, s: &str) -> Result<Ast> {
+        let mut stemmer = self.stemmer.as_ref().and_then(|lang| Stemmer::new(lang));
         let v_chars: Vec<char> = s.chars().collect();
-        let mut chars: &[char] = &v_chars;
+        let mut ast = match self.input_format {
+            InputFormat::Markdown => self.tokenize_markdown(&mut stemmer, &v_chars)?,
+            InputFormat::Latex => self.tokenize_latex(&mut stemmer, &v_chars)?,
+            InputFormat::Text | InputFormat::Html => self.tokenize_text(&mut stemmer, &v_chars)?,
+        };
+        segment::segment(&mut ast, &|s| self.decode(s));
+        let (names, indices) = proper::find(&ast, &|s| self.decode(s));
+        if self.ignore_proper {
+            for i in indices {
+                let word = mem::replace(&mut ast.words[i], Word::Untracked(String::new()));
+                ast.words[i] = match word {
+                    Word::Tracked(s, _, _, _) => Word::Ignored(s),
+                    word => word,
+                };
+            }
+        }
+        ast.proper_nouns = names;
+        ast.update_spans();
+        Ok(ast)
+    }
+
+    /// Tokenizes a text or HTML document
+    fn tokenize_text(&self, stemmer: &mut Option<Stemmer>, v_chars: &[char]) -> Result<Ast> {
+        let mut chars: &[char] = v_chars;
         let mut ast = Ast::new();
-        let mut is_sentence_beginning = true;
         let mut in_body = true;
 
 
         while !chars.is_empty() {
             let c = chars[0];
-            let (cs, word) = if c.is_alphabetic() {
-                try!(self.tokenize_word(chars, &mut is_sentence_beginning, in_body))
-            } else if self.html && c == '<' {
-                is_sentence_beginning = false;
-                try!(self.tokenize_html(chars, &mut ast, &mut in_body))
-            } else if self.html && c == '&' {
-                try!(self.tokenize_escape(chars))
+            if self.input_format == InputFormat::Html && c == '<' {
+                chars = self.tokenize_html(stemmer, chars, &mut ast, &mut in_body)?;
+                continue;
+            }
+            let (cs, word) = if self.is_word_start(chars) {
+                self.tokenize_word(stemmer, chars, in_body)?
+            } else if self.input_format == InputFormat::Html && c == '&' {
+                self.tokenize_escape(chars)?
             } else {
-                try!(self.tokenize_whitespace(chars, &mut is_sentence_beginning))
+                self.tokenize_whitespace(chars)?
             };
             chars = cs;
             ast.words.push(word);
@@ -485,10 +1204,15 @@ Details: the following was not closed: {}",
     /// Detect the local number of repetitions.
     ///
     /// For each word, the repetition value is set to the total number of occurences of this word
-    /// since there has been hat least `self.max_distance` between two occurences.
+    /// since there has been hat least `self.max_distance` between two occurences (counted
+    /// in words, or in the unit set by `with_distance_unit`).
     ///
     /// It is the default algorithm, and probably the one you want to use.
     ///
+    /// This method considers that `ast` is a whole document: calling it twice on two
+    /// different documents won't detect repetitions across them. If your document is split
+    /// in multiple chunks, use a `Session` (see `new_session`) instead.
+    ///
     /// # Arguments
     ///
     /// `ast` – A mutable reference to an internal data structure returned by `tokenize`
@@ -497,84 +1221,105 @@ Details: the following was not closed: {}",
     /// # Example
     ///
     /// ```rust
-    /// let mut parser = caribon::Parser::new("english").unwrap();
+    /// let parser = caribon::Parser::new("english").unwrap();
     /// let mut ast = parser.tokenize("Testing whether this repetition detector works or does not work").unwrap();
     /// parser.detect_local(&mut ast, 1.9);
     /// let result = parser.ast_to_markdown(&ast); // not the most useful output format, but the easiest to debug
     /// assert_eq!(&result, "Testing whether this repetition detector **works** or does not **work**");
     /// ```
-    pub fn detect_local(&mut self, ast: &mut Ast, threshold: f32) {
-        let mut vec = ast.get_body_mut();
-
-        // must only be called if offset = 0
-        fn try_remove(pos: u32,
-                      h: &mut HashMap<String, (u32, Vec<usize>)>,
-                      vec: &[Word],
-                      pos_to_i: &[usize],
-                      max_distance: u32) {
-            if pos > max_distance + 1 {
-                let pos_limit = pos - max_distance;
-                let i = pos_to_i[pos_limit as usize];
-                let stemmed = match vec[i] {
-                    Word::Untracked(_) => panic!("Should not happen"),
-                    Word::Ignored(_) => return,
-                    Word::Tracked(_, ref stemmed, _, _) => stemmed,
-                };
-                if let Some(&(old_pos, _)) = h.get(stemmed) {
-                    if old_pos == pos_limit + 1 {
-                        h.remove(stemmed);
-                    }
-                }
-            }
-        }
-        for i in 0..vec.len() {
-            let elem = match vec[i] {
-                Word::Untracked(_) => None,
-                Word::Ignored(_) => {
-                    self.pos += 1;
-                    self.pos_to_i.push(i + self.offset);
-                    None
-                }
-                Word::Tracked(_, ref stemmed, _, _) => {
-                    self.pos += 1;
-                    self.pos_to_i.push(i + self.offset);
-                    let s = self.fuzzy_get(&self.hash, stemmed);
-                    Some((self.hash.remove(&s), s))
-                }
-            };
-            // Try to remove elements on a map
-            if self.fuzzy.is_some() && self.offset == 0 {
-                try_remove(self.pos, &mut self.hash, vec, &self.pos_to_i, self.max_distance);
-            }
-            if let Some((e, stemmed)) = elem {
-                // Update old stemmed to the fuzzy matched one
-                vec[i].set_stemmed(stemmed.clone());
-                let (p_pos, mut subvec) = match e {
-                    None => (0, vec![]),
-                    Some(y) => y,
-                };
-                if p_pos != 0 && self.pos - p_pos < self.max_distance {
-                    subvec.push(i + self.offset);
-                    let v = subvec.len() as f32;
-                    for x in &subvec {
-                        if *x >= self.offset {
-                            vec[*x - self.offset].set_count(v);
-                        } 
-                    }
-                    self.hash.insert(stemmed, (self.pos, subvec));
-                } else {
-                    subvec = vec![i + self.offset];
-                    self.hash.insert(stemmed, (self.pos, subvec));
-                }
-            }
-        }
-        self.offset += vec.len();
-        
-        self.highlight(vec, threshold, value_to_colour)
+    pub fn detect_local(&self, ast: &mut Ast, threshold: f32) {
+        self.new_session().detect_local(ast, threshold)
+    }
+
+    /// Creates a new detection session.
+    ///
+    /// A `Session` holds the state of local repetition detection, so it can be continued
+    /// on the next chunk of a document (e.g. the next line of a stream). Sessions are
+    /// independent of each other, so the same `Parser` can be used by many sessions at once.
+    ///
+    /// Only the words of the chunk given to `detect_local` are highlighted: previous chunks are
+    /// considered as already processed.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let parser = caribon::Parser::new("english").unwrap();
+    /// let mut session = parser.new_session();
+    /// let mut first = parser.tokenize("The first line works").unwrap();
+    /// let mut second = parser.tokenize("and the second works too").unwrap();
+    /// session.detect_local(&mut first, 1.9);
+    /// session.detect_local(&mut second, 1.9);
+    /// assert_eq!(&parser.ast_to_markdown(&first), "The first line works");
+    /// assert_eq!(&parser.ast_to_markdown(&second), "and the second **works** too");
+    /// ```
+    ///
+    /// Since the `Parser` holds no state, it can also be shared between threads:
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use std::thread;
+    ///
+    /// let parser = Arc::new(caribon::Parser::new("english").unwrap());
+    /// let handles: Vec<_> = (0..2).map(|_| {
+    ///     let parser = parser.clone();
+    ///     thread::spawn(move || {
+    ///         let mut ast = parser.tokenize("It works, it really works").unwrap();
+    ///         parser.detect_local(&mut ast, 1.9);
+    ///         parser.ast_to_markdown(&ast)
+    ///     })
+    /// }).collect();
+    /// for handle in handles {
+    ///     assert_eq!(&handle.join().unwrap(), "It **works**, it really **works**");
+    /// }
+    /// ```
+    pub fn new_session(&self) -> Session<'_> {
+        Session::new(self)
+    }
+
+    /// Detect repetitions of expressions (i.e. sequences of words).
+    ///
+    /// Works like `detect_local`, except that instead of single words it looks for sequences
+    /// of `n` stemmed words, `n` being between the bounds given to `with_phrase_length`. Two
+    /// occurrences of an expression count as a repetition if they are separated by less than
+    /// `max_distance` words (or another unit, see `with_distance_unit`). An expression must
+    /// contain at least two tracked words and never spans across the end of a sentence (see
+    /// `Ast::sentences`).
+    ///
+    /// All tracked words of a repeated expression are highlighted; longer expressions are
+    /// detected first.
+    ///
+    /// # Arguments
+    ///
+    /// `ast` – A mutable reference to an internal data structure returned by `tokenize`
+    /// `threshold` – The threshold to consider a repetition (e.g. 1.9)
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let parser = caribon::Parser::new("english").unwrap()
+    ///                                             .with_phrase_length(2, 3);
+    /// let mut ast = parser.tokenize("Working hard pays. Don't stop working hard.").unwrap();
+    /// parser.detect_phrases(&mut ast, 1.9);
+    /// let result = parser.ast_to_markdown(&ast);
+    /// assert_eq!(&result, "**Working** **hard** pays. Don't stop **working** **hard**.");
+    ///
+    /// // "Dr." doesn't end a sentence, but "Brown." does
+    /// let mut ast = parser.tokenize("Dr. Brown said: ask Dr. Brown. Brown agreed.").unwrap();
+    /// parser.detect_phrases(&mut ast, 1.9);
+    /// assert_eq!(&parser.ast_to_markdown(&ast),
+    ///            "**Dr**. **Brown** said: ask **Dr**. **Brown**. Brown agreed.");
+    /// ```
+    pub fn detect_phrases(&self, ast: &mut Ast, threshold: f32) {
+        let mut detector = PhraseDetector::new(self, threshold);
+        let findings = detector.detect(ast);
+        highlight(ast, detector.name(), &findings);
     }
 
     /// Returns stats about the words
     ///
+    /// If fuzzy string matching is activated (see `with_fuzzy`), stems that are close enough
+    /// are counted together, under the most frequent of them.
+    ///
     /// # Arguments
     ///
     /// `words` – A reference to a list of words
@@ -584,7 +1329,27 @@ Details: the following was not closed: {}",
     /// This method retuns a tuple:
     /// * the first element is a hashmap between stemmed strings and the number of occurences of this word
     /// * the second oelement is the total number of (valid) words in the list (non counting whitespace, HTML tags...)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use caribon::Parser;
+    ///
+    /// let parser = Parser::new("english").unwrap().with_fuzzy(Some(0.25));
+    /// let ast = parser.tokenize("A caribou, another caribou, and a carribou.").unwrap();
+    /// let (h, count) = parser.words_stats(&ast);
+    /// assert_eq!(count, 7);
+    /// assert_eq!(h.get("caribou"), Some(&3.0));
+    /// assert_eq!(h.get("carribou"), None);
+    /// ```
     pub fn words_stats(&self, ast: &Ast) -> (HashMap<String, f32>, u32) {
+        let (h, count, _) = self.clustered_stats(ast);
+        (h, count)
+    }
+
+    /// Same as `words_stats`, but also returns a hashmap between the stemmed strings that are
+    /// counted under another one and this one
+    pub(crate) fn clustered_stats(&self, ast: &Ast) -> (HashMap<String, f32>, u32, HashMap<String, String>) {
         let mut h: HashMap<String, f32> = HashMap::new();
         let mut count: u32 = 0;
         let words: &[Word] = ast.get_body();
@@ -607,127 +1372,189 @@ Details: the following was not closed: {}",
             };
         }
 
-        (h, count)
+        let mut clusters: HashMap<String, String> = HashMap::new();
+        if self.fuzzy.is_none() {
+            return (h, count, clusters);
+        }
+
+        // Most frequent stems come first, so they represent their cluster
+        let mut stems: Vec<(String, f32)> = h.into_iter().collect();
+        stems.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then_with(|| a.0.cmp(&b.0)));
+        let mut index = FuzzyIndex::new();
+        let mut h: HashMap<String, f32> = HashMap::new();
+        for (stemmed, x) in stems {
+            let representative = self.fuzzy_get(&index, &stemmed);
+            *h.entry(representative.clone()).or_insert(0.0) += x;
+            if representative == stemmed {
+                index.insert(&stemmed);
+            } else {
+                clusters.insert(stemmed, representative);
+            }
+        }
+        (h, count, clusters)
     }
 
     /// Detect the global number of repetitions.
     ///
     /// For each word, repetition value is set to the total number of occurences of this word in whole text,
-    /// divided by total number of words in the text
+    /// divided by total number of words in the text. If fuzzy string matching is activated, occurences
+    /// of close words are counted together (see `words_stats`).
     ///
     /// # Arguments
     ///
-    /// * `vec` – A vector of `Word`.
+    /// * `ast` – A mutable reference to an internal data structure returned by `tokenize`
     /// * `threshold` – A threshold to highlight repetitions (e.g. 0.01)
     pub fn detect_global(&self, ast: &mut Ast, threshold: f32) {
-        let (h, count) = self.words_stats(&ast);
-        let mut vec = ast.get_body_mut();
-
-
-        // If there are not enough words for the threshold, do nothing instead of
-        // underlining all words
-        if 1.0 / threshold >= (count as f32) {
-            return;
-        }
-
-        // We set each word value to the relative number of occurences
-        for i in 0..vec.len() {
-            let tmp = if let Word::Tracked(_, ref stemmed, _, _) = vec[i] {
-                let x = h.get(stemmed).expect("HashMap was not filled correctly");
-                Some(*x)
-            } else {
-                None
-            };
-            if let Some(x) = tmp {
-                vec[i].set_count(x / (count as f32));
-
-            }
-        }
-        self.highlight(vec, threshold, |_, _| "blue")
+        let mut detector = GlobalDetector::new(self, threshold);
+        let findings = detector.detect(ast);
+        highlight(ast, detector.name(), &findings);
     }
 
-    /// Highlight words whose value is superior to the threshold
-    ///
-    /// # Arguments
+    /// Display the words to terminal, higlighting the repetitions.
     ///
-    /// * `words` – A vector containing repetitions.
-    /// * `threshold` – The threshold above which words must be highlighted.
-    /// * `f` – A closure that returns the colour given the value and threshold
+    /// Use terminal colour codes to highlight the repetitions, with the 16 standard colours
+    /// (see `ast_to_terminal_with` for more options)
     ///
-    /// # Returns
+    /// # Arguments
     ///
-    /// A vector of highlight
-    fn highlight<F>(&self, words: &mut [Word], threshold: f32, f: F)
-        where F: Fn(f32, f32) -> &'static str
-    {
-        let mut res = words;
-        for i in 0..res.len() {
-            let word: &mut Word = &mut res[i];
-            if let Word::Tracked(_, _, ref mut v, ref mut option) = *word {
-                if option.is_none() && *v >= threshold {
-                    // No colour is attributed, so see if we attribute one
-                    *option = Some(f(*v, threshold));
-                }
-                *v = 0.0; //resets value to zero so it doesn't cause problem if another algorithm is used after
-            }
-        }
+    /// * `ast` – A reference to `Ast`, returned by `tokenize` and modified by `detect_*`
+    pub fn ast_to_terminal(&self, ast: &Ast) -> String {
+        self.ast_to_terminal_with(ast, &TerminalOptions::new())
     }
 
-    /// Display the words to terminal, higlighting the repetitions.
-    ///
-    /// Use terminal colour codes to highlight the repetitions
+    /// Display the words to terminal, higlighting the repetitions according to some options.
     ///
     /// # Arguments
     ///
     /// * `ast` – A reference to `Ast`, returned by `tokenize` and modified by `detect_*`
-    pub fn ast_to_terminal(&self, ast: &Ast) -> String {
-        let mut res = String::new();
-        let words = &ast.words;
-
-        for word in words {
-            match *word {
-                Word::Untracked(ref s) => res = res + s,
-                Word::Ignored(ref s) => res = res + s,
-                Word::Tracked(ref s, _, _, option) => {
-                    if let Some(colour) = option {
-                        match get_shell_colour(colour) {
-                            None => res = res + s,
-                            Some(shell_colour) => res = res + shell_colour + s + SHELL_COLOUR_OFF,
-                        }
-                    } else {
-                        res.push_str(s);
-                    }
-                }
-            }
-        }
-        res
+    /// * `options` – The colours that can be used, and whether to add a legend and line numbers
+    pub fn ast_to_terminal_with(&self, ast: &Ast, options: &TerminalOptions) -> String {
+        terminal::render(ast, &self.theme, options)
     }
 
     /// Returns a list of repetitions found in the AST.
     pub fn ast_to_repetitions(&self, ast: &Ast) -> Vec<Repetition> {
-        let mut res = vec![];
-        let words = &ast.words;
-        let mut pos = 0;
+        let spans = ast.get_spans();
+
+        ast.words
+           .iter()
+           .zip(spans.iter())
+           .filter_map(|(word, span)| match *word {
+               Word::Tracked(_, _, v, option) => self.style(option).map(|style| Repetition {
+                   offset: span.offset,
+                   length: span.length,
+                   value: v,
+                   colour: style.colour.clone().unwrap_or_default(),
+                   span: *span,
+               }),
+               _ => None,
+           })
+           .collect()
+    }
 
-        for word in words {
-            match *word {
-                Word::Untracked(ref s) => pos += s.chars().count(),
-                Word::Ignored(ref s) => pos += s.chars().count(),
-                Word::Tracked(ref s, _, v, highlight) => {
-                    let len = s.chars().count();
-                    if let Some(colour) = highlight {
-                        res.push(Repetition {
-                            offset: pos,
-                            length: len,
-                            value: v,
-                            colour: colour
-                        });
-                    }
-                    pos += len;
-                }
+    /// Returns a JSON document describing the repetitions found in the AST.
+    ///
+    /// The document is an object with a `repetitions` field, which is an array containing
+    /// an object for each highlighted word (displayed by the theme), with the following fields:
+    ///
+    /// * `id`: identifier of this occurrence (its index in the array);
+    /// * `offset` and `length`: position and length of the word, in characters;
+    /// * `byte_offset` and `byte_length`: same, but in bytes;
+    /// * `line` and `column`: position of the word (both starting at 1, column counted in characters);
+    /// * `word`: the word, as it appears in the text;
+    /// * `stem`: the stemmed value of the word;
+    /// * `detector`: the name of the detector that flagged it (e.g. "local");
+    /// * `value`: the repetition value;
+    /// * `severity`: the value divided by the threshold of the detector;
+    /// * `colour`: the colour of the repetition according to the theme, or `null`;
+    /// * `sentence` and `paragraph`: indices of the sentence and paragraph of the word (see
+    ///   `Ast::sentences` and `Ast::paragraphs`);
+    /// * `related`: the `id`s of the other occurrences of the same repetition.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let parser = caribon::Parser::new("english").unwrap();
+    /// let mut ast = parser.tokenize("It works.\nIt works!").unwrap();
+    /// parser.detect_local(&mut ast, 1.9);
+    /// let json = parser.ast_to_json(&ast);
+    /// assert!(json.contains(r#""id": 1, "offset": 13, "length": 5, "byte_offset": 13, "byte_length": 5, "line": 2, "column": 4"#));
+    /// assert!(json.contains(r#""detector": "local""#));
+    /// assert!(json.contains(r#""sentence": 1, "paragraph": 0"#));
+    /// assert!(json.contains(r#""related": [0]"#));
+    /// ```
+    pub fn ast_to_json(&self, ast: &Ast) -> String {
+        struct Occurrence<'a> {
+            index: usize,
+            span: Span,
+            word: &'a str,
+            stemmed: &'a str,
+            value: f32,
+            highlight: Highlight,
+            style: &'a Style,
+        }
+
+        let mut occurrences: Vec<Occurrence> = vec![];
+        let mut groups: HashMap<(&str, usize), Vec<usize>> = HashMap::new();
+        let spans = ast.get_spans();
+
+        for (i, (word, span)) in ast.words.iter().zip(spans.iter()).enumerate() {
+            if let Word::Tracked(ref s, ref stemmed, v, Some(highlight)) = *word {
+                let style = match self.theme.style(&highlight) {
+                    Some(style) => style,
+                    None => continue,
+                };
+                groups.entry((highlight.detector, highlight.group))
+                      .or_default()
+                      .push(occurrences.len());
+                occurrences.push(Occurrence {
+                    index: i,
+                    span: *span,
+                    word: s,
+                    stemmed,
+                    value: v,
+                    highlight,
+                    style,
+                });
+            }
+        }
+
+        let mut res = String::from("{\"repetitions\": [");
+        for (id, occurrence) in occurrences.iter().enumerate() {
+            let highlight = occurrence.highlight;
+            let related: Vec<String> = groups[&(highlight.detector, highlight.group)]
+                .iter()
+                .filter(|&&other| other != id)
+                .map(|other| other.to_string())
+                .collect();
+            if id > 0 {
+                res.push(',');
             }
+            res.push_str(&format!("\n  {{\"id\": {}, \"offset\": {}, \"length\": {}, \"byte_offset\": {}, \
+                                   \"byte_length\": {}, \"line\": {}, \"column\": {}, \"word\": \"{}\", \
+                                   \"stem\": \"{}\", \"detector\": \"{}\", \"value\": {}, \"severity\": {}, \
+                                   \"colour\": {}, \"sentence\": {}, \"paragraph\": {}, \"related\": [{}]}}",
+                                  id,
+                                  occurrence.span.offset,
+                                  occurrence.span.length,
+                                  occurrence.span.byte_offset,
+                                  occurrence.span.byte_length,
+                                  occurrence.span.line + 1,
+                                  occurrence.span.column + 1,
+                                  escape_json(occurrence.word),
+                                  escape_json(occurrence.stemmed),
+                                  escape_json(highlight.detector),
+                                  float_to_json(occurrence.value),
+                                  float_to_json(highlight.severity),
+                                  match occurrence.style.colour {
+                                      Some(ref colour) => format!("\"{}\"", escape_json(colour)),
+                                      None => "null".to_owned(),
+                                  },
+                                  index_to_json(ast.sentence_of(occurrence.index)),
+                                  index_to_json(ast.paragraph_of(occurrence.index)),
+                                  related.join(", ")));
         }
-        
+        res.push_str("\n]}\n");
         res
     }
 
@@ -744,31 +1571,26 @@ Details: the following was not closed: {}",
     /// * offset: the offset to beginning of the line
     pub fn ast_to_ispell(&self, ast: &Ast, list: bool, offset: usize) -> String {
         let mut res = String::new();
-        let words = &ast.words;
-        let mut pos = offset;
+        let spans = ast.get_spans();
 
-        for word in words {
+        for (word, span) in ast.words.iter().zip(spans.iter()) {
             match *word {
-                Word::Untracked(ref s) => pos += s.chars().count(),
-                Word::Ignored(ref s) => {
-                    pos += s.chars().count();
+                Word::Untracked(_) => (),
+                Word::Ignored(_) => {
                     if !list {
                         res.push_str("*\n");
                     }
                 },
                 Word::Tracked(ref s, _, _, highlight) => {
-                    if highlight.is_some() {
+                    if self.style(highlight).is_some() {
                         if !list {
-                            res.push_str(&format!("# {} {}\n", s, pos));
+                            res.push_str(&format!("# {} {}\n", s, offset + span.offset));
                         } else {
                             res.push_str(&format!("{}\n", s));
                         }
-                    } else {
-                        if !list {
-                            res.push_str("*\n");
-                        }
+                    } else if !list {
+                        res.push_str("*\n");
                     }
-                    pos += s.chars().count();
                 }
             }
         }
@@ -794,7 +1616,7 @@ Details: the following was not closed: {}",
                 Word::Untracked(ref s) => res = res + s,
                 Word::Ignored(ref s) => res = res + s,
                 Word::Tracked(ref s, _, _, highlight) => {
-                    if let Some(_) = highlight {
+                    if self.style(highlight).is_some() {
                         res.push_str("**");
                         res.push_str(s);
                         res.push_str("**");
@@ -808,27 +1630,113 @@ Details: the following was not closed: {}",
     }
 
 
+    /// Display the Ast to LaTeX, wrapping repetitions in a command.
+    ///
+    /// Each repetition is replaced by `\command{r,g,b}{word}`, where `r,g,b` is the colour
+    /// of the theme in the `RGB` model of the `xcolor` package (e.g. `0,128,0` for
+    /// `green`). If the document has a preamble, a default definition of the command
+    /// (colouring and underlining the word) is added at its end with `\providecommand`,
+    /// after `\RequirePackage{xcolor}` since it uses `\textcolor` (loading the package again
+    /// does no harm if the preamble already does). You can give your own definition in the
+    /// preamble instead, e.g.
+    /// `\newcommand{\caribon}[2]{{\definecolor{caribon}{RGB}{#1}\sethlcolor{caribon}\hl{#2}}}`
+    /// to use the `soul` package.
+    ///
+    /// # Arguments
+    ///
+    /// * `ast` – An Ast containing repetitions.
+    /// * `command` – The name of the command, without the backslash (e.g. "caribon").
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use caribon::{Parser, InputFormat};
+    /// let parser = Parser::new("english").unwrap()
+    ///                                    .with_input_format(InputFormat::Latex);
+    /// let mut ast = parser.tokenize(r"This \emph{works} (see~\ref{works}), it works $works^2$").unwrap();
+    /// parser.detect_local(&mut ast, 1.9);
+    /// assert_eq!(&parser.ast_to_latex(&ast, "caribon"),
+    ///            r"This \emph{\caribon{0,128,0}{works}} (see~\ref{works}), it \caribon{0,128,0}{works} $works^2$");
+    ///
+    /// let mut ast = parser.tokenize("\\documentclass{article}\n\
+    ///                                \\begin{document}\nIt works, it works.\n\\end{document}").unwrap();
+    /// parser.detect_local(&mut ast, 1.9);
+    /// assert_eq!(&parser.ast_to_latex(&ast, "hl"),
+    ///            "\\documentclass{article}\n\
+    ///             \\RequirePackage{xcolor}\n\
+    ///             \\providecommand{\\hl}[2]{\\textcolor[RGB]{#1}{\\underline{#2}}}\n\
+    ///             \\begin{document}\nIt \\hl{0,128,0}{works}, it \\hl{0,128,0}{works}.\n\\end{document}");
+    /// ```
+    pub fn ast_to_latex(&self, ast: &Ast, command: &str) -> String {
+        let mut res = String::new();
+
+        for (i, word) in ast.words.iter().enumerate() {
+            if ast.begin_body == Some(i) {
+                res.push_str("\\RequirePackage{xcolor}\n");
+                res.push_str(&format!("\\providecommand{{\\{}}}[2]{{\\textcolor[RGB]{{#1}}{{\\underline{{#2}}}}}}\n",
+                                      command));
+            }
+            match *word {
+                Word::Tracked(ref s, _, _, highlight) => match self.style(highlight) {
+                    Some(style) => {
+                        let (r, g, b) = style.colour.as_ref().and_then(|c| rgb(c)).unwrap_or((0, 0, 0));
+                        res.push_str(&format!("\\{}{{{},{},{}}}{{{}}}", command, r, g, b, s));
+                    }
+                    None => res.push_str(s),
+                },
+                Word::Untracked(ref s) | Word::Ignored(ref s) => res.push_str(s),
+            }
+        }
+        res
+    }
+
     /// Display the Ast to HTML, higlighting the repetitions.
     ///
     /// Use some basic CSS/Js for underlining repetitions and highlighting the
     /// over occurrences of the word under the mouse.
     ///
+    /// Each tracked word is put in a `span` with a `data-stem` attribute, which the script
+    /// (a single listener on the document, so there is no inline JavaScript) uses to find the
+    /// other occurrences. Unless the input format is HTML, the text is escaped.
+    ///
     /// # Arguments
     ///
     /// * `ast` – An Ast containing repetitions.
     /// * `standalone` –  If true, generate a standalone HTML file, else just an HTML fragment
-    pub fn ast_to_html(&self, ast: &mut Ast, standalone: bool) -> String {
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use caribon::{Parser, InputFormat};
+    ///
+    /// let parser = Parser::new("english").unwrap().with_input_format(InputFormat::Text);
+    /// let mut ast = parser.tokenize("<script>alert('works & works')</script>").unwrap();
+    /// parser.detect_local(&mut ast, 1.9);
+    /// let html = parser.ast_to_html(&ast, false);
+    /// assert!(!html.contains("<script>"));
+    /// assert!(html.starts_with("&lt;<span data-stem=\"script\""));
+    /// assert!(html.ends_with("&#39;)&lt;/<span data-stem=\"script\" style=\"text-decoration: underline; color: green;\">script</span>&gt;"));
+    /// assert!(html.contains("(&#39;<span data-stem=\"work\" style=\"text-decoration: underline; color: green;\">works</span> &amp; "));
+    ///
+    /// // Stems are escaped too
+    /// ast.words[3].set_stemmed("\"><img src=x onerror='alert(1)'>".to_owned());
+    /// let html = parser.ast_to_html(&ast, false);
+    /// assert!(html.contains("<span data-stem=\"&quot;&gt;&lt;img src=x onerror=&#39;alert(1)&#39;&gt;\">alert</span>"));
+    /// assert!(!html.contains("<img"));
+    /// ```
+    pub fn ast_to_html(&self, ast: &Ast, standalone: bool) -> String {
         let mut res = String::new();
-        let words: &[Word];
+        // Index of the word after which the scripts must be inserted
+        let mut scripts_after = None;
 
         // If standalone, only use words located between <body> and </body>
-        if !standalone {
+        let words: &[Word] = if !standalone {
             // If standalone, only prints the body part of the AST
-            words = ast.get_body();
+            ast.get_body()
         } else {
             // There is a head, so we must insert the scripts in the right place
             if let Some(i) = ast.begin_head {
-                ast.words.insert(i + 1, Word::Untracked(SCRIPTS.to_owned()));
+                scripts_after = Some(i);
             } else {
                 // If there is no head, generate the beginning of the document
                 res.push_str("<html><head>\n");
@@ -839,37 +1747,22 @@ Details: the following was not closed: {}",
                     res.push_str("<body>\n");
                 }
             }
-            words = &ast.words;
-        }
+            &ast.words
+        };
 
-        for word in words {
+        let offset = if standalone { 0 } else { ast.get_body_offset() };
+        for (i, word) in words.iter().enumerate() {
             match *word {
-                Word::Untracked(ref s) | Word::Ignored(ref s) => {
-                            if !self.html {
-                                // If input is in text, add <br /> for newlines
-                                let new_str = s.replace("\n", "<br/>\n");
-                                res.push_str(&new_str);
-                            } else {
-                                res.push_str(s);
-                            }
-                },
+                Word::Untracked(ref s) | Word::Ignored(ref s) => res.push_str(&self.html_text(s)),
+                Word::Tracked(ref s, _, _, _) if ast.is_in_attribute(i + offset) => res.push_str(s),
                 Word::Tracked(ref s, ref stemmed, _, option) => {
-                    let this = format!("<span class = \"{}\" onmouseover = 'on(\"{}\")' \
-                                        onmouseout = 'off(\"{}\")' {}>{}</span>",
-                                       stemmed,
-                                       stemmed,
-                                       stemmed,
-                                       if let Some(colour) = option {
-                                           format!("style = \"text-decoration: underline; color: \
-                                                    {};\"",
-                                                   colour)
-                                       } else {
-                                           String::new()
-                                       },
-                                       s);
-                    res.push_str(&this);
+                    let attributes = format!("data-stem=\"{}\"", escape_html(stemmed));
+                    res.push_str(&self.html_word(s, &attributes, self.style(option)));
                 }
             }
+            if scripts_after == Some(i) {
+                res.push_str(SCRIPTS);
+            }
         }
 
         if standalone && ast.begin_body.is_none() && ast.end_body.is_none() {
@@ -879,61 +1772,219 @@ Details: the following was not closed: {}",
         res
     }
 
+    /// Display the Ast as a standalone, interactive HTML report.
+    ///
+    /// Besides the text, the report contains a panel listing the repetitions (sorted by
+    /// severity) and some statistics about the words (see `words_stats`). Clicking on a
+    /// repetition, or on a word, jumps to its next occurrence, and repetitions can be
+    /// hidden according to their detector and style. A slider allows to change the threshold
+    /// of local repetitions: the values of the words highlighted by `detect_local` (or a
+    /// `LocalDetector`) are embedded in the report, so it is recoloured without running
+    /// Caribon again. Highlights of other detectors are kept as they are.
+    ///
+    /// Only the repetitions that are in the Ast can be shown: to allow lowering the
+    /// threshold below `threshold`, detect local repetitions with a lower one.
+    ///
+    /// Everything (style and scripts) is included in the document, so it can be viewed offline.
+    ///
+    /// # Arguments
+    ///
+    /// * `ast` – An Ast containing repetitions.
+    /// * `threshold` – The initial threshold of local repetitions, at or above the one given
+    ///   to `detect_local`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use caribon::Parser;
+    ///
+    /// let parser = Parser::new("english").unwrap().with_max_distance(3);
+    /// let mut ast = parser.tokenize("Dog and cat, another cat and dog.").unwrap();
+    /// parser.detect_local(&mut ast, 1.9);
+    /// parser.detect_global(&mut ast, 0.25);
+    /// let report = parser.ast_to_report(&ast, 1.9);
+    /// // Local repetitions embed their value...
+    /// assert!(report.contains("<span data-stem=\"cat\" data-local=\"2\" data-local-group=\"4\" \
+    ///                          style=\"text-decoration: underline; color: green;\">cat</span>"));
+    /// // ...while other ones are kept as they are
+    /// assert!(report.contains("<span data-stem=\"dog\" data-detector=\"global\" data-group=\"0\" \
+    ///                          data-severity=\"1.1428572\" style=\"text-decoration: underline; color: blue;\">Dog</span>"));
+    /// assert!(report.contains("<tr><td>cat</td><td class=\"caribon-number\">2</td>"));
+    /// ```
+    pub fn ast_to_report(&self, ast: &Ast, threshold: f32) -> String {
+        // Values and groups of the words highlighted as local repetitions
+        let mut local: HashMap<usize, (f32, usize)> = HashMap::new();
+        for (i, word) in ast.words.iter().enumerate() {
+            if let Word::Tracked(_, _, v, Some(highlight)) = *word {
+                if highlight.detector == "local" {
+                    local.insert(i, (v, highlight.group));
+                }
+            }
+        }
+        let min = local.values().fold(threshold, |min, &(v, _)| min.min(v));
+        let max = local.values().fold(threshold, |max, &(v, _)| max.max(v));
+
+        // Lines of the theme for each detector, so the script can apply it
+        let mut detectors: Vec<&str> = vec!["local"];
+        for word in &ast.words {
+            if let Word::Tracked(_, _, _, Some(highlight)) = *word {
+                if !detectors.contains(&highlight.detector) {
+                    detectors.push(highlight.detector);
+                }
+            }
+        }
+        let theme: Vec<String> = detectors.iter()
+            .map(|detector| {
+                let rules: Vec<String> = self.theme
+                    .rules_for(detector)
+                    .into_iter()
+                    .map(|(min, style)| {
+                        format!("{{\"min\": {}, \"css\": {}, \"label\": \"{}\"}}",
+                                float_to_json(min),
+                                match style {
+                                    Some(style) => format!("\"{}\"", escape_json(&style_to_css(style))),
+                                    None => "null".to_owned(),
+                                },
+                                escape_json(&label(detector, min)))
+                    })
+                    .collect();
+                format!("\"{}\": [{}]", escape_json(detector), rules.join(", "))
+            })
+            .collect();
+        // '<' is escaped so the data can't close the script element
+        let data = format!("{{\"threshold\": {}, \"theme\": {{{}}}}}",
+                           float_to_json(threshold),
+                           theme.join(", "))
+            .replace('<', "\\u003c");
+
+        let mut res = String::new();
+        res.push_str("<!DOCTYPE html>\n<html><head>\n<meta charset=\"UTF-8\">\n");
+        res.push_str("<title>Caribon report</title>\n");
+        res.push_str(&format!("<style>\n{}</style>\n</head>\n<body>\n", REPORT_CSS));
+
+        res.push_str("<div id=\"caribon-sidebar\">\n<h2>Threshold</h2>\n");
+        res.push_str(&format!("<input type=\"range\" id=\"caribon-threshold\" min=\"{}\" max=\"{}\" \
+                               step=\"0.1\" value=\"{}\">\n",
+                              min.floor(),
+                              max.ceil(),
+                              threshold));
+        res.push_str(&format!("<div>Local repetitions from <span id=\"caribon-threshold-value\">{}</span> \
+                               occurrences</div>\n",
+                              threshold));
+        res.push_str("<h2>Display</h2>\n<div id=\"caribon-toggles\"></div>\n");
+        res.push_str("<h2>Repetitions (<span id=\"caribon-count\">0</span>)</h2>\n");
+        res.push_str("<ul id=\"caribon-groups\"></ul>\n");
+
+        let (stats, count) = self.words_stats(ast);
+        let mut stems: Vec<(&String, &f32)> = stats.iter().collect();
+        stems.sort_by(|a, b| b.1.partial_cmp(a.1).unwrap().then_with(|| a.0.cmp(b.0)));
+        res.push_str("<h2>Statistics</h2>\n<table>\n");
+        res.push_str(&format!("<tr><td>Words</td><td class=\"caribon-number\">{}</td><td></td></tr>\n", count));
+        res.push_str(&format!("<tr><td>Distinct stems</td><td class=\"caribon-number\">{}</td><td></td></tr>\n",
+                              stats.len()));
+        res.push_str("<tr><th>Most frequent</th><th></th><th></th></tr>\n");
+        for (stem, x) in stems.into_iter().take(20) {
+            res.push_str(&format!("<tr><td>{}</td><td class=\"caribon-number\">{}</td>\
+                                   <td class=\"caribon-number\">{:.1}%</td></tr>\n",
+                                  escape_html(stem),
+                                  x,
+                                  x * 100.0 / count as f32));
+        }
+        res.push_str("</table>\n</div>\n<div id=\"caribon-text\">\n");
+
+        let body_offset = ast.get_body_offset();
+        for (i, word) in ast.get_body().iter().enumerate() {
+            match *word {
+                Word::Untracked(ref s) | Word::Ignored(ref s) => res.push_str(&self.html_text(s)),
+                Word::Tracked(ref s, _, _, _) if ast.is_in_attribute(i + body_offset) => res.push_str(s),
+                Word::Tracked(ref s, ref stemmed, _, option) => {
+                    let mut attributes = format!("data-stem=\"{}\"", escape_html(stemmed));
+                    let values = local.get(&(i + body_offset)).cloned();
+                    if let Some((v, group)) = values {
+                        attributes.push_str(&format!(" data-local=\"{}\" data-local-group=\"{}\"", v, group));
+                    }
+                    let highlight = match option {
+                        Some(highlight) if highlight.detector != "local" => {
+                            attributes.push_str(&format!(" data-detector=\"{}\" data-group=\"{}\" \
+                                                          data-severity=\"{}\"",
+                                                         escape_html(highlight.detector),
+                                                         highlight.group,
+                                                         float_to_json(highlight.severity)));
+                            Some(highlight)
+                        }
+                        _ => values.filter(|&(v, _)| v >= threshold).map(|(v, group)| Highlight {
+                            detector: "local",
+                            severity: v / threshold,
+                            group,
+                        }),
+                    };
+                    res.push_str(&self.html_word(s, &attributes, self.style(highlight)));
+                }
+            }
+        }
+
+        res.push_str("\n</div>\n");
+        res.push_str(&format!("<script type=\"application/json\" id=\"caribon-data\">{}</script>\n", data));
+        res.push_str(&format!("<script>\n{}</script>\n</body></html>\n", REPORT_SCRIPTS));
+        res
+    }
+
+    /// Returns the HTML code of a word that is not tracked: unless the input format is HTML,
+    /// it is escaped and newlines are replaced by `<br/>`
+    fn html_text(&self, s: &str) -> String {
+        if self.input_format != InputFormat::Html {
+            escape_html(s).replace("\n", "<br/>\n")
+        } else {
+            s.to_owned()
+        }
+    }
+
+    /// Returns the HTML code of a tracked word: a span with some (escaped) attributes and
+    /// the style of its highlight, if any
+    fn html_word(&self, s: &str, attributes: &str, style: Option<&Style>) -> String {
+        format!("<span {}{}>{}</span>",
+                attributes,
+                if let Some(style) = style {
+                    format!(" style=\"{}\"", escape_html(&style_to_css(style)))
+                } else {
+                    String::new()
+                },
+                if self.input_format != InputFormat::Html {
+                    escape_html(s)
+                } else {
+                    s.to_owned()
+                })
+    }
+
+    /// Returns the style of a highlight according to the theme, or `None` if there is no
+    /// highlight or if it must not be displayed
+    fn style(&self, highlight: Option<Highlight>) -> Option<&Style> {
+        highlight.and_then(|highlight| self.theme.style(&highlight))
+    }
+
     /// Stems a string
     ///
     /// Either warps call to `stemmer.stem`, or, if `stemmer == None`, just returns the string
-    fn stem(&mut self, s: &str) -> String {
-        match self.stemmer {
+    fn stem(stemmer: &mut Option<Stemmer>, s: &str) -> String {
+        match *stemmer {
             Some(ref mut stemmer) => stemmer.stem(s),
             None => s.to_owned(),
         }
     }
 
 
-    /// Search a string in a hashmap with fuzzy string matching
-    /// Returns the matching string, or `None`
-    fn fuzzy_get<T>(&self, h: &HashMap<String, T>, pattern: &str) -> String {
+    /// Search a string in an index with fuzzy string matching
+    ///
+    /// Returns the closest matching string, or the pattern itself if there is none
+    pub(crate) fn fuzzy_get(&self, index: &FuzzyIndex, pattern: &str) -> String {
         if let Some(d_max) = self.fuzzy {
-            let length = pattern.len();
-            if length < 2 {
-                // Pattern is too short to do fuzzy matching
-                pattern.to_owned()
-            } else {
-                // If hashmap contains the exact pattern, no need to fuzzy search
-                if h.contains_key(pattern) {
-                    pattern.to_owned()
-                } else {
-                    let mut min_distance = pattern.len() as i32;
-                    let mut key = pattern;
-                    for s in h.keys()
-                              .filter(|s| {
-                                  // string is too small
-                                  if s.len() < 2 {
-                                      return false;
-                                  }
-                                  if (s.len() as f32 - length as f32).abs() >
-                                     (d_max * pattern.len() as f32) {
-                                      // Lengths don't allow a matching distance
-                                      return false;
-                                  }
-                                  true
-                              }) {
-                        let dist = levenshtein(s, pattern) as i32;
-                        if dist < min_distance {
-                            min_distance = dist;
-                            key = s;
-                        }
-                        if min_distance == 1 {
-                            break; // best result since perfect match has been ruled out
-                        }
-                    }
-                    if min_distance <= (d_max * pattern.len() as f32) as i32 {
-                        key.to_owned()
-                    } else {
-                        pattern.to_owned()
-                    }
-                }
+            if pattern.chars().count() < 2 || index.contains(pattern) {
+                // Pattern is too short to do fuzzy matching, or there is an exact match
+                return pattern.to_owned();
             }
+            self.metric.closest(index, pattern, d_max)
+                       .unwrap_or(pattern)
+                       .to_owned()
         } else {
             pattern.to_owned()
         }
@@ -0,0 +1,271 @@
+// Copyright (C) 2015, 2016 Élisabeth HENRY.
+//
+// This file is part of Caribon.
+//
+// Caribon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Caribon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Detection of proper nouns, used by `Parser::tokenize`.
+//!
+//! It is done in two passes over the whole document. The first one looks for names:
+//! sequences of capitalised words (possibly joined by particles such as "de" or "of")
+//! that are not at the beginning of a sentence, and that are not more often written in
+//! lowercase. The second one finds all the occurrences of these names, including at the
+//! beginning of sentences.
+
+use word::{Word, Ast, is_apostrophe};
+use std::collections::HashMap;
+
+/// Lowercase words that can join the words of a name, e.g. "de" in "Charles de Gaulle"
+const PARTICLES: &[&str] = &["de", "d'", "du", "des", "la", "le", "of", "the", "van", "von",
+                             "der", "den", "da", "di", "del", "della", "dos", "y"];
+
+/// A word of a sequence of capitalised words
+struct Part {
+    /// Index of the word in the `Ast`
+    index: usize,
+    /// Lowercase form
+    key: String,
+    /// Decoded form
+    text: String,
+    /// Whether it is a particle (which can't begin or end a name)
+    particle: bool,
+}
+
+/// A sequence of capitalised words
+struct Sequence {
+    parts: Vec<Part>,
+    /// Whether it is at the beginning of a sentence
+    begin: bool,
+}
+
+/// Returns the sequences of capitalised words of the document, and the number of times
+/// each word is written in lowercase
+fn sequences(ast: &Ast, decode: &dyn Fn(&str) -> String) -> (Vec<Sequence>, HashMap<String, usize>) {
+    let mut res: Vec<Sequence> = vec![];
+    let mut lowercase: HashMap<String, usize> = HashMap::new();
+    let mut current: Vec<Part> = vec![];
+    let mut current_begin = false;
+    let mut begin = true;
+    let mut sentences = ast.sentences.iter().skip(1).peekable();
+
+    for (i, word) in ast.words.iter().enumerate() {
+        if sentences.peek().is_some_and(|&&(start, _)| start == i) {
+            sentences.next();
+            begin = true;
+            res.push(Sequence { parts: current.split_off(0), begin: current_begin });
+        }
+        let (s, tracked) = match *word {
+            Word::Untracked(ref s) => {
+                if s.starts_with('<') {
+                    // HTML markup
+                    continue;
+                }
+                if !s.chars().all(|c| c == ' ' || c == '\t' || c == '\u{a0}') {
+                    res.push(Sequence { parts: current.split_off(0), begin: current_begin });
+                }
+                continue;
+            },
+            _ if ast.is_in_attribute(i) => continue,
+            Word::Tracked(ref s, _, _, _) => (s, true),
+            Word::Ignored(ref s) => (s, false),
+        };
+        let text: String = decode(s).chars()
+                                    .map(|c| if is_apostrophe(c) { '\'' } else { c })
+                                    .collect();
+        let key = text.to_lowercase();
+        let capitalised = text.chars().next().is_some_and(|c| c.is_uppercase());
+        let particle = !capitalised && PARTICLES.contains(&&*key);
+        let elision = text.ends_with('\'');
+
+        if tracked && capitalised {
+            if current.is_empty() {
+                current_begin = begin;
+            }
+            current.push(Part { index: i, key, text, particle: false });
+        } else if particle && !current.is_empty() {
+            current.push(Part { index: i, key, text, particle: true });
+        } else {
+            if tracked && !capitalised {
+                *lowercase.entry(key).or_insert(0) += 1;
+            }
+            res.push(Sequence { parts: current.split_off(0), begin: current_begin });
+        }
+        // A word after an elision (e.g. "L'" in "L'Europe") is at the same position
+        if !elision {
+            begin = false;
+        }
+    }
+    res.push(Sequence { parts: current, begin: current_begin });
+
+    // Particles can't end a name
+    for sequence in &mut res {
+        while sequence.parts.last().is_some_and(|p| p.particle) {
+            sequence.parts.pop();
+        }
+    }
+    res.retain(|sequence| !sequence.parts.is_empty());
+    (res, lowercase)
+}
+
+/// Returns the key and the text of a name made of some parts
+fn name(parts: &[Part]) -> (String, String) {
+    let mut key = String::new();
+    let mut text = String::new();
+    for (i, part) in parts.iter().enumerate() {
+        if i > 0 && !parts[i - 1].key.ends_with('\'') {
+            key.push(' ');
+            text.push(' ');
+        }
+        key.push_str(&part.key);
+        text.push_str(&part.text);
+    }
+    (key, text)
+}
+
+/// Finds the proper nouns of a document.
+///
+/// `decode` returns the text of a word as it appears to the reader (e.g. with HTML escape
+/// codes decoded).
+///
+/// Returns the list of names, sorted, and the indices of the words that are part of them.
+pub fn find(ast: &Ast, decode: &dyn Fn(&str) -> String) -> (Vec<String>, Vec<usize>) {
+    let (sequences, lowercase) = sequences(ast, decode);
+
+    // First pass: names are the sequences that are not at the beginning of a sentence
+    let mut names: HashMap<String, (String, usize)> = HashMap::new();
+    for sequence in sequences.iter().filter(|sequence| !sequence.begin) {
+        let (key, text) = name(&sequence.parts);
+        names.entry(key).or_insert((text, 0)).1 += 1;
+    }
+    // For the ones that are, the first word is ignored (e.g. "Yesterday" in "Yesterday
+    // John Smith came"), unless the sequence begins with a name (e.g. "New York is big")
+    let mut more = vec![];
+    for sequence in sequences.iter().filter(|sequence| sequence.begin) {
+        let parts = &sequence.parts;
+        if (1..parts.len() + 1).any(|j| names.contains_key(&name(&parts[..j]).0)) {
+            continue;
+        }
+        let mut parts: &[Part] = &parts[1..];
+        while parts.first().is_some_and(|p| p.particle) {
+            parts = &parts[1..];
+        }
+        if !parts.is_empty() {
+            more.push(name(parts));
+        }
+    }
+    for (key, text) in more {
+        names.entry(key).or_insert((text, 0)).1 += 1;
+    }
+    // A single word that is written in lowercase as often is probably not a name, but
+    // capitalised for another reason (e.g. in a title)
+    names.retain(|key, &mut (_, count)| key.contains(' ') || lowercase.get(key).map_or(true, |&n| n < count));
+
+    // Second pass: occurrences of these names, looking for the longest one first
+    let mut indices = vec![];
+    for sequence in &sequences {
+        let parts = &sequence.parts;
+        let mut i = 0;
+        while i < parts.len() {
+            let found = (i + 1..parts.len() + 1).rev()
+                .filter(|&j| !parts[i].particle && !parts[j - 1].particle)
+                .find(|&j| names.contains_key(&name(&parts[i..j]).0));
+            match found {
+                Some(j) => {
+                    indices.extend(parts[i..j].iter().filter(|p| !p.particle).map(|p| p.index));
+                    i = j;
+                },
+                None => i += 1,
+            }
+        }
+    }
+
+    let mut names: Vec<(String, String)> = names.into_iter().map(|(key, (text, _))| (key, text)).collect();
+    names.sort();
+    (names.into_iter().map(|(_, text)| text).collect(), indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::{Parser, InputFormat};
+    use word::Word;
+
+    /// Returns the proper nouns of `s`, and the words that are ignored because of them
+    fn names(lang: &str, format: InputFormat, s: &str) -> (Vec<String>, Vec<String>) {
+        let parser = Parser::new(lang).unwrap()
+                                      .with_input_format(format)
+                                      .with_ignored("")
+                                      .with_ignore_proper(true);
+        let ast = parser.tokenize(s).unwrap();
+        let ignored = ast.words.iter()
+                               .filter_map(|w| match *w {
+                                   Word::Ignored(ref s) => Some(s.clone()),
+                                   _ => None,
+                               })
+                               .collect();
+        (ast.proper_nouns, ignored)
+    }
+
+    fn text(s: &str) -> (Vec<String>, Vec<String>) {
+        names("english", InputFormat::Text, s)
+    }
+
+    fn strings(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn empty_input() {
+        assert_eq!(text(""), (vec![], vec![]));
+        assert_eq!(text("   \r\n"), (vec![], vec![]));
+        assert_eq!(text("Hello."), (vec![], vec![]));
+    }
+
+    #[test]
+    fn names_with_particles() {
+        assert_eq!(text("I met Charles de Gaulle. Then de Gaulle left. Charles de Gaulle came back."),
+                   (strings(&["Charles de Gaulle", "Gaulle"]),
+                    strings(&["Charles", "Gaulle", "Gaulle", "Charles", "Gaulle"])));
+        // A particle can't begin or end a name
+        assert_eq!(text("It is the Book of the"), (strings(&["Book"]), strings(&["Book"])));
+    }
+
+    #[test]
+    fn beginning_of_sentences() {
+        // "Yesterday" is only capitalised because it begins a sentence
+        assert_eq!(text("Yesterday John Smith came.\r\nI saw Smith."),
+                   (strings(&["John Smith", "Smith"]), strings(&["John", "Smith", "Smith"])));
+        // A name found elsewhere is recognized at the beginning of a sentence
+        assert_eq!(text("Paris is big. I like Paris."),
+                   (strings(&["Paris"]), strings(&["Paris", "Paris"])));
+        assert_eq!(text("Paris is big."), (vec![], vec![]));
+    }
+
+    #[test]
+    fn lowercase_words() {
+        // Capitalised in a title, but more often in lowercase
+        assert_eq!(text("A Tale of Cats\n\nI like cats, and cats like me."),
+                   (strings(&["Tale of Cats"]), strings(&["Tale", "Cats"])));
+        assert_eq!(text("I like Cats, and cats like me, cats."), (vec![], vec![]));
+    }
+
+    #[test]
+    fn markup_and_elisions() {
+        assert_eq!(names("english", InputFormat::Html, "<p>I live in <b>New</b> York.</p>"),
+                   (strings(&["New York"]), strings(&["New", "York"])));
+        assert_eq!(names("english", InputFormat::Html, "<p>I live in New</p><p>York.</p>"),
+                   (strings(&["New"]), strings(&["New"])));
+        assert_eq!(names("french", InputFormat::Text, "L'Europe est grande. J'aime l'Europe."),
+                   (strings(&["Europe"]), strings(&["L'", "Europe", "J'", "l'", "Europe"])));
+    }
+}
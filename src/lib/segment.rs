@@ -0,0 +1,350 @@
+// Copyright (C) 2015, 2016 Élisabeth HENRY.
+//
+// This file is part of Caribon.
+//
+// Caribon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Caribon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Segmentation of a document into sentences, paragraphs and sections, used by
+//! `Parser::tokenize`.
+//!
+//! It looks at what separates two consecutive words:
+//!
+//! * a heading begins a section: an HTML `<h1>` to `<h6>` element, a LaTeX sectioning
+//!   command (e.g. `\chapter` or `\section`) or a Markdown heading (a line beginning
+//!   with `#`, or followed by a line of `=` or `-`);
+//! * a blank line, an HTML block element (e.g. `<p>` or `</li>`), a LaTeX command such as
+//!   `\par` or `\item`, or the end of a heading separates paragraphs;
+//! * `.`, `!`, `?` or `…`, followed by closing quotes or brackets and whitespace, separates
+//!   sentences, unless the next word starts with a lowercase letter (e.g. after "e.g." or
+//!   in "Hello? she asked") or the previous one is an abbreviation (e.g. "Mr.") or an
+//!   initial (e.g. "J. R. R. Tolkien");
+//! * so does an HTML `<br>`, unless the next word starts with a lowercase letter; a single
+//!   newline is only whitespace, since prose is often wrapped.
+
+use word::{Word, Ast};
+use html;
+
+/// Abbreviations that are followed by a dot, in lowercase
+const ABBREVIATIONS: &[&str] = &["mr", "mrs", "ms", "dr", "prof", "st", "jr", "sr", "vs", "fig",
+                                 "vol", "pp", "cf", "ca", "approx", "dept", "inc", "ltd", "co",
+                                 "mt", "mme", "mlle", "mm", "cie", "chap", "éd", "ed"];
+
+/// HTML elements that begin a new paragraph
+const BLOCK_ELEMENTS: &[&str] = &["address", "article", "aside", "blockquote", "body", "caption",
+                                  "dd", "div", "dl", "dt", "fieldset", "figcaption", "figure",
+                                  "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "head",
+                                  "header", "hr", "html", "li", "main", "nav", "ol", "p", "pre",
+                                  "section", "table", "td", "th", "tr", "ul"];
+
+/// LaTeX commands that begin a new paragraph
+const BLOCK_COMMANDS: &[&str] = &["par", "part", "chapter", "section", "subsection",
+                                  "subsubsection", "paragraph", "subparagraph", "item", "begin",
+                                  "end"];
+
+/// LaTeX commands that begin a new section
+const SECTION_COMMANDS: &[&str] = &["part", "chapter", "section", "subsection", "subsubsection"];
+
+/// Characters that can follow the end of a sentence, before the whitespace
+const CLOSING: &[char] = &['"', '\'', '’', '”', '»', ')', ']', '*', '_'];
+
+/// Returns true if text between two words contains a blank line
+fn has_blank_line(gap: &[char]) -> bool {
+    let mut newline = false;
+    for &c in gap {
+        if c == '\n' {
+            if newline {
+                return true;
+            }
+            newline = true;
+        } else if !c.is_whitespace() {
+            newline = false;
+        }
+    }
+    false
+}
+
+/// Returns true if text between two words, which contains a line break if `line_break`
+/// is true, ends a sentence
+fn ends_sentence(gap: &[char], line_break: bool, previous: &str, next: char) -> bool {
+    if next.is_lowercase() || next.is_numeric() {
+        return false;
+    }
+    if line_break {
+        return true;
+    }
+    for (i, &c) in gap.iter().enumerate() {
+        match c {
+            '.' | '!' | '?' | '…' => {
+                if c == '.' && i == 0 {
+                    let previous = previous.to_lowercase();
+                    if previous.chars().count() == 1 || ABBREVIATIONS.contains(&&*previous) {
+                        continue;
+                    }
+                }
+                // Not "3.5" or "index.html"
+                let after = gap[i + 1..].iter()
+                    .find(|&&c| !CLOSING.contains(&c) && !".!?…".contains(c));
+                if after.is_some_and(|c| c.is_whitespace()) {
+                    return true;
+                }
+            },
+            _ => (),
+        }
+    }
+    false
+}
+
+/// Ranges of words, as in `Ast::sentences`
+type Ranges = Vec<(usize, usize)>;
+
+/// Returns true if an untracked token is a Markdown heading marker (e.g. `## `), or the
+/// underline of a heading (e.g. `=====`), and which one
+fn markdown_heading(s: &str) -> Option<bool> {
+    let marker = s.trim_end();
+    if !marker.is_empty() && marker.chars().all(|c| c == '#') {
+        Some(false)
+    } else if marker.len() >= 3 && (marker.chars().all(|c| c == '=') || marker.chars().all(|c| c == '-')) {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// Returns ranges of words beginning at `starts`
+fn ranges(starts: &[usize], len: usize) -> Ranges {
+    starts.iter()
+        .enumerate()
+        .map(|(i, &begin)| (begin, starts.get(i + 1).cloned().unwrap_or(len)))
+        .collect()
+}
+
+/// Sets the sentences, the paragraphs and the sections of a document.
+///
+/// `decode` returns the text of a word as it appears to the reader (e.g. with HTML escape
+/// codes decoded).
+pub fn segment(ast: &mut Ast, decode: &dyn Fn(&str) -> String) {
+    if ast.words.is_empty() {
+        ast.sentences = vec![];
+        ast.paragraphs = vec![];
+        ast.sections = vec![];
+        return;
+    }
+    let mut sentences = vec![0];
+    let mut paragraphs = vec![0];
+    let mut sections = vec![0];
+    let mut previous: Option<&str> = None;
+    // What is between the previous word and this one, without markup
+    let mut gap: Vec<char> = vec![];
+    // Whether there is a `<br>` in the gap
+    let mut line_break = false;
+    // Whether the next word begins a paragraph, or a section
+    let mut block = false;
+    let mut heading = false;
+    // Whether the end of the current line is the end of a heading
+    let mut heading_line = false;
+
+    for (i, word) in ast.words.iter().enumerate() {
+        let s = match *word {
+            Word::Untracked(ref s) => {
+                let chars: Vec<char> = s.chars().collect();
+                if s.starts_with('<') {
+                    if let Some(tag) = html::tag(&chars) {
+                        let is_heading = tag.name.len() == 2 && tag.name.starts_with('h')
+                            && ('1'..='6').contains(&tag.name.chars().nth(1).unwrap());
+                        if tag.name == "br" {
+                            gap.push('\n');
+                            line_break = true;
+                        } else if is_heading && !tag.end {
+                            heading = true;
+                        } else if is_heading || BLOCK_ELEMENTS.contains(&&*tag.name) {
+                            block = true;
+                        }
+                        continue;
+                    }
+                } else if s.starts_with('\\') {
+                    let name: String = chars[1..].iter().take_while(|c| c.is_alphabetic()).collect();
+                    if SECTION_COMMANDS.contains(&&*name) {
+                        heading = true;
+                        heading_line = true;
+                    } else if BLOCK_COMMANDS.contains(&&*name) {
+                        block = true;
+                    }
+                    continue;
+                }
+                let line_start = previous.is_none() || gap.contains(&'\n');
+                match markdown_heading(s) {
+                    Some(false) if line_start => {
+                        heading = true;
+                        heading_line = true;
+                        continue;
+                    },
+                    Some(true) if line_start && previous.is_some() && !has_blank_line(&gap) => {
+                        // The previous line was a heading
+                        let begin = *paragraphs.last().unwrap();
+                        if *sections.last().unwrap() != begin {
+                            sections.push(begin);
+                        }
+                        block = true;
+                        continue;
+                    },
+                    _ => (),
+                }
+                if heading_line && chars.contains(&'\n') {
+                    heading_line = false;
+                    block = true;
+                }
+                gap.extend(chars);
+                continue;
+            },
+            _ if ast.is_in_attribute(i) => continue,
+            Word::Tracked(ref s, _, _, _) | Word::Ignored(ref s) => s,
+        };
+        if let Some(previous) = previous {
+            let next = decode(s).chars().next().unwrap_or(' ');
+            if heading {
+                sections.push(i);
+            }
+            if heading || block || has_blank_line(&gap) {
+                paragraphs.push(i);
+                sentences.push(i);
+            } else if ends_sentence(&gap, line_break, &decode(previous), next) {
+                sentences.push(i);
+            }
+        }
+        previous = Some(s);
+        gap.clear();
+        line_break = false;
+        block = false;
+        heading = false;
+    }
+    let len = ast.words.len();
+    ast.sentences = ranges(&sentences, len);
+    ast.paragraphs = ranges(&paragraphs, len);
+    ast.sections = ranges(&sections, len);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::{Parser, InputFormat};
+
+    /// Returns the sentences, paragraphs and sections of a document, each as its words
+    /// separated by spaces
+    fn segments(format: InputFormat, s: &str) -> (Vec<String>, Vec<String>, Vec<String>) {
+        let parser = Parser::new("english").unwrap().with_input_format(format);
+        let ast = parser.tokenize(s).unwrap();
+        let words = |ranges: &[(usize, usize)]| -> Vec<String> {
+            ranges.iter()
+                  .map(|&(begin, end)| {
+                      ast.words[begin..end].iter()
+                                           .enumerate()
+                                           .filter(|&(i, w)| match *w {
+                                               Word::Untracked(_) => false,
+                                               _ => !ast.is_in_attribute(begin + i),
+                                           })
+                                           .map(|(_, w)| w.as_str())
+                                           .collect::<Vec<_>>()
+                                           .join(" ")
+                  })
+                  .collect()
+        };
+        (words(&ast.sentences), words(&ast.paragraphs), words(&ast.sections))
+    }
+
+    fn sentences(format: InputFormat, s: &str) -> Vec<String> {
+        segments(format, s).0
+    }
+
+    fn strings(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn empty_input() {
+        assert_eq!(segments(InputFormat::Text, ""), (vec![], vec![], vec![]));
+        // Only whitespace: a single (empty) range
+        assert_eq!(segments(InputFormat::Text, " \r\n"),
+                   (strings(&[""]), strings(&[""]), strings(&[""])));
+    }
+
+    #[test]
+    fn sentence_ends() {
+        assert_eq!(sentences(InputFormat::Text, "One. Two! Three?! \"Four.\" (Five…) Six"),
+                   strings(&["One", "Two", "Three", "Four", "Five", "Six"]));
+        assert_eq!(sentences(InputFormat::Text, "Mr. Smith met J. R. R. Tolkien, e.g. twice. \
+                                                 Pi is 3.14. See index.html, it is. Hello? she asked"),
+                   strings(&["Mr Smith met J R R Tolkien e g twice", "Pi is", "See index html it is",
+                             "Hello she asked"]));
+        // A single newline is only whitespace
+        assert_eq!(sentences(InputFormat::Text, "I met\r\nJohn. Then\nI left"),
+                   strings(&["I met John", "Then I left"]));
+        assert_eq!(sentences(InputFormat::Markdown, "I met\nJohn yesterday"),
+                   strings(&["I met John yesterday"]));
+        assert_eq!(sentences(InputFormat::Latex, "I met\nJohn yesterday"),
+                   strings(&["I met John yesterday"]));
+        // But an HTML line break ends a sentence, unless the next word is in lowercase
+        assert_eq!(sentences(InputFormat::Html, "One<br>Two<br/>three\nFour"),
+                   strings(&["One", "Two three Four"]));
+        // Unterminated
+        assert_eq!(sentences(InputFormat::Text, "One. Two"), strings(&["One", "Two"]));
+        assert_eq!(sentences(InputFormat::Text, "One."), strings(&["One"]));
+    }
+
+    #[test]
+    fn paragraphs() {
+        let (sentences, paragraphs, sections) =
+            segments(InputFormat::Text, "One. Two\r\n\r\nthree\n \t\nFour");
+        assert_eq!(sentences, strings(&["One", "Two", "three", "Four"]));
+        assert_eq!(paragraphs, strings(&["One Two", "three", "Four"]));
+        assert_eq!(sections, strings(&["One Two three Four"]));
+    }
+
+    #[test]
+    fn markdown_headings() {
+        let (_, paragraphs, sections) =
+            segments(InputFormat::Markdown, "# One\r\ntwo\r\n\r\nThree\r\n=====\r\nfour\r\n## Five #\nsix");
+        assert_eq!(paragraphs, strings(&["One", "two", "Three", "four", "Five", "six"]));
+        assert_eq!(sections, strings(&["One two", "Three four", "Five six"]));
+        // Not headings
+        let (_, paragraphs, sections) = segments(InputFormat::Markdown, "One\n\n---\n\nTwo #3");
+        assert_eq!(paragraphs, strings(&["One", "Two"]));
+        assert_eq!(sections, strings(&["One Two"]));
+    }
+
+    #[test]
+    fn html_blocks() {
+        let (sentences, paragraphs, sections) =
+            segments(InputFormat::Html, "<h1>One</h1><div><p>Two<br>Three</p><ul><li><p>Four\
+                                         </p></li><li>Five <img alt=\"Six. Seven\">eight</li>\
+                                         </ul></div><h2>Nine<h2>Ten");
+        assert_eq!(sentences, strings(&["One", "Two", "Three", "Four", "Five eight", "Nine", "Ten"]));
+        assert_eq!(paragraphs, strings(&["One", "Two Three", "Four", "Five eight", "Nine", "Ten"]));
+        assert_eq!(sections, strings(&["One Two Three Four Five eight", "Nine", "Ten"]));
+        // Unterminated
+        let (sentences, _, sections) = segments(InputFormat::Html, "<h1>One <b>two");
+        assert_eq!(sentences, strings(&["One two"]));
+        assert_eq!(sections, strings(&["One two"]));
+    }
+
+    #[test]
+    fn latex_blocks() {
+        let (sentences, paragraphs, sections) =
+            segments(InputFormat::Latex, "\\section{One}\r\ntwo\r\n\\begin{itemize}\\item Three \
+                                          \\begin{enumerate}\\item Four\\end{enumerate}\
+                                          \\end{itemize} five\\par Six \\subsection*{Seven}");
+        assert_eq!(sentences, strings(&["One", "two", "Three", "Four", "five", "Six", "Seven"]));
+        assert_eq!(paragraphs, strings(&["One", "two", "Three", "Four", "five", "Six", "Seven"]));
+        assert_eq!(sections, strings(&["One two Three Four five Six", "Seven"]));
+    }
+}
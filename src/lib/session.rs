@@ -0,0 +1,164 @@
+// Copyright (C) 2015, 2016 Élisabeth HENRY.
+//
+// This file is part of Caribon.
+//
+// Caribon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Caribon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
+
+use word::{Word, Ast};
+use parser::Parser;
+use detector::{Finding, highlight};
+use fuzzy::FuzzyIndex;
+
+use std::collections::{HashMap, VecDeque};
+
+/// State of a local repetition detection.
+///
+/// A `Session` is created by `Parser::new_session`. It keeps track of the words it has
+/// already seen, so calling `detect_local` on multiple `Ast`s with the same session will
+/// consider them as consecutive chunks of the same document.
+///
+/// If you only have one `Ast` per document, you can just use `Parser::detect_local`,
+/// which creates a new session each time.
+pub struct Session<'a> {
+    /// The parser containing the configuration
+    parser: &'a Parser,
+    /// Position of the current word, in the unit of `max_distance`
+    pos: u32,
+    /// Hashmap of repetitions
+    hash: HashMap<String, (u32, Vec<usize>)>,
+    /// Index of the keys of `hash`, only used for fuzzy matching
+    index: FuzzyIndex,
+    /// Positions and stemmed values of the tracked words that may still be repeated
+    window: VecDeque<(u32, String)>,
+    /// number of words that have been processed before
+    offset: usize,
+    /// Position where the current chunk begins
+    pos_offset: u32,
+}
+
+impl<'a> Session<'a> {
+    /// Creates a new, empty session.
+    pub fn new(parser: &'a Parser) -> Session<'a> {
+        Session {
+            parser,
+            pos: 0,
+            hash: HashMap::new(),
+            index: FuzzyIndex::new(),
+            window: VecDeque::new(),
+            offset: 0,
+            pos_offset: 0,
+        }
+    }
+
+    /// Forgets all previously seen words, so the next call to `detect_local` starts
+    /// a new document.
+    pub fn reset(&mut self) {
+        *self = Session::new(self.parser);
+    }
+
+    /// Detect the local number of repetitions, continuing from the previous chunks.
+    ///
+    /// See `Parser::detect_local` for details.
+    ///
+    /// # Arguments
+    ///
+    /// `ast` – A mutable reference to an internal data structure returned by `tokenize`
+    /// `threshold` – The threshold to consider a repetition (e.g. 1.9)
+    pub fn detect_local(&mut self, ast: &mut Ast, threshold: f32) {
+        let findings = self.find_local(ast, threshold);
+        highlight(ast, "local", &findings);
+    }
+
+    /// Same as `detect_local`, but returns the repetitions instead of highlighting them.
+    pub fn find_local(&mut self, ast: &Ast, threshold: f32) -> Vec<Finding> {
+        let parser = self.parser;
+        let vec = ast.get_body();
+        let body_offset = ast.get_body_offset();
+        let (positions, next_pos) = parser.distance_unit.positions(ast);
+        // Repetition values, groups and (fuzzy-matched) stemmed strings of the words of this chunk
+        let mut values: Vec<(f32, usize, Option<String>)> = vec![(0.0, 0, None); vec.len()];
+
+        for (i, word) in vec.iter().enumerate() {
+            self.pos = self.pos_offset + positions[body_offset + i];
+            let elem = match *word {
+                Word::Untracked(_) | Word::Ignored(_) => None,
+                Word::Tracked(_, ref stemmed, _, _) => {
+                    let s = parser.fuzzy_get(&self.index, stemmed);
+                    self.window.push_back((self.pos, s.clone()));
+                    Some((self.hash.remove(&s), s))
+                }
+            };
+            // Try to remove elements on a map
+            self.try_remove();
+            if let Some((e, stemmed)) = elem {
+                values[i].2 = Some(stemmed.clone());
+                if parser.fuzzy.is_some() {
+                    self.index.insert(&stemmed);
+                }
+                let (p_pos, mut subvec) = e.unwrap_or_default();
+                if !subvec.is_empty() && self.pos - p_pos < parser.max_distance {
+                    subvec.push(i + self.offset);
+                    let v = subvec.len() as f32;
+                    // The first occurrence identifies the repetition
+                    let group = subvec[0];
+                    for x in &subvec {
+                        if *x >= self.offset {
+                            values[*x - self.offset].0 = v;
+                            values[*x - self.offset].1 = group;
+                        }
+                    }
+                    self.hash.insert(stemmed, (self.pos, subvec));
+                } else {
+                    subvec = vec![i + self.offset];
+                    self.hash.insert(stemmed, (self.pos, subvec));
+                }
+            }
+        }
+        self.offset += vec.len();
+        self.pos_offset += next_pos;
+
+        values.into_iter()
+              .enumerate()
+              .filter_map(|(i, (v, group, stemmed))| match stemmed {
+                  Some(stemmed) if v >= threshold => Some(Finding {
+                      index: i + body_offset,
+                      value: v,
+                      severity: v / threshold,
+                      stemmed,
+                      group,
+                  }),
+                  _ => None,
+              })
+              .collect()
+    }
+
+    /// Removes from the map the words that went out of the window, if they
+    /// have not been repeated since.
+    ///
+    /// This keeps the map and the index small, which matters for fuzzy matching and for long
+    /// streams of chunks.
+    fn try_remove(&mut self) {
+        let max_distance = self.parser.max_distance;
+        while let Some(&(pos, _)) = self.window.front() {
+            if self.pos - pos < max_distance {
+                break;
+            }
+            let (_, stemmed) = self.window.pop_front().unwrap();
+            if self.hash.get(&stemmed).is_some_and(|&(old_pos, _)| old_pos == pos) {
+                self.hash.remove(&stemmed);
+                self.index.remove(&stemmed);
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+// Copyright (C) 2015, 2016 Élisabeth HENRY.
+//
+// This file is part of Caribon.
+//
+// Caribon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Caribon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Built-in lists of stop words, used by `Parser::get_ignored_from_lang`.
+//!
+//! The lists are in `src/lib/stopwords/`, one file per language supported by the
+//! stemmer. Words are separated by whitespace, and `#` starts a comment.
+
+/// Stop words of each language: name of the stemmer, ISO 639-1 code, content of the list
+const LISTS: &[(&str, &str, &str)] = &[("danish", "da", include_str!("stopwords/danish.txt")),
+                                       ("dutch", "nl", include_str!("stopwords/dutch.txt")),
+                                       ("english", "en", include_str!("stopwords/english.txt")),
+                                       ("porter", "", include_str!("stopwords/english.txt")),
+                                       ("finnish", "fi", include_str!("stopwords/finnish.txt")),
+                                       ("french", "fr", include_str!("stopwords/french.txt")),
+                                       ("german", "de", include_str!("stopwords/german.txt")),
+                                       ("hungarian", "hu", include_str!("stopwords/hungarian.txt")),
+                                       ("italian", "it", include_str!("stopwords/italian.txt")),
+                                       ("norwegian", "no", include_str!("stopwords/norwegian.txt")),
+                                       ("portuguese", "pt", include_str!("stopwords/portuguese.txt")),
+                                       ("romanian", "ro", include_str!("stopwords/romanian.txt")),
+                                       ("russian", "ru", include_str!("stopwords/russian.txt")),
+                                       ("spanish", "es", include_str!("stopwords/spanish.txt")),
+                                       ("swedish", "sv", include_str!("stopwords/swedish.txt")),
+                                       ("turkish", "tr", include_str!("stopwords/turkish.txt"))];
+
+/// Returns true if `lang` designates a list: its name, its code, or its code followed by a
+/// region (e.g. "fr_CA" or "en-GB", but not "no_stemmer")
+fn matches(lang: &str, name: &str, code: &str) -> bool {
+    if lang == name || (!code.is_empty() && lang == code) {
+        return true;
+    }
+    if code.is_empty() || !lang.starts_with(code) {
+        return false;
+    }
+    let rest = &lang[code.len()..];
+    (rest.starts_with('_') || rest.starts_with('-'))
+        && rest.len() == 3
+        && rest[1..].chars().all(|c| c.is_ascii_alphabetic())
+}
+
+/// Returns the content of a list of words without its comments
+pub fn without_comments(content: &str) -> String {
+    content.lines()
+        .map(|line| match line.find('#') {
+            Some(i) => &line[..i],
+            None => line,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Returns the built-in list of stop words of a language, without its comments, or `None`
+/// if there is none
+pub fn list(lang: &str) -> Option<String> {
+    LISTS.iter()
+        .find(|&&(name, code, _)| matches(lang, name, code))
+        .map(|&(_, _, content)| without_comments(content))
+}
+
+/// Returns the names of the languages that have a built-in list of stop words
+pub fn languages() -> Vec<&'static str> {
+    LISTS.iter().map(|&(name, _, _)| name).collect()
+}
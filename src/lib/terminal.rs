@@ -0,0 +1,274 @@
+// Copyright (C) 2015, 2016 Élisabeth HENRY.
+//
+// This file is part of Caribon.
+//
+// Caribon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Caribon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Terminal output, used by `Parser::ast_to_terminal_with`.
+
+use word::{Word, Ast};
+use theme::{Theme, Style, Rgb, rgb, standard_colour, label};
+use display::SHELL_COLOUR_OFF;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::io::{self, IsTerminal};
+
+/// RGB values of the 16 standard terminal colours (as displayed by xterm)
+const STANDARD_COLOURS: [Rgb; 16] = [(0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0),
+                                     (0, 0, 238), (205, 0, 205), (0, 205, 205),
+                                     (229, 229, 229), (127, 127, 127), (255, 0, 0),
+                                     (0, 255, 0), (255, 255, 0), (92, 92, 255),
+                                     (255, 0, 255), (0, 255, 255), (255, 255, 255)];
+
+/// Levels of each component of the colour cube of 256-colour terminals
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Colours a terminal can display.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Palette {
+    /// No colours at all (bold, italic and underline are still used)
+    None,
+    /// The 16 standard colours
+    Ansi16,
+    /// 256 colours
+    Ansi256,
+    /// 24-bit colours
+    TrueColour,
+}
+
+impl Palette {
+    /// Guesses the palette of the terminal from the `COLORTERM` and `TERM` environment
+    /// variables, returning `Palette::None` if `NO_COLOR` is set (see <https://no-color.org>).
+    pub fn detect() -> Palette {
+        let var = |name: &str| env::var(name).unwrap_or_default();
+        if !var("NO_COLOR").is_empty() {
+            Palette::None
+        } else if var("COLORTERM") == "truecolor" || var("COLORTERM") == "24bit" {
+            Palette::TrueColour
+        } else if var("TERM").contains("256color") {
+            Palette::Ansi256
+        } else {
+            Palette::Ansi16
+        }
+    }
+}
+
+/// Options for `Parser::ast_to_terminal_with`.
+///
+/// # Example
+///
+/// ```rust
+/// use caribon::{Parser, TerminalOptions, Palette};
+///
+/// let parser = Parser::new("english").unwrap();
+/// let mut ast = parser.tokenize("It works,\nit works").unwrap();
+/// parser.detect_local(&mut ast, 1.9);
+/// let options = TerminalOptions::new().with_palette(Palette::Ansi256).with_gutter(true);
+/// assert_eq!(parser.ast_to_terminal_with(&ast, &options),
+///            "\x1B[2m1 │\x1B[0m It \x1B[4;38;5;28mworks\x1B[0m,\n\
+///             \x1B[2m2 │\x1B[0m it \x1B[4;38;5;28mworks\x1B[0m");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerminalOptions {
+    /// Whether escape codes are used at all; if false, the text is printed as is
+    pub escapes: bool,
+    /// The colours that can be used
+    pub palette: Palette,
+    /// Whether a legend of the styles is printed after the text
+    pub legend: bool,
+    /// Whether each line is preceded by its number
+    pub gutter: bool,
+}
+
+impl Default for TerminalOptions {
+    fn default() -> TerminalOptions {
+        TerminalOptions::new()
+    }
+}
+
+impl TerminalOptions {
+    /// Creates options using the 16 standard colours, without legend or gutter.
+    pub fn new() -> TerminalOptions {
+        TerminalOptions {
+            escapes: true,
+            palette: Palette::Ansi16,
+            legend: false,
+            gutter: false,
+        }
+    }
+
+    /// Creates options suited to the standard output: escape codes are only used if it is
+    /// a terminal, and the palette is detected with `Palette::detect`.
+    pub fn detect() -> TerminalOptions {
+        TerminalOptions {
+            escapes: io::stdout().is_terminal(),
+            palette: Palette::detect(),
+            legend: false,
+            gutter: false,
+        }
+    }
+
+    /// Sets whether escape codes are used.
+    pub fn with_escapes(mut self, escapes: bool) -> TerminalOptions {
+        self.escapes = escapes;
+        self
+    }
+
+    /// Sets the colours that can be used.
+    pub fn with_palette(mut self, palette: Palette) -> TerminalOptions {
+        self.palette = palette;
+        self
+    }
+
+    /// Sets whether a legend is printed after the text.
+    pub fn with_legend(mut self, legend: bool) -> TerminalOptions {
+        self.legend = legend;
+        self
+    }
+
+    /// Sets whether each line is preceded by its number.
+    pub fn with_gutter(mut self, gutter: bool) -> TerminalOptions {
+        self.gutter = gutter;
+        self
+    }
+}
+
+/// Displays an Ast to a terminal
+pub fn render(ast: &Ast, theme: &Theme, options: &TerminalOptions) -> String {
+    let mut res = String::new();
+    // Lines of the theme that are used, with their detector, for the legend
+    let mut legend: BTreeMap<(usize, &str), (f32, &Style)> = BTreeMap::new();
+
+    for word in &ast.words {
+        match *word {
+            Word::Untracked(ref s) | Word::Ignored(ref s) => res.push_str(s),
+            Word::Tracked(ref s, _, _, None) => res.push_str(s),
+            Word::Tracked(ref s, _, _, Some(highlight)) => match theme.rule(&highlight) {
+                Some((i, min, Some(style))) => {
+                    legend.insert((i, highlight.detector), (min, style));
+                    res.push_str(&styled(s, style, options));
+                }
+                _ => res.push_str(s),
+            },
+        }
+    }
+
+    if options.gutter {
+        // A final newline doesn't begin a new line
+        let end = if res.ends_with('\n') { "\n" } else { "" };
+        let lines: Vec<&str> = res[..res.len() - end.len()].split('\n').collect();
+        let width = lines.len().to_string().len();
+        let gutter = |n: usize| format!("{:>width$} │", n, width = width);
+        res = lines.iter()
+                   .enumerate()
+                   .map(|(n, line)| if options.escapes {
+                       format!("\x1B[2m{}{} {}", gutter(n + 1), SHELL_COLOUR_OFF, line)
+                   } else {
+                       format!("{} {}", gutter(n + 1), line)
+                   })
+                   .collect::<Vec<_>>()
+                   .join("\n") + end;
+    }
+
+    if options.legend && !legend.is_empty() {
+        if !res.ends_with('\n') {
+            res.push('\n');
+        }
+        res.push_str("\nLegend:");
+        for (&(_, detector), &(min, style)) in &legend {
+            res.push_str("  ");
+            res.push_str(&styled(&label(detector, min), style, options));
+        }
+        res.push('\n');
+    }
+    res
+}
+
+/// Wraps a string in the escape codes of a style
+fn styled(s: &str, style: &Style, options: &TerminalOptions) -> String {
+    if !options.escapes {
+        return s.to_owned();
+    }
+    let mut codes: Vec<String> = vec![];
+    if style.bold {
+        codes.push("1".to_owned());
+    }
+    if style.italic {
+        codes.push("3".to_owned());
+    }
+    if style.underline {
+        codes.push("4".to_owned());
+    }
+    if let Some(ref colour) = style.colour {
+        codes.extend(colour_code(colour, options.palette, false));
+    }
+    if let Some(ref colour) = style.background {
+        codes.extend(colour_code(colour, options.palette, true));
+    }
+    if codes.is_empty() {
+        s.to_owned()
+    } else {
+        format!("\x1B[{}m{}{}", codes.join(";"), s, SHELL_COLOUR_OFF)
+    }
+}
+
+/// Returns the escape code selecting a foreground or background colour
+fn colour_code(colour: &str, palette: Palette, background: bool) -> Option<String> {
+    let (r, g, b) = rgb(colour)?;
+    let base = if background { 40 } else { 30 };
+    match palette {
+        Palette::None => None,
+        Palette::Ansi16 => {
+            let n = standard_colour(colour).unwrap_or_else(|| nearest_standard_colour((r, g, b)));
+            Some(if n < 8 {
+                     base + n as usize
+                 } else {
+                     base + 60 + n as usize - 8
+                 }
+                 .to_string())
+        }
+        Palette::Ansi256 => Some(format!("{};5;{}", base + 8, nearest_256_colour((r, g, b)))),
+        Palette::TrueColour => Some(format!("{};2;{};{};{}", base + 8, r, g, b)),
+    }
+}
+
+/// Square of the distance between two colours
+fn distance((r1, g1, b1): Rgb, (r2, g2, b2): Rgb) -> i32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32) * (x as i32 - y as i32);
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+/// Returns the number of the closest of the 16 standard colours
+fn nearest_standard_colour(colour: Rgb) -> u8 {
+    (0..16u8).min_by_key(|&n| distance(colour, STANDARD_COLOURS[n as usize])).unwrap()
+}
+
+/// Returns the number of the closest colour of a 256-colour terminal (among the colour
+/// cube and the grey ramp, since the first 16 colours depend on the terminal)
+fn nearest_256_colour(colour: Rgb) -> u8 {
+    let level = |x: u8| (0..6).min_by_key(|&i| (CUBE_LEVELS[i] as i32 - x as i32).abs()).unwrap();
+    let (r, g, b) = (level(colour.0), level(colour.1), level(colour.2));
+    let cube = (CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+
+    let average = (colour.0 as usize + colour.1 as usize + colour.2 as usize) / 3;
+    let grey = (average.saturating_sub(3) / 10).min(23);
+    let grey_value = (8 + 10 * grey) as u8;
+
+    if distance(colour, (grey_value, grey_value, grey_value)) < distance(colour, cube) {
+        232 + grey as u8
+    } else {
+        16 + 36 * r as u8 + 6 * g as u8 + b as u8
+    }
+}
@@ -0,0 +1,322 @@
+// Copyright (C) 2015, 2016 Élisabeth HENRY.
+//
+// This file is part of Caribon.
+//
+// Caribon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Caribon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
+
+use word::Highlight;
+use error::{Error, Result};
+
+use std::fs;
+use std::path::Path;
+
+/// The default theme: local repetitions (and repeated expressions) are green, orange or red
+/// according to their severity, global ones are blue.
+const DEFAULT: &str = "* = green underline
+* 1.5 = orange underline
+* 2 = red underline
+global = blue underline
+";
+
+/// A theme that doesn't use colours.
+const MONOCHROME: &str = "* = underline
+* 1.5 = bold underline
+* 2 = bold italic underline
+global = italic
+";
+
+/// A theme avoiding to distinguish repetitions by red and green.
+const COLOURBLIND: &str = "* = blue underline
+* 1.5 = orange underline
+* 2 = purple bold underline
+global = cyan underline
+";
+
+/// A colour, as red, green and blue components
+pub(crate) type Rgb = (u8, u8, u8);
+
+/// Colour names that can be used in a theme, with their RGB value and, if they have one,
+/// their number among the 16 standard terminal colours
+const COLOURS: &[(&str, Rgb, Option<u8>)] = &[("black", (0, 0, 0), Some(0)),
+                                              ("red", (255, 0, 0), Some(1)),
+                                              ("green", (0, 128, 0), Some(2)),
+                                              ("yellow", (255, 255, 0), Some(3)),
+                                              ("blue", (0, 0, 255), Some(4)),
+                                              ("magenta", (255, 0, 255), Some(5)),
+                                              ("purple", (128, 0, 128), Some(5)),
+                                              ("cyan", (0, 255, 255), Some(6)),
+                                              ("white", (255, 255, 255), Some(7)),
+                                              ("grey", (128, 128, 128), Some(8)),
+                                              ("gray", (128, 128, 128), Some(8)),
+                                              ("orange", (255, 165, 0), None),
+                                              ("brown", (165, 42, 42), None),
+                                              ("pink", (255, 192, 203), None),
+                                              ("maroon", (128, 0, 0), None),
+                                              ("olive", (128, 128, 0), None),
+                                              ("lime", (0, 255, 0), None),
+                                              ("teal", (0, 128, 128), None),
+                                              ("navy", (0, 0, 128), None),
+                                              ("silver", (192, 192, 192), None),
+                                              ("gold", (255, 215, 0), None),
+                                              ("violet", (238, 130, 238), None)];
+
+/// How a highlighted word is displayed.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Style {
+    /// The colour, either a name (e.g. "red") or an hexadecimal code (e.g. "#ff0000"), if any
+    pub colour: Option<String>,
+    /// The background colour, if any
+    pub background: Option<String>,
+    /// Whether the word is in bold
+    pub bold: bool,
+    /// Whether the word is in italic
+    pub italic: bool,
+    /// Whether the word is underlined
+    pub underline: bool,
+}
+
+/// A style (or `None` to hide the highlight) for a detector, from a given severity
+#[derive(Debug, Clone)]
+struct Rule {
+    /// The name of the detector, or `None` for all detectors that have no rule of their own
+    detector: Option<String>,
+    /// The minimal severity
+    min: f32,
+    style: Option<Style>,
+}
+
+/// Maps highlights to the style they must be displayed with, according to the detector
+/// that flagged them and their severity (see `Highlight::severity`).
+///
+/// A theme can be read from a file (see `Theme::from_file` for the syntax), and is used by
+/// all `Parser::ast_to_*` methods (see `Parser::with_theme`).
+///
+/// # Example
+///
+/// ```rust
+/// use caribon::{Theme, Highlight};
+///
+/// let theme = Theme::parse("* = green underline
+///                           * 2 = red bold underline
+///                           global = none").unwrap();
+/// let mut highlight = Highlight { detector: "local", severity: 1.2, group: 0 };
+/// assert_eq!(theme.style(&highlight).unwrap().colour, Some("green".to_owned()));
+/// highlight.severity = 2.5;
+/// assert!(theme.style(&highlight).unwrap().bold);
+/// highlight.detector = "global";
+/// assert_eq!(theme.style(&highlight), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Theme {
+    rules: Vec<Rule>,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme::parse(DEFAULT).unwrap()
+    }
+}
+
+impl Theme {
+    /// Returns a list of the names of the built-in themes.
+    ///
+    /// These values are correct values to give to `Theme::named`.
+    pub fn list_names() -> Vec<&'static str> {
+        vec!["default", "monochrome", "colourblind"]
+    }
+
+    /// Returns a built-in theme, or `None` if there is no theme with this name.
+    pub fn named(name: &str) -> Option<Theme> {
+        let s = match name {
+            "default" => DEFAULT,
+            "monochrome" => MONOCHROME,
+            "colourblind" => COLOURBLIND,
+            _ => return None,
+        };
+        Some(Theme::parse(s).unwrap())
+    }
+
+    /// Reads a theme from a file.
+    ///
+    /// Each line of the file has the form `detector [severity] = style`, where:
+    ///
+    /// * `detector` is the name of a detector (e.g. `local`, `global` or `phrase`), or `*`
+    ///   for all the detectors that don't appear in the theme;
+    /// * `severity` is the minimal severity from which the style is used (default: 0);
+    /// * `style` is a colour (a name, e.g. `red`, or an hexadecimal code, e.g. `#ff0000`),
+    ///   a background colour preceded by `on` (e.g. `on yellow`) and/or some attributes
+    ///   among `bold`, `italic` and `underline`, separated by spaces, or `none` to hide
+    ///   the highlight.
+    ///
+    /// Colour names are `black`, `red`, `green`, `yellow`, `blue`, `magenta`, `purple`,
+    /// `cyan`, `white`, `grey`, `orange`, `brown`, `pink`, `maroon`, `olive`, `lime`,
+    /// `teal`, `navy`, `silver`, `gold` and `violet`.
+    ///
+    /// Empty lines and lines starting with `#` are ignored. If several lines have the same
+    /// detector and severity, the last one is used.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Theme> {
+        let path = path.as_ref();
+        match fs::read_to_string(path) {
+            Ok(s) => Theme::parse(&s),
+            Err(e) => Err(Error::new(&format!("Could not read theme file {}: {}", path.display(), e))),
+        }
+    }
+
+    /// Reads a theme from a string, with the same syntax as `from_file`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use caribon::Theme;
+    /// assert!(Theme::parse("local 2.5 = bold red\nphrase = none").is_ok());
+    /// assert!(Theme::parse("local NaN = red").is_err());
+    /// assert!(Theme::parse("local inf = red").is_err());
+    /// ```
+    pub fn parse(s: &str) -> Result<Theme> {
+        let mut rules = vec![];
+        for (n, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |msg: &str| Error::new(&format!("Invalid theme, line {}: {}", n + 1, msg));
+
+            let (key, value) = match line.find('=') {
+                Some(i) => (&line[..i], &line[i + 1..]),
+                None => return Err(error("expected 'detector [severity] = style'")),
+            };
+            let mut key = key.split_whitespace();
+            let detector = match key.next() {
+                Some("*") => None,
+                Some(detector) => Some(detector.to_owned()),
+                None => return Err(error("missing detector name")),
+            };
+            let min = match key.next() {
+                Some(x) => match x.parse::<f32>() {
+                    Ok(x) if x.is_finite() => x,
+                    _ => return Err(error(&format!("invalid severity '{}'", x))),
+                },
+                None => 0.0,
+            };
+            if key.next().is_some() {
+                return Err(error("expected 'detector [severity] = style'"));
+            }
+
+            let style = if value.trim() == "none" {
+                None
+            } else {
+                let mut style = Style::default();
+                let mut words = value.split_whitespace();
+                while let Some(word) = words.next() {
+                    match word {
+                        "bold" => style.bold = true,
+                        "italic" => style.italic = true,
+                        "underline" => style.underline = true,
+                        "on" => match words.next() {
+                            Some(colour) if style.background.is_none() && rgb(colour).is_some() => {
+                                style.background = Some(colour.to_owned())
+                            }
+                            _ => return Err(error("expected a background colour after 'on'")),
+                        },
+                        _ if style.colour.is_none() && rgb(word).is_some() => style.colour = Some(word.to_owned()),
+                        _ => return Err(error(&format!("invalid style '{}'", word))),
+                    }
+                }
+                if style == Style::default() {
+                    return Err(error("missing style"));
+                }
+                Some(style)
+            };
+            rules.push(Rule { detector, min, style });
+        }
+        Ok(Theme { rules })
+    }
+
+    /// Returns the style of a highlight, or `None` if it must not be displayed.
+    pub fn style(&self, highlight: &Highlight) -> Option<&Style> {
+        self.rule(highlight).and_then(|(_, _, style)| style)
+    }
+
+    /// Returns the index of the line of the theme used for a highlight, with its minimal
+    /// severity and its style
+    pub(crate) fn rule(&self, highlight: &Highlight) -> Option<(usize, f32, Option<&Style>)> {
+        self.rules_of(highlight.detector)
+            .into_iter()
+            .filter(|&(_, rule)| rule.min <= highlight.severity)
+            .max_by(|a, b| a.1.min.total_cmp(&b.1.min))
+            .map(|(i, rule)| (i, rule.min, rule.style.as_ref()))
+    }
+
+    /// Returns the minimal severity and the style of the lines of the theme that apply to
+    /// a detector, sorted by minimal severity
+    pub(crate) fn rules_for(&self, detector: &str) -> Vec<(f32, Option<&Style>)> {
+        let mut res: Vec<_> = self.rules_of(detector)
+                                  .into_iter()
+                                  .map(|(_, rule)| (rule.min, rule.style.as_ref()))
+                                  .collect();
+        res.sort_by(|a, b| a.0.total_cmp(&b.0));
+        res
+    }
+
+    /// Returns the lines of the theme that apply to a detector, with their index: its own
+    /// lines if it has some, else the ones for all detectors
+    fn rules_of(&self, detector: &str) -> Vec<(usize, &Rule)> {
+        let specific = self.rules
+                           .iter()
+                           .any(|rule| rule.detector.as_deref() == Some(detector));
+        self.rules
+            .iter()
+            .enumerate()
+            .filter(|&(_, rule)| if specific {
+                rule.detector.as_deref() == Some(detector)
+            } else {
+                rule.detector.is_none()
+            })
+            .collect()
+    }
+}
+
+/// Returns the description of a line of a theme, as displayed in legends (e.g.
+/// "local (severity >= 1.5)")
+pub(crate) fn label(detector: &str, min: f32) -> String {
+    if min > 0.0 {
+        format!("{} (severity >= {})", detector, min)
+    } else {
+        detector.to_owned()
+    }
+}
+
+/// Returns the RGB value of a colour (a name or an hexadecimal code), or `None` if it is
+/// not a valid colour
+pub(crate) fn rgb(colour: &str) -> Option<Rgb> {
+    if let Some(hex) = colour.strip_prefix('#') {
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let digit = |i: usize, n: usize| u8::from_str_radix(&hex[i..i + n], 16).ok();
+        match hex.len() {
+            3 => Some((digit(0, 1)? * 17, digit(1, 1)? * 17, digit(2, 1)? * 17)),
+            6 => Some((digit(0, 2)?, digit(2, 2)?, digit(4, 2)?)),
+            _ => None,
+        }
+    } else {
+        COLOURS.iter().find(|c| c.0 == colour).map(|c| c.1)
+    }
+}
+
+/// Returns the number of a colour among the 16 standard terminal colours, if it is one
+/// of them
+pub(crate) fn standard_colour(colour: &str) -> Option<u8> {
+    COLOURS.iter().find(|c| c.0 == colour).and_then(|c| c.2)
+}
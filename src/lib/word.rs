@@ -15,10 +15,176 @@
 // You should have received a copy of the GNU Lesser General Public License
 // along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+/// How a repeated word must be highlighted, and why.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Highlight {
+    /// The name of the detector that flagged this word (see `Detector::name`)
+    pub detector: &'static str,
+    /// The severity of the repetition: its value divided by the threshold of the detector
+    /// (so at least 1.0 for built-in detectors). The `Theme` uses it to choose a style.
+    pub severity: f32,
+    /// Identifier of the repetition this word is part of.
+    ///
+    /// All words flagged by the same detector with the same group are occurrences of the
+    /// same repetition.
+    pub group: usize,
+}
+
+/// Position of a `Word` in the source text.
+///
+/// Lines and columns start at 0. Columns are counted in characters (`column`) and in
+/// UTF-16 code units (`utf16_column`, which is what e.g. the Language Server Protocol uses).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    /// The beginning of the word, in characters
+    pub offset: usize,
+    /// The length of the word, in characters
+    pub length: usize,
+    /// The beginning of the word, in bytes
+    pub byte_offset: usize,
+    /// The length of the word, in bytes
+    pub byte_length: usize,
+    /// The line where the word begins
+    pub line: usize,
+    /// The column where the word begins, in characters
+    pub column: usize,
+    /// The column where the word begins, in UTF-16 code units
+    pub utf16_column: usize,
+    /// The length of the word, in UTF-16 code units
+    pub utf16_length: usize,
+}
+
+/// Words that are elided before a vowel in French, without their apostrophe
+const ELISIONS_FR: &str = "l d j m n s t c qu jusqu lorsqu puisqu quoiqu";
+/// Words that are elided before a vowel in Italian, without their apostrophe
+const ELISIONS_IT: &str = "l un dell all dall nell sull coll quell c d m n s t v";
+
+/// Rules deciding which characters are part of a word (see `Parser::with_word_rules`).
+///
+/// Words are made of letters and, if `digits` is set, digits. As in Unicode word boundaries
+/// (UAX #29), letters joined by an apostrophe (`'` or `’`) are a single word (e.g. "don't"),
+/// except after an elision (e.g. "l'" in "l'homme"), which is a separate, ignored word.
+/// Unlike Unicode rules, letters joined by a hyphen can be a single word too (e.g.
+/// "well-known").
+///
+/// Apostrophes are all replaced by `'` to compare words.
+///
+/// # Example
+///
+/// ```rust
+/// use caribon::{Parser, WordRules};
+///
+/// let parser = Parser::new("french").unwrap();
+/// let mut ast = parser.tokenize("Aujourd'hui, l'homme voit l’homme.").unwrap();
+/// parser.detect_local(&mut ast, 1.9);
+/// assert_eq!(&parser.ast_to_markdown(&ast), "Aujourd'hui, l'**homme** voit l’**homme**.");
+///
+/// let parser = Parser::new("english").unwrap();
+/// let mut ast = parser.tokenize("A well-known fact: COVID-19 is not COVID-20, o'clock; o’clock!").unwrap();
+/// parser.detect_local(&mut ast, 1.9);
+/// assert_eq!(&parser.ast_to_markdown(&ast),
+///            "A well-known fact: COVID-19 is not COVID-20, **o'clock**; **o’clock**!");
+///
+/// let parser = Parser::new("english").unwrap()
+///     .with_word_rules(WordRules::new().with_hyphens(false).with_digits(false));
+/// let mut ast = parser.tokenize("A well-known fact: COVID-19 is not COVID-20").unwrap();
+/// parser.detect_local(&mut ast, 1.9);
+/// assert_eq!(&parser.ast_to_markdown(&ast),
+///            "A well-known fact: **COVID**-19 is not **COVID**-20");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordRules {
+    /// Whether letters joined by an apostrophe are a single word
+    pub apostrophes: bool,
+    /// Whether letters or digits joined by a hyphen are a single word
+    pub hyphens: bool,
+    /// Whether digits are part of words (words must still contain a letter)
+    pub digits: bool,
+    /// Words (in lowercase, without their apostrophe) that are separate words when they
+    /// are elided, e.g. "l" for "l'homme"
+    pub elisions: Vec<String>,
+}
+
+impl Default for WordRules {
+    fn default() -> WordRules {
+        WordRules::new()
+    }
+}
+
+impl WordRules {
+    /// Creates rules joining words on apostrophes and hyphens, and allowing digits in
+    /// words, without elisions.
+    pub fn new() -> WordRules {
+        WordRules {
+            apostrophes: true,
+            hyphens: true,
+            digits: true,
+            elisions: vec![],
+        }
+    }
+
+    /// Returns the default rules for a language (as given to `Parser::new`), which only
+    /// differ by their elisions (for French and Italian).
+    pub fn for_language(lang: &str) -> WordRules {
+        let rules = WordRules::new();
+        if lang.starts_with("fr") {
+            rules.with_elisions(ELISIONS_FR)
+        } else if lang.starts_with("it") {
+            rules.with_elisions(ELISIONS_IT)
+        } else {
+            rules
+        }
+    }
+
+    /// Sets whether letters joined by an apostrophe are a single word.
+    pub fn with_apostrophes(mut self, apostrophes: bool) -> WordRules {
+        self.apostrophes = apostrophes;
+        self
+    }
+
+    /// Sets whether letters or digits joined by a hyphen are a single word.
+    pub fn with_hyphens(mut self, hyphens: bool) -> WordRules {
+        self.hyphens = hyphens;
+        self
+    }
+
+    /// Sets whether digits are part of words.
+    pub fn with_digits(mut self, digits: bool) -> WordRules {
+        self.digits = digits;
+        self
+    }
+
+    /// Sets the elisions, given as a comma or whitespace separated list of words.
+    pub fn with_elisions(mut self, list: &str) -> WordRules {
+        self.elisions = list.split(|c: char| c == ',' || c.is_whitespace())
+                            .filter(|s| !s.is_empty())
+                            .map(|s| s.to_lowercase())
+                            .collect();
+        self
+    }
+
+    /// Returns true if a character is part of a word
+    pub(crate) fn is_word_char(&self, c: char) -> bool {
+        c.is_alphabetic() || (self.digits && c.is_numeric())
+    }
+}
+
+/// Returns true if a character is an apostrophe
+pub(crate) fn is_apostrophe(c: char) -> bool {
+    c == '\'' || c == '’'
+}
+
+/// Returns true if a character is a hyphen
+pub(crate) fn is_hyphen(c: char) -> bool {
+    c == '-' || c == '‐' || c == '‑'
+}
+
 /// `Word` type: some inner representation used by `Parser`.
 ///
 /// You probably should not use this type directly.
-#[repr(C)]
 #[derive(Debug,Clone)]
 pub enum Word {
     /// A String which is not part of the text (typically whitespace, HTML formatting, ...)
@@ -28,11 +194,18 @@ pub enum Word {
     Ignored(String),
     /// Tracked string, containing the string, the stemmed variant of the
     /// string, some value corresponding to the degree of repetitions and
-    /// an option to a highlighting colour
-    Tracked(String, String, f32, Option<&'static str>),
+    /// an option to a highlight
+    Tracked(String, String, f32, Option<Highlight>),
 }
 
 impl Word {
+    /// Returns the string of the word, as it appears in the text.
+    pub fn as_str(&self) -> &str {
+        match *self {
+            Word::Untracked(ref s) | Word::Ignored(ref s) | Word::Tracked(ref s, _, _, _) => s,
+        }
+    }
+
     /// Sets the stemmed value of a word.
     pub fn set_stemmed(&mut self, s: String) {
         if let Word::Tracked(_, ref mut stemmed, _, _) = *self {
@@ -48,7 +221,6 @@ impl Word {
     }
 }
 
-#[repr(C)]
 #[derive(Debug,Clone)]
 /// The internal representation of the document.
 ///
@@ -64,6 +236,35 @@ pub struct Ast {
     pub begin_body: Option<usize>,
     /// position of </body> tag, if any
     pub end_body: Option<usize>,
+    /// Position of each word in the source text (`spans[i]` is the position of `words[i]`).
+    ///
+    /// This is set by `Parser::tokenize`; if you modify `words`, call `update_spans`.
+    pub spans: Vec<Span>,
+    /// Ranges of words (`(begin, end)`, `end` excluded, in order) that are in the value of an
+    /// HTML attribute (see `Parser::with_html_attributes`), and thus can't be wrapped in tags
+    pub attributes: Vec<(usize, usize)>,
+    /// Ranges of words (`(begin, end)`, `end` excluded, in order) of each sentence.
+    ///
+    /// The ranges cover all the words: each sentence but the first one begins with a word,
+    /// and the punctuation, whitespace and markup that follow a sentence are part of it.
+    /// This is set by `Parser::tokenize`.
+    pub sentences: Vec<(usize, usize)>,
+    /// Ranges of words of each paragraph, like `sentences` (a paragraph always begins with
+    /// a sentence).
+    pub paragraphs: Vec<(usize, usize)>,
+    /// Ranges of words of each section, like `paragraphs` (a section always begins with a
+    /// paragraph, and a new one begins at each heading).
+    pub sections: Vec<(usize, usize)>,
+    /// Proper nouns found in the document, sorted (see `Parser::with_ignore_proper`).
+    ///
+    /// This is set by `Parser::tokenize`, whether proper nouns are ignored or not.
+    pub proper_nouns: Vec<String>,
+}
+
+impl Default for Ast {
+    fn default() -> Ast {
+        Ast::new()
+    }
 }
 
 impl Ast {
@@ -74,9 +275,113 @@ impl Ast {
             begin_head: None,
             begin_body: None,
             end_body: None,
+            spans: vec![],
+            attributes: vec![],
+            sentences: vec![],
+            paragraphs: vec![],
+            sections: vec![],
+            proper_nouns: vec![],
         }
     }
 
+    /// Computes the position of each word, assuming that the concatenation of `words`
+    /// is the source text.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let parser = caribon::Parser::new("english").unwrap();
+    /// let ast = parser.tokenize("Ça va?\nOui, ça 𝄞 va").unwrap();
+    /// let span = ast.spans[ast.spans.len() - 1]; // "va"
+    /// assert_eq!((span.offset, span.byte_offset), (17, 22));
+    /// assert_eq!((span.line, span.column, span.utf16_column), (1, 10, 11));
+    /// ```
+    pub fn update_spans(&mut self) {
+        self.spans = Ast::compute_spans(&self.words);
+    }
+
+    /// Returns the position of each word: `spans` if it is up to date, or newly
+    /// computed positions else.
+    pub fn get_spans(&self) -> Cow<'_, [Span]> {
+        if self.spans.len() == self.words.len() {
+            Cow::Borrowed(&self.spans)
+        } else {
+            Cow::Owned(Ast::compute_spans(&self.words))
+        }
+    }
+
+    fn compute_spans(words: &[Word]) -> Vec<Span> {
+        let mut res = Vec::with_capacity(words.len());
+        let mut current = Span::default();
+        for word in words {
+            let s = word.as_str();
+            let mut span = current;
+            span.length = 0;
+            span.byte_length = s.len();
+            span.utf16_length = 0;
+            for c in s.chars() {
+                span.length += 1;
+                span.utf16_length += c.len_utf16();
+                if c == '\n' {
+                    current.line += 1;
+                    current.column = 0;
+                    current.utf16_column = 0;
+                } else {
+                    current.column += 1;
+                    current.utf16_column += c.len_utf16();
+                }
+            }
+            current.offset += span.length;
+            current.byte_offset += span.byte_length;
+            res.push(span);
+        }
+        res
+    }
+
+    /// Returns the index of the range that contains `i`, if any
+    fn find_range(ranges: &[(usize, usize)], i: usize) -> Option<usize> {
+        ranges.binary_search_by(|&(begin, end)| if end <= i {
+                  Ordering::Less
+              } else if begin > i {
+                  Ordering::Greater
+              } else {
+                  Ordering::Equal
+              })
+              .ok()
+    }
+
+    /// Returns true if `words[i]` is in the value of an HTML attribute.
+    pub fn is_in_attribute(&self, i: usize) -> bool {
+        Ast::find_range(&self.attributes, i).is_some()
+    }
+
+    /// Returns the index of the sentence (in `sentences`) that contains `words[i]`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let parser = caribon::Parser::new("english").unwrap();
+    /// let ast = parser.tokenize("Mr. Smith works... He works hard.\n\nA new paragraph!").unwrap();
+    /// assert_eq!(ast.sentences.len(), 3);
+    /// assert_eq!(ast.paragraphs.len(), 2);
+    /// let i = ast.words.iter().position(|w| w.as_str() == "He").unwrap();
+    /// assert_eq!(ast.sentence_of(i), Some(1));
+    /// assert_eq!(ast.paragraph_of(i), Some(0));
+    /// ```
+    pub fn sentence_of(&self, i: usize) -> Option<usize> {
+        Ast::find_range(&self.sentences, i)
+    }
+
+    /// Returns the index of the paragraph (in `paragraphs`) that contains `words[i]`.
+    pub fn paragraph_of(&self, i: usize) -> Option<usize> {
+        Ast::find_range(&self.paragraphs, i)
+    }
+
+    /// Returns the index of the section (in `sections`) that contains `words[i]`.
+    pub fn section_of(&self, i: usize) -> Option<usize> {
+        Ast::find_range(&self.sections, i)
+    }
+
     /// Sets begin_head to current last position of words
     ///
     /// This should be called *before* inserting the corresponding element.
@@ -109,6 +414,50 @@ impl Ast {
         self.end_body = Some(i);
     }
 
+    /// Returns the part of the Ast between `words[begin]` and `words[end]` (excluded), e.g.
+    /// to detect repetitions again in some paragraphs only.
+    ///
+    /// Spans are kept as they are, so they are still positions in the whole source text;
+    /// sentences, paragraphs, sections and attributes are cut at the bounds of the slice.
+    /// The whole slice is considered as a body (the markers of `<head>` and `<body>` are not
+    /// kept).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let parser = caribon::Parser::new("english").unwrap();
+    /// let ast = parser.tokenize("One paragraph.\n\nAnother one, another.").unwrap();
+    /// let (begin, end) = ast.paragraphs[1];
+    /// let mut slice = ast.slice(begin, end);
+    /// assert_eq!(slice.paragraphs, vec![(0, end - begin)]);
+    /// assert_eq!(slice.spans[0].line, 2);
+    /// parser.detect_local(&mut slice, 1.9);
+    /// assert_eq!(&parser.ast_to_markdown(&slice), "**Another** one, **another**.");
+    /// ```
+    pub fn slice(&self, begin: usize, end: usize) -> Ast {
+        let end = end.min(self.words.len());
+        let begin = begin.min(end);
+        let ranges = |ranges: &[(usize, usize)]| -> Vec<(usize, usize)> {
+            ranges.iter()
+                  .filter(|&&(b, e)| b.max(begin) < e.min(end))
+                  .map(|&(b, e)| (b.max(begin) - begin, e.min(end) - begin))
+                  .collect()
+        };
+        let spans = self.get_spans();
+        Ast {
+            words: self.words[begin..end].to_vec(),
+            begin_head: None,
+            begin_body: None,
+            end_body: None,
+            spans: spans[begin..end].to_vec(),
+            attributes: ranges(&self.attributes),
+            sentences: ranges(&self.sentences),
+            paragraphs: ranges(&self.paragraphs),
+            sections: ranges(&self.sections),
+            proper_nouns: self.proper_nouns.clone(),
+        }
+    }
+
     /// Get only the words contained between <body> and </body>
     ///
     /// If body_begin and body_end are both set (and the first one is before the second),
@@ -124,6 +473,18 @@ impl Ast {
         &self.words
     }
 
+    /// Get the index (in `words`) of the first word returned by `get_body`
+    pub fn get_body_offset(&self) -> usize {
+        if let Some(begin) = self.begin_body {
+            if let Some(end) = self.end_body {
+                if begin < end {
+                    return begin + 1;
+                }
+            }
+        }
+        0
+    }
+
     /// Get only the words contained between <body> and </body>, mutable version
     ///
     /// Same as get_body, but takes and return a mutable reference.
@@ -138,3 +499,166 @@ impl Ast {
         &mut self.words
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::{Parser, InputFormat};
+
+    /// Returns the span of each word of `s`, with the word
+    fn spans(s: &str) -> Vec<(String, Span)> {
+        let parser = Parser::new("english").unwrap();
+        let ast = parser.tokenize(s).unwrap();
+        assert_eq!(ast.spans.len(), ast.words.len());
+        ast.words.iter().map(|w| w.as_str().to_owned()).zip(ast.spans.iter().cloned()).collect()
+    }
+
+    /// Returns the span of the word `word` of `s`
+    fn span_of(s: &str, word: &str) -> Span {
+        spans(s).into_iter().find(|w| w.0 == word).unwrap().1
+    }
+
+    #[test]
+    fn spans_of_empty_input() {
+        assert!(spans("").is_empty());
+        assert!(Ast::new().get_spans().is_empty());
+    }
+
+    #[test]
+    fn spans_are_contiguous() {
+        let s = "Ça va?\r\nOui, ça 𝄞 va.\n\n\u{feff}Très\tbien";
+        let mut offset = 0;
+        let mut byte_offset = 0;
+        for (word, span) in spans(s) {
+            assert_eq!(span.offset, offset);
+            assert_eq!(span.byte_offset, byte_offset);
+            assert_eq!(span.length, word.chars().count());
+            assert_eq!(span.byte_length, word.len());
+            assert_eq!(span.utf16_length, word.encode_utf16().count());
+            assert_eq!(&s[span.byte_offset..span.byte_offset + span.byte_length], word);
+            offset += span.length;
+            byte_offset += span.byte_length;
+        }
+        assert_eq!(offset, s.chars().count());
+        assert_eq!(byte_offset, s.len());
+    }
+
+    #[test]
+    fn spans_with_crlf() {
+        let span = span_of("one\r\ntwo\r\n\r\nthree", "three");
+        assert_eq!((span.line, span.column, span.utf16_column), (3, 0, 0));
+        let span = span_of("one\r\n  two", "two");
+        assert_eq!((span.offset, span.line, span.column), (7, 1, 2));
+        // A carriage return alone doesn't end a line
+        let span = span_of("one\rtwo", "two");
+        assert_eq!((span.line, span.column), (0, 4));
+    }
+
+    #[test]
+    fn spans_in_utf16() {
+        // U+1D11E is two UTF-16 code units, "é" one, whether it is precomposed or not
+        let span = span_of("𝄞𝄞 music", "music");
+        assert_eq!((span.column, span.utf16_column, span.byte_offset), (3, 5, 9));
+        let span = span_of("e\u{301}t\u{e9} 𝄞x", "x");
+        assert_eq!((span.column, span.utf16_column), (6, 7));
+        let span = span_of("a\n𝄞 b", "b");
+        assert_eq!((span.line, span.column, span.utf16_column), (1, 2, 3));
+        let span = span_of("𝐀𝐁𝐂 d", "𝐀𝐁𝐂");
+        assert_eq!((span.length, span.utf16_length, span.byte_length), (3, 6, 12));
+    }
+
+    #[test]
+    fn outdated_spans() {
+        let parser = Parser::new("english").unwrap();
+        let mut ast = parser.tokenize("one two").unwrap();
+        ast.words.push(Word::Untracked("\nthree".to_owned()));
+        assert_eq!(ast.get_spans().len(), 4);
+        assert_eq!(ast.spans.len(), 3);
+        ast.update_spans();
+        assert_eq!(ast.spans[3].offset, 7);
+    }
+
+    /// Returns the words of `s` with their kind ('t' for tracked, 'i' for ignored)
+    fn words(parser: &Parser, s: &str) -> Vec<(char, String)> {
+        let ast = parser.tokenize(s).unwrap();
+        ast.words.iter()
+           .filter_map(|w| match *w {
+               Word::Tracked(ref s, _, _, _) => Some(('t', s.clone())),
+               Word::Ignored(ref s) => Some(('i', s.clone())),
+               Word::Untracked(_) => None,
+           })
+           .collect()
+    }
+
+    fn w(kind: char, s: &str) -> (char, String) {
+        (kind, s.to_owned())
+    }
+
+    #[test]
+    fn word_rules_for_languages() {
+        assert!(WordRules::for_language("english").elisions.is_empty());
+        assert!(WordRules::for_language("french").elisions.contains(&"qu".to_owned()));
+        assert!(WordRules::for_language("italian").elisions.contains(&"dell".to_owned()));
+        assert_eq!(WordRules::new().with_elisions(" L, d\tJ ").elisions, vec!["l", "d", "j"]);
+        assert!(WordRules::new().with_elisions("").elisions.is_empty());
+    }
+
+    #[test]
+    fn apostrophes_and_hyphens() {
+        let parser = Parser::new("english").unwrap().with_input_format(InputFormat::Text);
+        assert_eq!(words(&parser, "cat’s well-known rock'n'roll"),
+                   vec![w('t', "cat’s"), w('t', "well-known"), w('t', "rock'n'roll")]);
+        // Only between letters
+        assert_eq!(words(&parser, "'cats' -dogs- birds' fish--cows"),
+                   vec![w('t', "cats"), w('t', "dogs"), w('t', "birds"), w('t', "fish"),
+                        w('t', "cows")]);
+        assert_eq!(words(&parser, "non‐breaking non‑breaking"),
+                   vec![w('t', "non‐breaking"), w('t', "non‑breaking")]);
+
+        let parser = parser.with_word_rules(WordRules::new().with_apostrophes(false)
+                                                           .with_hyphens(false));
+        assert_eq!(words(&parser, "don't well-known"),
+                   vec![w('t', "don"), w('t', "t"), w('t', "well"), w('t', "known")]);
+    }
+
+    #[test]
+    fn elisions() {
+        let parser = Parser::new("french").unwrap().with_input_format(InputFormat::Text);
+        assert_eq!(words(&parser, "L’homme qu'elle jusqu'à aujourd'hui"),
+                   vec![w('i', "L’"), w('t', "homme"), w('i', "qu'"), w('i', "elle"),
+                        w('i', "jusqu'"), w('i', "à"), w('t', "aujourd'hui")]);
+        // An elision must be followed by a letter
+        assert_eq!(words(&parser, "l' homme"), vec![w('t', "l"), w('t', "homme")]);
+    }
+
+    #[test]
+    fn digits() {
+        let parser = Parser::new("english").unwrap().with_input_format(InputFormat::Text);
+        assert_eq!(words(&parser, "COVID-19 2nd 42 3.14 1-2"),
+                   vec![w('t', "COVID-19"), w('t', "2nd")]);
+        let parser = parser.with_word_rules(WordRules::new().with_digits(false));
+        assert_eq!(words(&parser, "COVID-19 2nd"), vec![w('t', "COVID"), w('t', "nd")]);
+    }
+
+    #[test]
+    fn slices() {
+        let parser = Parser::new("english").unwrap();
+        let ast = parser.tokenize("One. Two three.\n\nFour.").unwrap();
+        let three = ast.words.iter().position(|w| w.as_str() == "three").unwrap();
+        let four = ast.words.iter().position(|w| w.as_str() == "Four").unwrap();
+        // Cuts the second sentence and the first paragraph
+        let slice = ast.slice(three, four + 1);
+        assert_eq!(slice.words[0].as_str(), "three");
+        assert_eq!(slice.sentences, vec![(0, four - three), (four - three, four - three + 1)]);
+        assert_eq!(slice.paragraphs, slice.sentences);
+        assert_eq!(slice.spans[four - three].line, 2);
+        // Out of bounds or empty
+        assert!(ast.slice(ast.words.len(), 1000).words.is_empty());
+        assert!(ast.slice(3, 3).sentences.is_empty());
+
+        let parser = parser.with_input_format(InputFormat::Html);
+        let ast = parser.tokenize("<html><head><title>T</title></head><body><p>A</p></body></html>").unwrap();
+        let slice = ast.slice(0, ast.words.len());
+        assert_eq!(slice.get_body().len(), ast.words.len());
+    }
+}
@@ -0,0 +1,425 @@
+// Copyright (C) 2015 Élisabeth HENRY.
+//
+// This file is part of Caribon.
+//
+// Caribon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Caribon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Minimal JSON values, enough for the messages of the Language Server Protocol.
+
+use std::fmt;
+
+/// A JSON value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Builds an object from a list of fields
+    pub fn object(fields: Vec<(&str, Value)>) -> Value {
+        Value::Object(fields.into_iter().map(|(k, v)| (k.to_owned(), v)).collect())
+    }
+
+    /// Returns the field `key` of an object, or `Null`
+    pub fn get(&self, key: &str) -> &Value {
+        if let Value::Object(ref fields) = *self {
+            for (k, v) in fields {
+                if k == key {
+                    return v;
+                }
+            }
+        }
+        &Value::Null
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Value::String(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Number(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        match *self {
+            Value::Number(x) if x >= 0.0 => Some(x as usize),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match *self {
+            Value::Array(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        *self == Value::Null
+    }
+
+    /// Parses a JSON document
+    pub fn parse(s: &str) -> Result<Value, String> {
+        let chars: Vec<char> = s.chars().collect();
+        let mut parser = JsonParser { chars: &chars, pos: 0, depth: 0 };
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.pos < chars.len() {
+            return Err(format!("Unexpected trailing characters at {}", parser.pos));
+        }
+        Ok(value)
+    }
+}
+
+impl<'a> From<&'a str> for Value {
+    fn from(s: &'a str) -> Value {
+        Value::String(s.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::String(s)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(x: usize) -> Value {
+        Value::Number(x as f64)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Null => f.write_str("null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(x) => {
+                if x.is_finite() {
+                    write!(f, "{}", x)
+                } else {
+                    f.write_str("null")
+                }
+            }
+            Value::String(ref s) => write!(f, "\"{}\"", escape_json(s)),
+            Value::Array(ref v) => {
+                f.write_str("[")?;
+                for (i, x) in v.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", x)?;
+                }
+                f.write_str("]")
+            }
+            Value::Object(ref fields) => {
+                f.write_str("{")?;
+                for (i, (k, v)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "\"{}\":{}", escape_json(k), v)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+/// Escapes a string so it can be put between quotes
+fn escape_json(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            c if (c as u32) < 0x20 => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Maximal number of nested arrays and objects, so a malicious document can't overflow
+/// the stack
+const MAX_DEPTH: usize = 128;
+
+struct JsonParser<'a> {
+    chars: &'a [char],
+    pos: usize,
+    /// Number of arrays and objects containing the current value
+    depth: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Result<char, String> {
+        match self.peek() {
+            Some(c) => {
+                self.pos += 1;
+                Ok(c)
+            }
+            None => Err("Unexpected end of JSON document".to_owned()),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, s: &str) -> Result<(), String> {
+        for c in s.chars() {
+            if self.next()? != c {
+                return Err(format!("Expected '{}' at {}", s, self.pos));
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('[') | Some('{') if self.depth >= MAX_DEPTH => {
+                Err(format!("Too many nested arrays or objects at {}", self.pos))
+            }
+            Some('[') | Some('{') => {
+                self.depth += 1;
+                let res = self.parse_container();
+                self.depth -= 1;
+                res
+            }
+            _ => self.parse_scalar(),
+        }
+    }
+
+    /// Parses an array or an object
+    fn parse_container(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('[') => {
+                self.pos += 1;
+                let mut v = vec![];
+                self.skip_whitespace();
+                if self.peek() == Some(']') {
+                    self.pos += 1;
+                    return Ok(Value::Array(v));
+                }
+                loop {
+                    v.push(self.parse_value()?);
+                    self.skip_whitespace();
+                    match self.next()? {
+                        ',' => continue,
+                        ']' => return Ok(Value::Array(v)),
+                        c => return Err(format!("Unexpected '{}' in array", c)),
+                    }
+                }
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut fields = vec![];
+                self.skip_whitespace();
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    return Ok(Value::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.parse_string()?;
+                    self.skip_whitespace();
+                    self.expect(":")?;
+                    let value = self.parse_value()?;
+                    fields.push((key, value));
+                    self.skip_whitespace();
+                    match self.next()? {
+                        ',' => continue,
+                        '}' => return Ok(Value::Object(fields)),
+                        c => return Err(format!("Unexpected '{}' in object", c)),
+                    }
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Parses a value that is not an array or an object
+    fn parse_scalar(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('n') => self.expect("null").map(|_| Value::Null),
+            Some('t') => self.expect("true").map(|_| Value::Bool(true)),
+            Some('f') => self.expect("false").map(|_| Value::Bool(false)),
+            Some('"') => self.parse_string().map(Value::String),
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                let begin = self.pos;
+                while let Some(c) = self.peek() {
+                    if c.is_ascii_digit() || "+-.eE".contains(c) {
+                        self.pos += 1;
+                    } else {
+                        break;
+                    }
+                }
+                let s: String = self.chars[begin..self.pos].iter().collect();
+                s.parse().map(Value::Number).map_err(|_| format!("Invalid number: {}", s))
+            }
+            Some(c) => Err(format!("Unexpected '{}' at {}", c, self.pos)),
+            None => Err("Unexpected end of JSON document".to_owned()),
+        }
+    }
+
+    fn parse_hex(&mut self) -> Result<u32, String> {
+        let mut x = 0;
+        for _ in 0..4 {
+            let c = self.next()?;
+            x = x * 16 + c.to_digit(16).ok_or_else(|| format!("Invalid escape digit: {}", c))?;
+        }
+        Ok(x)
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut res = String::new();
+        loop {
+            match self.next()? {
+                '"' => return Ok(res),
+                '\\' => {
+                    match self.next()? {
+                        'n' => res.push('\n'),
+                        'r' => res.push('\r'),
+                        't' => res.push('\t'),
+                        'b' => res.push('\u{8}'),
+                        'f' => res.push('\u{c}'),
+                        'u' => {
+                            let mut x = self.parse_hex()?;
+                            if (0xD800..0xDC00).contains(&x) {
+                                // Surrogate pair
+                                self.expect("\\u")?;
+                                let low = self.parse_hex()?;
+                                if !(0xDC00..0xE000).contains(&low) {
+                                    return Err(format!("Invalid low surrogate: \\u{:04x}", low));
+                                }
+                                x = 0x10000 + ((x - 0xD800) << 10) + (low - 0xDC00);
+                            }
+                            res.push(::std::char::from_u32(x).unwrap_or('\u{FFFD}'));
+                        }
+                        c => res.push(c),
+                    }
+                }
+                c => res.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Value;
+
+    #[test]
+    fn parse_values() {
+        assert_eq!(Value::parse(" null "), Ok(Value::Null));
+        assert_eq!(Value::parse("true"), Ok(Value::Bool(true)));
+        assert_eq!(Value::parse("[]"), Ok(Value::Array(vec![])));
+        assert_eq!(Value::parse("{}"), Ok(Value::Object(vec![])));
+        let v = Value::parse(r#"{"a": [1, "b", false], "c": {"d": null}}"#).unwrap();
+        assert_eq!(v.get("a").as_array().unwrap().len(), 3);
+        assert!(v.get("c").get("d").is_null());
+        assert!(v.get("missing").is_null());
+    }
+
+    #[test]
+    fn parse_numbers() {
+        assert_eq!(Value::parse("0").unwrap().as_f64(), Some(0.0));
+        assert_eq!(Value::parse("-12").unwrap().as_f64(), Some(-12.0));
+        assert_eq!(Value::parse("1.5").unwrap().as_f64(), Some(1.5));
+        assert_eq!(Value::parse("2e3").unwrap().as_f64(), Some(2000.0));
+        assert_eq!(Value::parse("2.5E-1").unwrap().as_f64(), Some(0.25));
+        assert_eq!(Value::parse("42").unwrap().as_usize(), Some(42));
+        assert_eq!(Value::parse("-1").unwrap().as_usize(), None);
+    }
+
+    #[test]
+    fn parse_escapes() {
+        let v = Value::parse(r#""a\"b\\c\/d\n\r\t\b\f""#).unwrap();
+        assert_eq!(v.as_str(), Some("a\"b\\c/d\n\r\t\u{8}\u{c}"));
+        assert_eq!(Value::parse(r#""\u00e9\u20AC""#).unwrap().as_str(), Some("é€"));
+    }
+
+    #[test]
+    fn parse_surrogate_pairs() {
+        assert_eq!(Value::parse(r#""\ud834\udd1e""#).unwrap().as_str(), Some("𝄞"));
+        // A lone low surrogate is not a character
+        assert_eq!(Value::parse(r#""\udd1e""#).unwrap().as_str(), Some("\u{FFFD}"));
+        // A high surrogate must be followed by a low one
+        assert!(Value::parse(r#""\ud834x""#).is_err());
+        assert!(Value::parse(r#""\ud834\u0041""#).is_err());
+        assert!(Value::parse(r#""\ud834\ud834""#).is_err());
+        // Characters outside the BMP can also be given as is
+        assert_eq!(Value::parse("\"𝄞\"").unwrap().as_str(), Some("𝄞"));
+    }
+
+    #[test]
+    fn parse_malformed() {
+        for s in &["", "nul", "[1, 2", "[1 2]", "{\"a\" 1}", "{\"a\": 1,}", "{a: 1}", "\"abc",
+                   "\"\\u12\"", "\"\\uzzzz\"", "1.2.3", "-", "+1", "[1] x", "'a'"] {
+            assert!(Value::parse(s).is_err(), "{:?} should not be parsed", s);
+        }
+    }
+
+    #[test]
+    fn parse_nested() {
+        let nested = |n: usize| format!("{}{}", "[".repeat(n), "]".repeat(n));
+        assert!(Value::parse(&nested(128)).is_ok());
+        assert!(Value::parse(&nested(129)).is_err());
+        assert!(Value::parse(&"[".repeat(300_000)).is_err());
+        assert!(Value::parse(&"{\"a\":".repeat(300_000)).is_err());
+    }
+
+    #[test]
+    fn display() {
+        let v = Value::object(vec![("s", "a\"b\\\n\u{1}".into()),
+                                   ("n", 3.into()),
+                                   ("x", Value::Number(0.5)),
+                                   ("inf", Value::Number(f64::INFINITY)),
+                                   ("a", Value::Array(vec![true.into(), Value::Null]))]);
+        let s = v.to_string();
+        assert_eq!(s, r#"{"s":"a\"b\\\n\u0001","n":3,"x":0.5,"inf":null,"a":[true,null]}"#);
+        assert_eq!(Value::parse(&s).unwrap().get("s").as_str(), Some("a\"b\\\n\u{1}"));
+    }
+}
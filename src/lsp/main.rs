@@ -0,0 +1,1004 @@
+// Copyright (C) 2015 Élisabeth HENRY.
+//
+// This file is part of Caribon.
+//
+// Caribon is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published
+// by the Free Software Foundation, either version 2.1 of the License, or
+// (at your option) any later version.
+//
+// Caribon is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Caribon.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Language server for Caribon.
+//!
+//! Speaks the Language Server Protocol on stdin/stdout and publishes the repetitions of
+//! opened documents as diagnostics.
+
+extern crate caribon;
+mod json;
+
+use caribon::{Ast, Word, Parser, PROJECT_IGNORED_FILE, InputFormat, Pipeline, LocalDetector, GlobalDetector, PhraseDetector};
+use json::Value;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Command used by code actions to add a word to the ignored list
+const IGNORE_COMMAND: &str = "caribon.ignoreWord";
+
+/// Diagnostic severity "Information"
+const SEVERITY_INFORMATION: usize = 3;
+
+/// Time without messages from the client after which modified documents are analysed
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Options that can be set by the client, in `initializationOptions` or
+/// in the `caribon` section of the configuration.
+struct Settings {
+    language: String,
+    threshold: f32,
+    max_distance: u32,
+    fuzzy: Option<f32>,
+    global_threshold: Option<f32>,
+    phrase_threshold: Option<f32>,
+    ignore_proper: bool,
+    /// Words added to the default ignored list of the language
+    ignored: Vec<String>,
+    /// Words added by the `caribon.ignoreWord` command, which the configuration doesn't
+    /// replace
+    added_ignored: Vec<String>,
+    /// Whether the words of the `.caribon-ignore` file of a document's project are ignored
+    project_ignored: bool,
+}
+
+impl Settings {
+    fn new() -> Settings {
+        Settings {
+            language: "french".to_owned(),
+            threshold: 1.9,
+            max_distance: 50,
+            fuzzy: Some(0.2),
+            global_threshold: None,
+            phrase_threshold: None,
+            ignore_proper: false,
+            ignored: vec![],
+            added_ignored: vec![],
+            project_ignored: true,
+        }
+    }
+
+    /// Updates the settings with the fields present in `v`
+    fn update(&mut self, v: &Value) {
+        if let Some(s) = v.get("language").as_str() {
+            self.language = s.to_owned();
+        }
+        if let Some(x) = v.get("threshold").as_f64() {
+            self.threshold = x as f32;
+        }
+        if let Some(x) = v.get("maxDistance").as_usize() {
+            self.max_distance = x as u32;
+        }
+        if let Value::Bool(b) = *v.get("ignoreProper") {
+            self.ignore_proper = b;
+        }
+        if let Value::Bool(b) = *v.get("projectIgnored") {
+            self.project_ignored = b;
+        }
+        // For these ones, an explicit null deactivates the option
+        let optional = |key: &str, old: Option<f32>| if has_key(v, key) {
+            v.get(key).as_f64().map(|x| x as f32)
+        } else {
+            old
+        };
+        self.fuzzy = optional("fuzzy", self.fuzzy);
+        self.global_threshold = optional("globalThreshold", self.global_threshold);
+        self.phrase_threshold = optional("phraseThreshold", self.phrase_threshold);
+        if let Some(words) = v.get("ignored").as_array() {
+            self.ignored = words.iter()
+                                .filter_map(|w| w.as_str())
+                                .map(|w| w.to_owned())
+                                .collect();
+        }
+    }
+
+    /// Creates a parser for a document of the given language id, and path if it is a file
+    fn parser(&self, language_id: &str, path: Option<&Path>) -> caribon::Result<Parser> {
+        let mut parser = Parser::new(&self.language)?
+            .with_input_format(match language_id {
+                "html" => InputFormat::Html,
+                "markdown" => InputFormat::Markdown,
+                "latex" => InputFormat::Latex,
+                _ => InputFormat::Text,
+            })
+            .with_fuzzy(self.fuzzy)
+            .with_ignore_proper(self.ignore_proper)
+            .with_max_distance(self.max_distance);
+        if let Some(file) = path.filter(|_| self.project_ignored).and_then(Parser::find_project_ignored) {
+            parser = parser.with_more_ignored_file(file)?;
+        }
+        if !self.ignored.is_empty() {
+            parser = parser.with_more_ignored(&self.ignored.join(" "));
+        }
+        if !self.added_ignored.is_empty() {
+            parser = parser.with_more_ignored(&self.added_ignored.join(" "));
+        }
+        Ok(parser)
+    }
+}
+
+fn has_key(v: &Value, key: &str) -> bool {
+    match *v {
+        Value::Object(ref fields) => fields.iter().any(|(k, _)| k == key),
+        _ => false,
+    }
+}
+
+/// Returns the path of a `file://` URI
+fn uri_path(uri: &str) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("file://")?;
+    // Skips the host, which is usually empty
+    let bytes = &rest.as_bytes()[rest.find('/')?..];
+    let mut path = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes.get(i + 1..i + 3)
+                           .and_then(|hex| ::std::str::from_utf8(hex).ok())
+                           .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) if bytes[i] == b'%' => {
+                path.push(byte);
+                i += 3;
+            }
+            _ => {
+                path.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    let path = String::from_utf8(path).ok()?;
+    // "/C:/dir" on Windows
+    if cfg!(windows) && path.get(2..3) == Some(":") {
+        return Some(PathBuf::from(&path[1..]));
+    }
+    Some(PathBuf::from(path))
+}
+
+/// Adds a word at the end of a file of ignored words, creating it if needed
+fn append_ignored(file: &Path, word: &str) -> io::Result<()> {
+    let content = match fs::read_to_string(file) {
+        Ok(content) => content,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    if content.lines().any(|line| line.trim().to_lowercase() == word.to_lowercase()) {
+        return Ok(());
+    }
+    let mut out = OpenOptions::new().create(true).append(true).open(file)?;
+    if !content.is_empty() && !content.ends_with('\n') {
+        writeln!(out)?;
+    }
+    writeln!(out, "{}", word)
+}
+
+/// A highlighted word, with its position in LSP coordinates
+struct Occurrence {
+    line: usize,
+    /// Start column, in UTF-16 code units
+    begin: usize,
+    /// End column, in UTF-16 code units
+    end: usize,
+    word: String,
+    value: f32,
+    detector: &'static str,
+    /// Occurrences of the same detector with the same group are the same repetition
+    group: usize,
+}
+
+/// Returns the highlighted words of an `Ast`, with their positions
+fn occurrences(ast: &Ast) -> Vec<Occurrence> {
+    let spans = ast.get_spans();
+    ast.words
+       .iter()
+       .zip(spans.iter())
+       .filter_map(|(word, span)| match *word {
+           Word::Tracked(ref s, _, value, Some(highlight)) => Some(Occurrence {
+               line: span.line,
+               begin: span.utf16_column,
+               end: span.utf16_column + span.utf16_length,
+               word: s.clone(),
+               value,
+               detector: highlight.detector,
+               group: highlight.group,
+           }),
+           _ => None,
+       })
+       .collect()
+}
+
+/// Returns the index of the word `n` words (not counting whitespace and markup) before
+/// `words[i]`, or 0
+fn words_before(ast: &Ast, mut i: usize, n: usize) -> usize {
+    let mut count = 0;
+    while i > 0 && count < n {
+        i -= 1;
+        match ast.words[i] {
+            Word::Untracked(_) => (),
+            _ => count += 1,
+        }
+    }
+    i
+}
+
+/// Returns the index following the word `n` words (not counting whitespace and markup)
+/// after `words[i - 1]`, or the number of words
+fn words_after(ast: &Ast, mut i: usize, n: usize) -> usize {
+    let mut count = 0;
+    while i < ast.words.len() && count < n {
+        match ast.words[i] {
+            Word::Untracked(_) => (),
+            _ => count += 1,
+        }
+        i += 1;
+    }
+    i
+}
+
+/// An opened document
+struct Document {
+    text: String,
+    language_id: String,
+    /// Highlighted words found by the previous analyses, sorted by position
+    occurrences: Vec<Occurrence>,
+    /// Whether `occurrences` are up to date, except on the lines in `changed`
+    valid: bool,
+    /// Lines (the first and the last ones) modified since the last analysis, if any
+    changed: Option<(usize, usize)>,
+    /// Last group given to occurrences
+    last_group: usize,
+}
+
+impl Document {
+    fn new(text: &str, language_id: &str) -> Document {
+        Document {
+            text: text.to_owned(),
+            language_id: language_id.to_owned(),
+            occurrences: vec![],
+            valid: false,
+            changed: Some((0, usize::MAX)),
+            last_group: 0,
+        }
+    }
+
+    /// Marks the whole document as modified, e.g. when the settings change
+    fn invalidate(&mut self) {
+        self.valid = false;
+        self.changed = Some((0, usize::MAX));
+    }
+
+    /// Applies a change of `didChange` to the text, and marks the lines it modifies
+    fn apply(&mut self, change: &Value) {
+        let text = change.get("text").as_str().unwrap_or("");
+        let range = change.get("range");
+        if range.is_null() {
+            self.text = text.to_owned();
+            self.invalidate();
+            return;
+        }
+        let begin = offset_at(&self.text, range.get("start"));
+        let end = offset_at(&self.text, range.get("end"));
+        if begin > end {
+            return;
+        }
+        self.text.replace_range(begin..end, text);
+
+        // Lines `first` to `last` are replaced by lines `first` to `new_last`, and the next
+        // ones move accordingly
+        let first = range.get("start").get("line").as_usize().unwrap_or(0);
+        let last = range.get("end").get("line").as_usize().unwrap_or(0).max(first);
+        let new_last = first + text.matches('\n').count();
+        let moved = |line: usize| if line > last {
+            (line - last).saturating_add(new_last)
+        } else if line >= first {
+            new_last
+        } else {
+            line
+        };
+        self.occurrences.retain(|o| o.line < first || o.line > last);
+        for occurrence in &mut self.occurrences {
+            occurrence.line = moved(occurrence.line);
+        }
+        self.changed = Some(match self.changed {
+            Some((a, b)) => (a.min(first), moved(b).max(new_last)),
+            None => (first, new_last),
+        });
+    }
+
+    /// Detects the repetitions again around the lines modified since the last analysis.
+    ///
+    /// The paragraphs containing these lines, and the ones less than `max_distance` words
+    /// away, are analysed again (with `max_distance` words before and after them, so
+    /// repetitions across their bounds are found); elsewhere, the occurrences found before
+    /// are kept. The whole document is analysed if it is new or if global repetitions are
+    /// detected, since they depend on all of it.
+    fn analyse(&mut self, parser: &Parser, settings: &Settings) {
+        let (first, last) = match self.changed.take() {
+            Some(lines) => lines,
+            None => return,
+        };
+        let ast = match parser.tokenize(&self.text) {
+            Ok(ast) => ast,
+            // Document is probably being edited, so just don't report anything
+            Err(_) => {
+                self.occurrences.clear();
+                self.valid = false;
+                return;
+            }
+        };
+        let len = ast.words.len();
+        let spans = ast.get_spans();
+        let max_distance = settings.max_distance as usize;
+        let paragraph_begin = |i: usize| ast.paragraph_of(i).map_or(i, |p| ast.paragraphs[p].0);
+        let paragraph_end = |i: usize| if i == 0 {
+            0
+        } else {
+            ast.paragraph_of(i - 1).map_or(i, |p| ast.paragraphs[p].1)
+        };
+
+        // Words whose repetitions may have changed
+        let (begin, end) = if !self.valid || settings.global_threshold.is_some() {
+            (0, len)
+        } else {
+            let modified = |i: &usize| (first..=last).contains(&spans[*i].line);
+            let (i, j) = match ((0..len).find(modified), (0..len).rev().find(modified)) {
+                (Some(i), Some(j)) => (i, j),
+                // The modified lines were removed, so look around the next word
+                _ => {
+                    let i = spans.iter().position(|span| span.line > last).unwrap_or(len);
+                    (i.min(len.saturating_sub(1)), i.min(len.saturating_sub(1)))
+                }
+            };
+            (paragraph_begin(words_before(&ast, i, max_distance)),
+             paragraph_end(words_after(&ast, j + 1, max_distance)))
+        };
+        let context = (words_before(&ast, begin, max_distance), words_after(&ast, end, max_distance));
+        let start = spans.get(begin).map_or((0, 0), |span| (span.line, span.utf16_column));
+        let stop = spans.get(end).map_or((usize::MAX, 0), |span| (span.line, span.utf16_column));
+        let inside = |o: &Occurrence| (o.line, o.begin) >= start && (o.line, o.begin) < stop;
+
+        let mut slice = ast.slice(context.0, context.1);
+        let mut pipeline = Pipeline::new();
+        if let Some(threshold) = settings.phrase_threshold {
+            pipeline.push(Box::new(PhraseDetector::new(parser, threshold)));
+        }
+        pipeline.push(Box::new(LocalDetector::new(parser, settings.threshold)));
+        if let Some(threshold) = settings.global_threshold {
+            pipeline.push(Box::new(GlobalDetector::new(parser, threshold)));
+        }
+        pipeline.run(&mut slice);
+        let found = occurrences(&slice);
+
+        // Occurrences around the analysed words that were already known keep their group,
+        // so the new ones are related to them
+        let mut groups: HashMap<(&str, usize), usize> = HashMap::new();
+        for occurrence in found.iter().filter(|o| !inside(o)) {
+            let known = self.occurrences.iter().find(|o| {
+                (o.line, o.begin, o.detector) == (occurrence.line, occurrence.begin, occurrence.detector)
+            });
+            if let Some(known) = known {
+                groups.insert((occurrence.detector, occurrence.group), known.group);
+            }
+        }
+        self.occurrences.retain(|o| !inside(o));
+        for mut occurrence in found.into_iter().filter(|o| inside(o)) {
+            let key = (occurrence.detector, occurrence.group);
+            occurrence.group = match groups.get(&key) {
+                Some(&group) => group,
+                None => {
+                    self.last_group += 1;
+                    groups.insert(key, self.last_group);
+                    self.last_group
+                }
+            };
+            self.occurrences.push(occurrence);
+        }
+        self.occurrences.sort_by_key(|o| (o.line, o.begin));
+        self.valid = true;
+    }
+
+    /// Returns the diagnostics of the occurrences found by the last analysis
+    fn diagnostics(&self, uri: &str) -> Vec<Value> {
+        let mut groups: HashMap<(&str, usize), Vec<&Occurrence>> = HashMap::new();
+        for occurrence in &self.occurrences {
+            groups.entry((occurrence.detector, occurrence.group))
+                  .or_default()
+                  .push(occurrence);
+        }
+
+        let mut res = vec![];
+        for occurrence in &self.occurrences {
+            let related = groups[&(occurrence.detector, occurrence.group)]
+                .iter()
+                .filter(|other| other.line != occurrence.line || other.begin != occurrence.begin)
+                .map(|other| {
+                    let location = Value::object(vec![("uri", uri.into()),
+                                                      ("range", range(other.line, other.begin, other.end))]);
+                    Value::object(vec![("location", location),
+                                       ("message", format!("Other occurrence: \"{}\"", other.word).into())])
+                })
+                .collect();
+            res.push(Value::object(vec![
+                ("range", range(occurrence.line, occurrence.begin, occurrence.end)),
+                ("severity", SEVERITY_INFORMATION.into()),
+                ("source", "caribon".into()),
+                ("code", occurrence.detector.into()),
+                ("message", format!("Repetition of \"{}\" ({} repetition, value {})",
+                                    occurrence.word,
+                                    occurrence.detector,
+                                    occurrence.value).into()),
+                ("relatedInformation", Value::Array(related)),
+                ("data", Value::object(vec![("word", occurrence.word.clone().into())])),
+            ]));
+        }
+        res
+    }
+}
+
+/// Converts a LSP position to a byte offset in `text`
+fn offset_at(text: &str, position: &Value) -> usize {
+    let line = position.get("line").as_usize().unwrap_or(0);
+    let character = position.get("character").as_usize().unwrap_or(0);
+    let mut current_line = 0;
+    let mut column = 0;
+    for (i, c) in text.char_indices() {
+        if current_line == line {
+            if column >= character || c == '\n' {
+                return i;
+            }
+            column += c.len_utf16();
+        } else if c == '\n' {
+            current_line += 1;
+        }
+    }
+    text.len()
+}
+
+fn range(line: usize, begin: usize, end: usize) -> Value {
+    let position = |character: usize| {
+        Value::object(vec![("line", line.into()), ("character", character.into())])
+    };
+    Value::object(vec![("start", position(begin)), ("end", position(end))])
+}
+
+struct Server<W: Write> {
+    settings: Settings,
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+    /// Where messages to the client are written
+    output: RefCell<W>,
+}
+
+impl<W: Write> Server<W> {
+    fn new(output: W) -> Server<W> {
+        Server {
+            settings: Settings::new(),
+            documents: HashMap::new(),
+            shutdown: false,
+            output: RefCell::new(output),
+        }
+    }
+
+    /// Sends a message to the client
+    fn send(&self, message: Value) -> io::Result<()> {
+        let content = message.to_string();
+        let mut out = self.output.borrow_mut();
+        write!(out, "Content-Length: {}\r\n\r\n{}", content.len(), content)?;
+        out.flush()
+    }
+
+    fn respond(&self, id: &Value, result: Value) -> io::Result<()> {
+        self.send(Value::object(vec![("jsonrpc", "2.0".into()), ("id", id.clone()), ("result", result)]))
+    }
+
+    fn notify(&self, method: &str, params: Value) -> io::Result<()> {
+        self.send(Value::object(vec![("jsonrpc", "2.0".into()), ("method", method.into()), ("params", params)]))
+    }
+
+    fn show_message(&self, message: &str) -> io::Result<()> {
+        // 1 is "Error"
+        self.notify("window/showMessage",
+                    Value::object(vec![("type", 1.into()), ("message", message.into())]))
+    }
+
+    /// Analyses the modified lines of a document, if any
+    fn analyse(&mut self, uri: &str) -> io::Result<()> {
+        let error = match self.documents.get_mut(uri) {
+            Some(document) if document.changed.is_some() => match self.settings.parser(&document.language_id,
+                                                                                      uri_path(uri).as_deref()) {
+                Ok(parser) => {
+                    document.analyse(&parser, &self.settings);
+                    None
+                }
+                Err(e) => {
+                    document.changed = None;
+                    document.occurrences.clear();
+                    Some(e.to_string())
+                }
+            },
+            _ => None,
+        };
+        match error {
+            Some(e) => self.show_message(&e),
+            None => Ok(()),
+        }
+    }
+
+    /// Publishes the repetitions of a document as diagnostics
+    fn publish(&self, uri: &str) -> io::Result<()> {
+        let diagnostics = match self.documents.get(uri) {
+            None => vec![],
+            Some(document) => document.diagnostics(uri),
+        };
+        self.notify("textDocument/publishDiagnostics",
+                    Value::object(vec![("uri", uri.into()), ("diagnostics", Value::Array(diagnostics))]))
+    }
+
+    /// Analyses the documents modified since their last analysis, and publishes their
+    /// diagnostics
+    fn publish_changed(&mut self) -> io::Result<()> {
+        let uris: Vec<String> = self.documents
+                                    .iter()
+                                    .filter(|(_, document)| document.changed.is_some())
+                                    .map(|(uri, _)| uri.clone())
+                                    .collect();
+        for uri in uris {
+            self.analyse(&uri)?;
+            self.publish(&uri)?;
+        }
+        Ok(())
+    }
+
+    /// Analyses all the documents again, e.g. after a change of settings
+    fn publish_all(&mut self) -> io::Result<()> {
+        for document in self.documents.values_mut() {
+            document.invalidate();
+        }
+        self.publish_changed()
+    }
+
+    /// Adds a word to the ignored ones, and analyses the documents again.
+    ///
+    /// If `uri` is a file, the word is also written in the `.caribon-ignore` file of its
+    /// project (which is created next to it if there is none), so it is still ignored when
+    /// the server is restarted.
+    fn ignore_word(&mut self, word: &str, uri: Option<&str>) -> io::Result<()> {
+        self.settings.added_ignored.push(word.to_owned());
+        let path = uri.and_then(uri_path).filter(|_| self.settings.project_ignored);
+        if let Some(path) = path {
+            let file = Parser::find_project_ignored(&path)
+                .or_else(|| path.parent().map(|dir| dir.join(PROJECT_IGNORED_FILE)));
+            if let Some(file) = file {
+                if let Err(e) = append_ignored(&file, word) {
+                    self.show_message(&format!("Could not add \"{}\" to {}: {}", word, file.display(), e))?;
+                }
+            }
+        }
+        self.publish_all()
+    }
+
+    /// Returns the code actions for the given diagnostics
+    fn code_actions(&self, params: &Value) -> Value {
+        let mut words: Vec<String> = vec![];
+        let mut res = vec![];
+        let uri = params.get("textDocument").get("uri").clone();
+        let diagnostics = params.get("context").get("diagnostics").as_array().unwrap_or(&[]);
+        for diagnostic in diagnostics {
+            if diagnostic.get("source").as_str() != Some("caribon") {
+                continue;
+            }
+            let word = match diagnostic.get("data").get("word").as_str() {
+                Some(word) => word.to_lowercase(),
+                None => continue,
+            };
+            if words.contains(&word) {
+                continue;
+            }
+            let title = format!("Add \"{}\" to ignored words", word);
+            let command = Value::object(vec![("title", title.clone().into()),
+                                             ("command", IGNORE_COMMAND.into()),
+                                             ("arguments", Value::Array(vec![word.clone().into(), uri.clone()]))]);
+            res.push(Value::object(vec![("title", title.into()),
+                                        ("kind", "quickfix".into()),
+                                        ("diagnostics", Value::Array(vec![diagnostic.clone()])),
+                                        ("command", command)]));
+            words.push(word);
+        }
+        Value::Array(res)
+    }
+
+    /// Handles a message, returns `false` if the server must stop
+    fn handle(&mut self, message: &Value) -> io::Result<bool> {
+        let id = message.get("id");
+        let params = message.get("params");
+        let method = match message.get("method").as_str() {
+            Some(method) => method,
+            // Response to a request we didn't send
+            None => return Ok(true),
+        };
+
+        match method {
+            "initialize" => {
+                self.settings.update(params.get("initializationOptions"));
+                let capabilities = Value::object(vec![
+                    ("textDocumentSync", Value::object(vec![("openClose", true.into()),
+                                                            // Incremental text synchronisation
+                                                            ("change", 2.into())])),
+                    ("codeActionProvider", true.into()),
+                    ("executeCommandProvider",
+                     Value::object(vec![("commands", Value::Array(vec![IGNORE_COMMAND.into()]))])),
+                ]);
+                let info = Value::object(vec![("name", "caribon-lsp".into()),
+                                              ("version", env!("CARGO_PKG_VERSION").into())]);
+                self.respond(id, Value::object(vec![("capabilities", capabilities),
+                                                    ("serverInfo", info)]))?;
+            }
+            "initialized" => {}
+            "shutdown" => {
+                self.shutdown = true;
+                self.respond(id, Value::Null)?;
+            }
+            "exit" => return Ok(false),
+            "workspace/didChangeConfiguration" => {
+                self.settings.update(params.get("settings").get("caribon"));
+                self.publish_all()?;
+            }
+            "textDocument/didOpen" => {
+                let document = params.get("textDocument");
+                if let Some(uri) = document.get("uri").as_str() {
+                    self.documents.insert(uri.to_owned(),
+                                          Document::new(document.get("text").as_str().unwrap_or(""),
+                                                        document.get("languageId").as_str().unwrap_or("plaintext")));
+                    self.analyse(uri)?;
+                    self.publish(uri)?;
+                }
+            }
+            "textDocument/didChange" => {
+                // The document is analysed again once the client stops sending changes (see
+                // `publish_changed`)
+                let uri = params.get("textDocument").get("uri").as_str().unwrap_or("");
+                if let Some(document) = self.documents.get_mut(uri) {
+                    for change in params.get("contentChanges").as_array().unwrap_or(&[]) {
+                        document.apply(change);
+                    }
+                }
+            }
+            "textDocument/didClose" => {
+                if let Some(uri) = params.get("textDocument").get("uri").as_str() {
+                    self.documents.remove(uri);
+                    self.publish(uri)?;
+                }
+            }
+            "textDocument/codeAction" => {
+                let actions = self.code_actions(params);
+                self.respond(id, actions)?;
+            }
+            "workspace/executeCommand" => {
+                if params.get("command").as_str() == Some(IGNORE_COMMAND) {
+                    // Arguments are the word and the URI of the document
+                    let arguments = params.get("arguments").as_array().unwrap_or(&[]);
+                    if let Some(word) = arguments.first().and_then(|w| w.as_str()) {
+                        self.ignore_word(word, arguments.get(1).and_then(|uri| uri.as_str()))?;
+                    }
+                }
+                self.respond(id, Value::Null)?;
+            }
+            _ => {
+                if !id.is_null() {
+                    // Method not found
+                    let error = Value::object(vec![("code", Value::Number(-32601.0)),
+                                                   ("message", format!("Unknown method: {}", method).into())]);
+                    self.send(Value::object(vec![("jsonrpc", "2.0".into()), ("id", id.clone()), ("error", error)]))?;
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Reads a message from the client, or returns `None` at the end of input
+fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            if length.is_some() {
+                break;
+            } else {
+                continue;
+            }
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let mut content = vec![0; length.unwrap_or(0)];
+    input.read_exact(&mut content)?;
+    Ok(Some(String::from_utf8_lossy(&content).into_owned()))
+}
+
+fn main() {
+    // Messages are read by another thread, so documents can be analysed when none comes
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        let mut input = stdin.lock();
+        loop {
+            match read_message(&mut input) {
+                Ok(Some(content)) => {
+                    if sender.send(content).is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    eprintln!("Error reading message: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+
+    let mut server = Server::new(io::stdout());
+    loop {
+        let content = match receiver.recv_timeout(DEBOUNCE) {
+            Ok(content) => content,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                if let Err(e) = server.publish_changed() {
+                    eprintln!("Error writing message: {}", e);
+                    break;
+                }
+                continue;
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+        let message = match Value::parse(&content) {
+            Ok(message) => message,
+            Err(e) => {
+                eprintln!("Error parsing message: {}", e);
+                continue;
+            }
+        };
+        match server.handle(&message) {
+            Ok(true) => {}
+            Ok(false) => exit(if server.shutdown { 0 } else { 1 }),
+            Err(e) => {
+                eprintln!("Error writing message: {}", e);
+                break;
+            }
+        }
+    }
+    exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{offset_at, read_message, uri_path, Server};
+    use json::Value;
+
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn position(line: usize, character: usize) -> Value {
+        Value::object(vec![("line", line.into()), ("character", character.into())])
+    }
+
+    #[test]
+    fn offsets_in_utf16() {
+        let text = "a𝄞b\néc";
+        // 𝄞 is two UTF-16 code units, but four bytes
+        assert_eq!(offset_at(text, &position(0, 1)), 1);
+        assert_eq!(offset_at(text, &position(0, 3)), 5);
+        assert_eq!(offset_at(text, &position(0, 4)), 6);
+        assert_eq!(offset_at(text, &position(1, 1)), 9);
+        // Past the end of a line or of the text
+        assert_eq!(offset_at(text, &position(0, 10)), 6);
+        assert_eq!(offset_at(text, &position(5, 0)), text.len());
+        assert_eq!(offset_at("", &position(0, 0)), 0);
+    }
+
+    #[test]
+    fn offsets_with_crlf() {
+        let text = "ab\r\ncd";
+        assert_eq!(offset_at(text, &position(0, 2)), 2);
+        assert_eq!(offset_at(text, &position(1, 0)), 4);
+        assert_eq!(offset_at(text, &position(1, 1)), 5);
+    }
+
+    /// Returns the messages sent by the server
+    fn sent(server: &Server<Vec<u8>>) -> Vec<Value> {
+        let output = String::from_utf8(server.output.borrow().clone()).unwrap();
+        let mut input = output.as_bytes();
+        let mut res = vec![];
+        while let Some(content) = read_message(&mut input).unwrap() {
+            res.push(Value::parse(&content).unwrap());
+        }
+        res
+    }
+
+    #[test]
+    fn incremental_changes() {
+        let mut server = Server::new(vec![]);
+        let open = Value::parse(r#"{"method": "textDocument/didOpen", "params": {"textDocument":
+            {"uri": "file:///a.txt", "languageId": "plaintext", "text": "Un mot.\r\nDeux 𝄞 mots."}}}"#).unwrap();
+        server.handle(&open).unwrap();
+        let change = Value::parse(r#"{"method": "textDocument/didChange", "params": {
+            "textDocument": {"uri": "file:///a.txt"},
+            "contentChanges": [{"range": {"start": {"line": 1, "character": 8},
+                                          "end": {"line": 1, "character": 12}},
+                                "text": "textes"},
+                               {"range": {"start": {"line": 0, "character": 0},
+                                          "end": {"line": 0, "character": 2}},
+                                "text": "Le"}]}}"#).unwrap();
+        server.handle(&change).unwrap();
+        assert_eq!(server.documents["file:///a.txt"].text, "Le mot.\r\nDeux 𝄞 textes.");
+        // Diagnostics are only published once the changes are over
+        assert_eq!(sent(&server).len(), 1);
+        server.publish_changed().unwrap();
+
+        let messages = sent(&server);
+        assert_eq!(messages.len(), 2);
+        // "mots" is after a character taking two UTF-16 code units
+        let diagnostics = messages[0].get("params").get("diagnostics").as_array().unwrap();
+        let ranges: Vec<String> = diagnostics.iter().map(|d| d.get("range").to_string()).collect();
+        assert_eq!(ranges, vec![r#"{"start":{"line":0,"character":3},"end":{"line":0,"character":6}}"#,
+                                r#"{"start":{"line":1,"character":8},"end":{"line":1,"character":12}}"#]);
+        // After the change, nothing is repeated
+        assert_eq!(messages[1].get("params").get("diagnostics").as_array().unwrap().len(), 0);
+    }
+
+    /// Returns the diagnostics of a server that has just opened `text`
+    fn diagnostics(server: &mut Server<Vec<u8>>, text: &str) -> String {
+        let open = Value::object(vec![
+            ("method", "textDocument/didOpen".into()),
+            ("params", Value::object(vec![("textDocument", Value::object(vec![
+                ("uri", "file:///a.txt".into()),
+                ("languageId", "plaintext".into()),
+                ("text", text.into()),
+            ]))])),
+        ]);
+        server.handle(&open).unwrap();
+        server.documents["file:///a.txt"].diagnostics("file:///a.txt").iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn partial_analysis() {
+        let init = Value::parse(r#"{"method": "initialize", "id": 1,
+                                    "params": {"initializationOptions": {"maxDistance": 4}}}"#).unwrap();
+        let text = "Un chat dort, un chat mange.\n\nUn mot ici.\n\nAutre chose.\n\n\
+                    Encore autre chose.\n\nUne souris court, la souris danse.\n";
+        let mut server = Server::new(vec![]);
+        server.handle(&init).unwrap();
+        diagnostics(&mut server, text);
+        assert_eq!(server.documents["file:///a.txt"].occurrences.len(), 8);
+
+        // Adds a repetition of "mot" and two lines in the third paragraph
+        let change = Value::parse(r#"{"method": "textDocument/didChange", "params": {
+            "textDocument": {"uri": "file:///a.txt"},
+            "contentChanges": [{"range": {"start": {"line": 2, "character": 7},
+                                          "end": {"line": 2, "character": 10}},
+                                "text": "mot.\n\nSon mot"}]}}"#).unwrap();
+        server.handle(&change).unwrap();
+        server.publish_changed().unwrap();
+        let document = &server.documents["file:///a.txt"];
+        assert_eq!(document.occurrences.len(), 11);
+        // The last paragraph was not analysed again (its repetition was the fourth one found),
+        // but it moved
+        assert_eq!((document.occurrences[10].line, document.occurrences[10].group), (10, 4));
+
+        // Same diagnostics as an analysis of the whole document
+        let mut other = Server::new(vec![]);
+        other.handle(&init).unwrap();
+        assert_eq!(document.diagnostics("file:///a.txt").iter().map(|d| d.to_string()).collect::<String>(),
+                   diagnostics(&mut other, &document.text));
+
+        // Removes whole lines, in two changes
+        let change = Value::parse(r#"{"method": "textDocument/didChange", "params": {
+            "textDocument": {"uri": "file:///a.txt"},
+            "contentChanges": [{"range": {"start": {"line": 2, "character": 0},
+                                          "end": {"line": 6, "character": 0}},
+                                "text": ""},
+                               {"range": {"start": {"line": 0, "character": 17},
+                                          "end": {"line": 0, "character": 21}},
+                                "text": "chien"}]}}"#).unwrap();
+        server.handle(&change).unwrap();
+        server.publish_changed().unwrap();
+        let document = &server.documents["file:///a.txt"];
+        assert_eq!(document.text, "Un chat dort, un chien mange.\n\nAutre chose.\n\n\
+                                   Encore autre chose.\n\nUne souris court, la souris danse.\n");
+        let mut other = Server::new(vec![]);
+        other.handle(&init).unwrap();
+        assert_eq!(document.diagnostics("file:///a.txt").iter().map(|d| d.to_string()).collect::<String>(),
+                   diagnostics(&mut other, &document.text));
+    }
+
+    #[test]
+    fn file_uris() {
+        assert_eq!(uri_path("file:///home/me/a%20b.txt"), Some(PathBuf::from("/home/me/a b.txt")));
+        assert_eq!(uri_path("file://localhost/%C3%A9t%C3%A9.md"), Some(PathBuf::from("/été.md")));
+        // Not an escape sequence
+        assert_eq!(uri_path("file:///100%"), Some(PathBuf::from("/100%")));
+        assert_eq!(uri_path("untitled:Untitled-1"), None);
+    }
+
+    #[test]
+    fn ignored_words_are_kept() {
+        let dir = env::temp_dir().join(format!("caribon-lsp-test-{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(".caribon-ignore");
+        let _ = fs::remove_file(&file);
+        let uri = format!("file://{}", dir.join("a.txt").display());
+
+        let mut server = Server::new(vec![]);
+        let open = Value::object(vec![
+            ("method", "textDocument/didOpen".into()),
+            ("params", Value::object(vec![("textDocument", Value::object(vec![
+                ("uri", uri.clone().into()),
+                ("languageId", "plaintext".into()),
+                ("text", "Un chat, un chat et un chien, un chien.".into()),
+            ]))])),
+        ]);
+        server.handle(&open).unwrap();
+        assert_eq!(server.documents[&uri].occurrences.len(), 4);
+        let command = Value::object(vec![
+            ("method", "workspace/executeCommand".into()),
+            ("id", 1.into()),
+            ("params", Value::object(vec![("command", "caribon.ignoreWord".into()),
+                                          ("arguments", Value::Array(vec!["chat".into(), uri.clone().into()]))])),
+        ]);
+        server.handle(&command).unwrap();
+        assert_eq!(server.documents[&uri].occurrences.len(), 2);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "chat\n");
+
+        // The configuration doesn't replace it
+        let configuration = Value::parse(r#"{"method": "workspace/didChangeConfiguration",
+            "params": {"settings": {"caribon": {"ignored": ["chien"]}}}}"#).unwrap();
+        server.handle(&configuration).unwrap();
+        assert_eq!(server.documents[&uri].occurrences.len(), 0);
+
+        // Nor does a restart, since it is in the project's file
+        let mut server = Server::new(vec![]);
+        server.handle(&open).unwrap();
+        assert_eq!(server.documents[&uri].occurrences.len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn messages() {
+        let mut input: &[u8] = b"Content-Length: 2\r\nContent-Type: x\r\n\r\n{}Content-Length: 4\r\n\r\nnull";
+        assert_eq!(read_message(&mut input).unwrap(), Some("{}".to_owned()));
+        assert_eq!(read_message(&mut input).unwrap(), Some("null".to_owned()));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+        // Truncated content
+        let mut input: &[u8] = b"Content-Length: 10\r\n\r\n{}";
+        assert!(read_message(&mut input).is_err());
+    }
+}